@@ -1,4 +1,7 @@
 use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+
+use crate::config::TomlConfig;
 
 /// The account name argument parser.
 #[derive(Debug, Parser)]
@@ -35,3 +38,56 @@ pub struct AccountNameFlag {
     #[arg(name = "account_name", value_name = "NAME")]
     pub name: Option<String>,
 }
+
+/// The account names flag parser.
+#[derive(Debug, Default, Parser)]
+pub struct AccountNamesFlag {
+    /// Target several accounts at once.
+    ///
+    /// Account names are separated by commas. Results of all the
+    /// given accounts are merged together, and ids are qualified by
+    /// their account (work:42).
+    #[arg(long = "accounts", value_name = "NAMES", value_delimiter = ',')]
+    #[arg(name = "account_names", conflicts_with_all = ["account_name", "all_accounts"])]
+    pub names: Vec<String>,
+
+    /// Target all the accounts of the configuration.
+    #[arg(
+        long = "all-accounts",
+        name = "all_accounts",
+        conflicts_with = "account_name"
+    )]
+    pub all: bool,
+}
+
+impl AccountNamesFlag {
+    /// Return `true` if several accounts are targeted.
+    pub fn is_multiple(&self) -> bool {
+        self.all || !self.names.is_empty()
+    }
+
+    /// Resolve the account names against the given configuration.
+    ///
+    /// All accounts are sorted by name. Unknown accounts lead to an
+    /// error, and duplicates are removed.
+    pub fn resolve(&self, config: &TomlConfig) -> Result<Vec<String>> {
+        if self.all {
+            let mut names: Vec<String> = config.accounts.keys().cloned().collect();
+            names.sort();
+            return Ok(names);
+        }
+
+        let mut names: Vec<String> = Vec::new();
+
+        for name in &self.names {
+            if !config.accounts.contains_key(name) {
+                return Err(eyre!("cannot find account {name}"));
+            }
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+
+        Ok(names)
+    }
+}
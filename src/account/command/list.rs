@@ -23,7 +23,8 @@ impl AccountListCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing list accounts command");
 
-        let accounts: Accounts = config.accounts.iter().into();
+        let mut accounts: Accounts = config.accounts.iter().into();
+        accounts.set_theme(config.theme(None));
 
         printer.print_table(accounts, self.table_max_width)?;
         Ok(())
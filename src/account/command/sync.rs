@@ -19,7 +19,7 @@ use tracing::info;
 
 use crate::{
     account::arg::name::OptionalAccountNameArg, backend::BackendKind, config::TomlConfig,
-    printer::Printer,
+    printer::Printer, rule,
 };
 
 static MAIN_PROGRESS_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
@@ -91,6 +91,7 @@ impl AccountSyncCommand {
         let (toml_account_config, account_config) =
             config.clone().into_account_configs(account, true)?;
         let account_name = account_config.name.as_str();
+        let dry_run = self.dry_run;
 
         match toml_account_config.sync_kind() {
             Some(BackendKind::Imap) | Some(BackendKind::ImapCache) => {
@@ -104,11 +105,17 @@ impl AccountSyncCommand {
                     .with_prebuilt_credentials()
                     .await?;
                 let imap = BackendBuilder::new(account_config.clone(), imap_ctx);
-                self.sync(printer, account_name, imap).await
+                self.sync(printer, account_name, imap).await?;
             }
             Some(backend) => bail!("backend {backend:?} not supported for synchronization"),
             None => bail!("no backend configured for synchronization"),
         }
+
+        if !dry_run && !rule::rules(&toml_account_config).is_empty() {
+            apply_rules(printer, config, account_name).await?;
+        }
+
+        Ok(())
     }
 
     async fn sync(
@@ -266,3 +273,36 @@ impl AccountSyncCommand {
         Ok(())
     }
 }
+
+/// Apply the rules of the given account on its synchronized cache.
+///
+/// Rules are only applied to envelopes they did not handle yet. The
+/// first time a rule is applied to a folder, its matching envelopes
+/// are recorded as handled without executing its actions, so that
+/// only envelopes added by later synchronizations are handled. Their
+/// actions modify the local cache only: they reach the server at the
+/// next synchronization.
+async fn apply_rules(
+    printer: &mut impl Printer,
+    config: &TomlConfig,
+    account_name: &str,
+) -> Result<()> {
+    let (toml_account_config, account_config) = config
+        .clone()
+        .into_account_configs(Some(account_name), false)?;
+
+    let backend = rule::build_backend(toml_account_config.clone(), account_config.clone()).await?;
+    let history = RuleHistory::new(&account_config)?;
+    let matches = rule::apply_to_new_envelopes(&backend, &history, &toml_account_config).await?;
+
+    for m in matches.iter() {
+        printer.print_log(format!(
+            "Rule {} applied to {} envelope(s) of folder {}",
+            m.rule,
+            m.ids.len(),
+            m.folder
+        ))?;
+    }
+
+    Ok(())
+}
@@ -17,11 +17,19 @@ use email::sendmail::config::SendmailConfig;
 use email::smtp::config::SmtpConfig;
 use email::template::config::TemplateConfig;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
 use crate::{
-    backend::BackendKind, envelope::config::EnvelopeConfig, flag::config::FlagConfig,
-    folder::config::FolderConfig, message::config::MessageConfig,
+    backend::BackendKind,
+    envelope::{column::EnvelopeColumn, config::EnvelopeConfig},
+    flag::config::FlagConfig,
+    folder::{config::FolderConfig, search::SavedSearchConfig},
+    message::{config::MessageConfig, html::HtmlRenderer},
+    rule::RuleConfig,
+    theme::config::ThemeConfig,
 };
 
 #[cfg(feature = "account-sync")]
@@ -65,6 +73,14 @@ pub struct TomlAccountConfig {
     pub flag: Option<FlagConfig>,
     pub message: Option<MessageConfig>,
     pub template: Option<TemplateConfig>,
+    pub theme: Option<ThemeConfig>,
+    /// The saved searches, by name.
+    pub search: Option<HashMap<String, SavedSearchConfig>>,
+    /// The filtering rules, by name.
+    pub rules: Option<HashMap<String, RuleConfig>>,
+    /// Record mutating operations in the undo journal. Defaults to
+    /// false.
+    pub journal: Option<bool>,
 
     #[cfg(feature = "imap")]
     pub imap: Option<ImapConfig>,
@@ -142,6 +158,13 @@ impl TomlAccountConfig {
             .or(self.backend.as_ref())
     }
 
+    pub fn list_envelopes_columns(&self) -> Option<&Vec<EnvelopeColumn>> {
+        self.envelope
+            .as_ref()
+            .and_then(|envelope| envelope.list.as_ref())
+            .and_then(|list| list.columns.as_ref())
+    }
+
     pub fn watch_envelopes_kind(&self) -> Option<&BackendKind> {
         self.envelope
             .as_ref()
@@ -198,6 +221,14 @@ impl TomlAccountConfig {
             .or(self.backend.as_ref())
     }
 
+    pub fn html_renderer(&self) -> HtmlRenderer {
+        self.message
+            .as_ref()
+            .and_then(|message| message.read.as_ref())
+            .and_then(|get| get.html_renderer.clone())
+            .unwrap_or_default()
+    }
+
     pub fn copy_messages_kind(&self) -> Option<&BackendKind> {
         self.message
             .as_ref()
@@ -4,15 +4,21 @@ pub mod config;
 pub(crate) mod wizard;
 
 use color_eyre::Result;
-use comfy_table::{presets, Attribute, Cell, Color, ContentArrangement, Row, Table};
+use comfy_table::{ContentArrangement, Row, Table};
 use serde::Serialize;
 use std::{collections::hash_map::Iter, fmt, ops::Deref};
 
-use crate::printer::{PrintTable, WriteColor};
+use crate::{
+    printer::{PrintTable, WriteColor},
+    theme::config::ThemeConfig,
+};
 
 use self::config::TomlAccountConfig;
 
 /// Represents the printable account.
+///
+/// JSON schema: `{"name": string, "backend": string, "default":
+/// bool}`.
 #[derive(Debug, Default, PartialEq, Eq, Serialize)]
 pub struct Account {
     /// Represents the account name.
@@ -41,63 +47,58 @@ impl fmt::Display for Account {
 
 impl From<Account> for Row {
     fn from(account: Account) -> Self {
-        let mut r = Row::new();
-        r.add_cell(Cell::new(account.name).fg(Color::Green));
-        r.add_cell(Cell::new(account.backend).fg(Color::Blue));
-        r.add_cell(Cell::new(if account.default { "yes" } else { "" }).fg(Color::White));
-        r
+        Row::from(&account)
     }
 }
 impl From<&Account> for Row {
     fn from(account: &Account) -> Self {
-        let mut r = Row::new();
-        r.add_cell(Cell::new(&account.name).fg(Color::Green));
-        r.add_cell(Cell::new(&account.backend).fg(Color::Blue));
-        r.add_cell(Cell::new(if account.default { "yes" } else { "" }).fg(Color::White));
-        r
+        ThemeConfig::default().account_row(&account.name, &account.backend, account.default)
     }
 }
 
 /// Represents the list of printable accounts.
+///
+/// JSON schema: an array of accounts.
 #[derive(Debug, Default, Serialize)]
-pub struct Accounts(pub Vec<Account>);
+#[serde(transparent)]
+pub struct Accounts {
+    pub accounts: Vec<Account>,
+    /// The theme used to display accounts as a table.
+    #[serde(skip)]
+    pub theme: ThemeConfig,
+}
+
+impl Accounts {
+    pub fn set_theme(&mut self, theme: ThemeConfig) {
+        self.theme = theme;
+    }
+}
 
 impl Deref for Accounts {
     type Target = Vec<Account>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.accounts
     }
 }
 
 impl From<Accounts> for Table {
     fn from(accounts: Accounts) -> Self {
-        let mut table = Table::new();
-        table
-            .load_preset(presets::NOTHING)
-            .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(Row::from([
-                Cell::new("NAME").add_attribute(Attribute::Reverse),
-                Cell::new("BACKENDS").add_attribute(Attribute::Reverse),
-                Cell::new("DEFAULT").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(accounts.0.into_iter().map(Row::from));
-        table
+        Table::from(&accounts)
     }
 }
 
 impl From<&Accounts> for Table {
     fn from(accounts: &Accounts) -> Self {
+        let theme = &accounts.theme;
         let mut table = Table::new();
         table
-            .load_preset(presets::NOTHING)
+            .load_preset(theme.preset())
             .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(Row::from([
-                Cell::new("NAME").add_attribute(Attribute::Reverse),
-                Cell::new("BACKENDS").add_attribute(Attribute::Reverse),
-                Cell::new("DEFAULT").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(accounts.0.iter().map(Row::from));
+            .set_header(theme.header(["NAME", "BACKENDS", "DEFAULT"]))
+            .add_rows(accounts.iter().map(|account| {
+                theme.account_row(&account.name, &account.backend, account.default)
+            }));
         table
     }
 }
@@ -166,6 +167,9 @@ impl From<Iter<'_, String, TomlAccountConfig>> for Accounts {
         // sort accounts by name
         accounts.sort_by(|a, b| a.name.partial_cmp(&b.name).unwrap());
 
-        Self(accounts)
+        Self {
+            accounts,
+            theme: Default::default(),
+        }
     }
 }
@@ -2,8 +2,14 @@ pub mod config;
 pub(crate) mod wizard;
 
 use async_trait::async_trait;
-use color_eyre::Result;
-use std::{fmt::Display, ops::Deref, sync::Arc};
+use color_eyre::{eyre::eyre, Result};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    ops::Deref,
+    slice,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 #[cfg(feature = "imap")]
 use email::imap::{ImapContextBuilder, ImapContextSync};
@@ -24,28 +30,51 @@ use email::{
         get::GetEnvelope,
         list::{ListEnvelopes, ListEnvelopesOptions},
         watch::WatchEnvelopes,
-        Id, SingleId,
+        Envelope, Id, SingleId,
     },
     flag::{add::AddFlags, remove::RemoveFlags, set::SetFlags, Flag, Flags},
     folder::{
         add::AddFolder, delete::DeleteFolder, expunge::ExpungeFolder, list::ListFolders,
-        purge::PurgeFolder,
+        purge::PurgeFolder, TRASH,
     },
     message::{
         add::AddMessage,
         copy::CopyMessages,
-        delete::DeleteMessages,
+        delete::{config::DeleteMessageStyle, DeleteMessages},
         get::GetMessages,
         peek::PeekMessages,
         r#move::MoveMessages,
         send::{SendMessage, SendMessageThenSaveCopy},
         Messages,
     },
+    search_query::SearchEmailsQuery,
     AnyResult,
 };
 use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    account::config::TomlAccountConfig,
+    cache::IdMapper,
+    envelope::{
+        arg::ids::IdExpr,
+        details::EnvelopeDetails,
+        query::BATCH_SIZE,
+        thread::{ThreadLinks, ThreadedEnvelopes},
+        Envelopes, Mailbox,
+    },
+    folder::stats::FolderStats,
+    printer::{Print, WriteColor},
+    undo::{to_journal_flags, Journal, JournalEntry, JournalOp},
+};
+
+/// The maximum amount of characters of envelope body previews.
+const PREVIEW_LEN: usize = 120;
 
-use crate::{account::config::TomlAccountConfig, cache::IdMapper, envelope::Envelopes};
+/// Above this amount of unknown envelopes, their folder is listed
+/// once instead of getting envelopes one by one.
+const GET_ENVELOPES_MAX: usize = 10;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -610,9 +639,57 @@ impl AsRef<Option<SendmailContextSync>> for BackendContext {
     }
 }
 
+/// The alias of a message before and after being moved or copied to
+/// another folder.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FollowedAlias {
+    /// The alias of the message in the source folder.
+    pub alias: String,
+    /// The alias of the message in the target folder, if it could be
+    /// found.
+    pub new_alias: Option<String>,
+}
+
+impl Display for FollowedAlias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.new_alias {
+            Some(new_alias) => write!(f, "{} → {new_alias}", self.alias),
+            None => write!(f, "{} → ?", self.alias),
+        }
+    }
+}
+
+/// The list of followed aliases.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FollowedAliases(pub Vec<FollowedAlias>);
+
+impl Deref for FollowedAliases {
+    type Target = Vec<FollowedAlias>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Print for FollowedAliases {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        for alias in self.iter() {
+            writeln!(writer, "{alias}")?;
+        }
+        Ok(writer.reset()?)
+    }
+}
+
 pub struct Backend {
     pub toml_account_config: Arc<TomlAccountConfig>,
     pub backend: email::backend::Backend<BackendContext>,
+    /// The session of mutating operations recorded in the journal,
+    /// unset when the journal is disabled, which is the default.
+    journal_session: Option<String>,
+    /// The envelopes listed by the backend, by folder and by backend
+    /// id, reused to follow messages and to record their flags. A
+    /// full listing of a folder replaces its envelopes.
+    envelopes: Mutex<HashMap<String, HashMap<String, Envelope>>>,
 }
 
 impl Backend {
@@ -635,12 +712,187 @@ impl Backend {
 
         with_features(&mut backend_builder);
 
+        let journal_session = toml_account_config
+            .journal
+            .unwrap_or_default()
+            .then(|| Uuid::new_v4().to_string());
+
         Ok(Self {
             toml_account_config: toml_account_config.clone(),
             backend: backend_builder.build().await?,
+            journal_session,
+            envelopes: Mutex::default(),
         })
     }
 
+    /// Stop recording mutating operations in the journal.
+    pub fn without_journal(mut self) -> Self {
+        self.journal_session = None;
+        self
+    }
+
+    /// Record the given operation in the journal of the account.
+    ///
+    /// Failures are logged, they do not interrupt the operation.
+    fn journal(&self, folder: &str, ids: impl IntoIterator<Item = impl ToString>, op: JournalOp) {
+        let Some(session) = &self.journal_session else {
+            return;
+        };
+
+        let account = &self.backend.account_config.name;
+        let entry = JournalEntry::new(session, account, folder, ids, op);
+
+        if let Err(err) = Journal::new(account).and_then(|journal| journal.append(entry)) {
+            warn!("cannot record operation in journal: {err}");
+            debug!("{err:?}");
+        }
+    }
+
+    /// Get the flags of the given envelopes, by alias, in order to
+    /// record them in the journal before changing them.
+    ///
+    /// Envelopes are got at once, see [`Self::get_envelopes`]. Since
+    /// their flags are about to change, they are forgotten afterwards.
+    async fn get_journal_flags(
+        &self,
+        folder: &str,
+        aliases: &[usize],
+        ids: &[String],
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut flags = BTreeMap::new();
+
+        if self.journal_session.is_some() {
+            let mut envelopes = self.get_envelopes(folder, ids).await;
+
+            for (alias, id) in aliases.iter().zip(ids) {
+                match envelopes.remove(id) {
+                    Some(envelope) => {
+                        flags.insert(alias.to_string(), to_journal_flags(envelope.flags));
+                    }
+                    None => debug!("cannot get flags of envelope {id} from folder {folder}"),
+                }
+            }
+        }
+
+        self.forget_envelopes(folder, Some(ids));
+        flags
+    }
+
+    /// Remember the given envelopes of the given folder.
+    fn cache_envelopes<'a>(&self, folder: &str, envelopes: impl IntoIterator<Item = &'a Envelope>) {
+        let mut cache = self
+            .envelopes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let cache = cache.entry(folder.to_owned()).or_default();
+        for envelope in envelopes {
+            cache.insert(envelope.id.clone(), envelope.clone());
+        }
+    }
+
+    /// Forget the remembered envelopes of the given folder matching
+    /// the given backend ids, or all of them when no id is given.
+    fn forget_envelopes(&self, folder: &str, ids: Option<&[String]>) {
+        let mut cache = self
+            .envelopes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match ids {
+            None => {
+                cache.remove(folder);
+            }
+            Some(ids) => {
+                if let Some(cache) = cache.get_mut(folder) {
+                    for id in ids {
+                        cache.remove(id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// List all envelopes of the given folder, and remember them
+    /// instead of the ones previously remembered.
+    async fn list_backend_envelopes(&self, folder: &str) -> Result<email::envelope::Envelopes> {
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
+        };
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        // envelopes gone from the folder are not kept forever
+        self.forget_envelopes(folder, None);
+        self.cache_envelopes(folder, envelopes.iter());
+        Ok(envelopes)
+    }
+
+    /// Get the envelopes matching the given backend ids, by backend
+    /// id.
+    ///
+    /// Envelopes already listed are reused. Other ones are got one by
+    /// one, or by listing the folder once when they are more than
+    /// [`GET_ENVELOPES_MAX`]. Envelopes that cannot be found are
+    /// omitted.
+    async fn get_envelopes(&self, folder: &str, ids: &[String]) -> HashMap<String, Envelope> {
+        let mut envelopes = HashMap::with_capacity(ids.len());
+        let mut missing = Vec::new();
+
+        {
+            let cache = self
+                .envelopes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let cache = cache.get(folder);
+
+            for id in ids {
+                match cache.and_then(|cache| cache.get(id)) {
+                    Some(envelope) => {
+                        envelopes.insert(id.clone(), envelope.clone());
+                    }
+                    None => missing.push(id),
+                }
+            }
+        }
+
+        if missing.len() > GET_ENVELOPES_MAX {
+            match self.list_backend_envelopes(folder).await {
+                Ok(listed) => {
+                    let mut listed: HashMap<_, _> = listed
+                        .iter()
+                        .map(|envelope| (envelope.id.as_str(), envelope))
+                        .collect();
+                    for id in missing {
+                        if let Some(envelope) = listed.remove(id.as_str()) {
+                            envelopes.insert(id.clone(), envelope.clone());
+                        }
+                    }
+                }
+                Err(err) => {
+                    debug!("cannot list envelopes from folder {folder}: {err}");
+                    debug!("{err:?}");
+                }
+            }
+        } else {
+            for id in missing {
+                match self
+                    .backend
+                    .get_envelope(folder, &SingleId::from(id.as_str()))
+                    .await
+                {
+                    Ok(envelope) => {
+                        envelopes.insert(id.clone(), envelope);
+                    }
+                    Err(err) => {
+                        debug!("cannot get envelope {id} from folder {folder}: {err}");
+                        debug!("{err:?}");
+                    }
+                }
+            }
+        }
+
+        envelopes
+    }
+
     fn build_id_mapper(
         &self,
         folder: &str,
@@ -682,56 +934,405 @@ impl Backend {
         let backend_kind = self.toml_account_config.list_envelopes_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
         let envelopes = self.backend.list_envelopes(folder, opts).await?;
-        let envelopes =
+        self.cache_envelopes(folder, envelopes.iter());
+        let mut envelopes =
             Envelopes::from_backend(&self.backend.account_config, &id_mapper, envelopes)?;
+        for envelope in envelopes.iter_mut() {
+            envelope.folder = Some(folder.to_owned());
+        }
         Ok(envelopes)
     }
 
+    /// Resolve envelope id expressions into envelope ids of the
+    /// given folder.
+    ///
+    /// Ranges only keep ids known by the folder, "first" and "last"
+    /// are resolved by sorting envelopes by date, and Message-IDs are
+    /// resolved by looking up envelopes of the whole folder. Resolved
+    /// ids are deduplicated, in order.
+    pub async fn resolve_ids(&self, folder: &str, exprs: &[IdExpr]) -> Result<Vec<usize>> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+
+        let mut ids = Vec::with_capacity(exprs.len());
+        let mut envelopes = None;
+
+        for expr in exprs {
+            // the account qualifier already selected the backend
+            let expr = expr.unqualified();
+
+            match expr {
+                IdExpr::Id(id) => {
+                    ids.push(*id);
+                }
+                IdExpr::Range(start, end) if matches!(id_mapper, IdMapper::Dummy) => {
+                    // without aliases, ranges are checked against the
+                    // ids of the folder, so that unknown ids are never
+                    // sent to the backend
+                    if envelopes.is_none() {
+                        envelopes = Some(self.list_backend_envelopes(folder).await?);
+                    }
+
+                    let known: HashSet<&str> = envelopes
+                        .iter()
+                        .flat_map(|envelopes| envelopes.iter())
+                        .map(|envelope| envelope.id.as_str())
+                        .collect();
+
+                    for id in *start..=*end {
+                        if known.contains(id.to_string().as_str()) {
+                            ids.push(id);
+                        }
+                    }
+                }
+                IdExpr::Range(start, end) => {
+                    for id in *start..=*end {
+                        if id_mapper.contains_alias(id)? {
+                            ids.push(id);
+                        }
+                    }
+                }
+                IdExpr::First | IdExpr::Last => {
+                    let order = if *expr == IdExpr::First {
+                        "asc"
+                    } else {
+                        "desc"
+                    };
+                    let query = format!("order by date {order}")
+                        .parse::<SearchEmailsQuery>()
+                        .map_err(|err| eyre!("cannot build sort query: {err}"))?;
+                    let opts = ListEnvelopesOptions {
+                        page: 0,
+                        page_size: 1,
+                        query: Some(query),
+                    };
+                    let envelope = self
+                        .backend
+                        .list_envelopes(folder, opts)
+                        .await?
+                        .first()
+                        .map(|envelope| envelope.id.clone())
+                        .ok_or_else(|| eyre!("cannot resolve {expr}: folder {folder} is empty"))?;
+                    ids.push(parse_alias(id_mapper.get_or_create_alias(envelope)?)?);
+                }
+                IdExpr::MessageId(message_id) => {
+                    if envelopes.is_none() {
+                        envelopes = Some(self.list_backend_envelopes(folder).await?);
+                    }
+
+                    let id = envelopes
+                        .iter()
+                        .flat_map(|envelopes| envelopes.iter())
+                        .find(|envelope| {
+                            envelope.message_id.trim_matches(['<', '>']) == message_id.as_str()
+                        })
+                        .map(|envelope| envelope.id.clone())
+                        .ok_or_else(|| eyre!("cannot find envelope {expr} in folder {folder}"))?;
+                    ids.push(parse_alias(id_mapper.get_or_create_alias(id)?)?);
+                }
+                IdExpr::Account(..) => {
+                    return Err(eyre!("cannot resolve {expr}: nested accounts"));
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        ids.retain(|id| seen.insert(*id));
+
+        Ok(ids)
+    }
+
+    /// Search envelope ids of the given folder matching the given
+    /// query, across all pages.
+    pub async fn search_ids(&self, folder: &str, query: SearchEmailsQuery) -> Result<Vec<usize>> {
+        let envelopes = self.search_envelopes(folder, query).await?;
+        Ok(envelopes.into_iter().map(|(id, _)| id).collect())
+    }
+
+    /// Search envelopes of the given folder matching the given query,
+    /// across all pages, as pairs of envelope id and Message-ID.
+    pub async fn search_envelopes(
+        &self,
+        folder: &str,
+        query: SearchEmailsQuery,
+    ) -> Result<Vec<(usize, Option<String>)>> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: Some(query),
+        };
+
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        self.cache_envelopes(folder, envelopes.iter());
+
+        envelopes
+            .iter()
+            .map(|envelope| {
+                let id = parse_alias(id_mapper.get_or_create_alias(&envelope.id)?)?;
+                let message_id =
+                    Some(envelope.message_id.clone()).filter(|message_id| !message_id.is_empty());
+                Ok((id, message_id))
+            })
+            .collect()
+    }
+
+    /// Sort the given envelope ids of the given folder by date, oldest
+    /// first.
+    ///
+    /// Envelopes are got at once, see [`Self::get_envelopes`].
+    /// Envelopes that cannot be found are kept last, in their order.
+    pub async fn sort_ids_by_date(&self, folder: &str, ids: &mut [usize]) -> Result<()> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let backend_ids = id_mapper.get_ids(ids.iter())?;
+        let envelopes = self.get_envelopes(folder, &backend_ids).await;
+
+        let mut dates: HashMap<usize, _> = HashMap::with_capacity(ids.len());
+        for (id, backend_id) in ids.iter().zip(&backend_ids) {
+            if let Some(envelope) = envelopes.get(backend_id) {
+                dates.insert(*id, envelope.date);
+            }
+        }
+
+        ids.sort_by_key(|id| match dates.get(id) {
+            Some(date) => (false, Some(*date)),
+            None => (true, None),
+        });
+
+        Ok(())
+    }
+
+    /// Resolve a single envelope id expression into an envelope id
+    /// of the given folder.
+    pub async fn resolve_id(&self, folder: &str, expr: &IdExpr) -> Result<usize> {
+        self.resolve_ids(folder, slice::from_ref(expr))
+            .await?
+            .first()
+            .copied()
+            .ok_or_else(|| eyre!("cannot find envelope {expr} in folder {folder}"))
+    }
+
+    /// Remove aliases of envelopes that do not exist anymore in the
+    /// given folder.
+    ///
+    /// Returns the amount of removed aliases.
+    pub async fn prune_id_mapper(&self, folder: &str) -> Result<usize> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
+        };
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        let count = id_mapper.prune(envelopes.iter().map(|envelope| envelope.id.as_str()))?;
+        Ok(count)
+    }
+
+    /// Compute the statistics of the given folder.
+    ///
+    /// Maildir folders are read from the file system, IMAP folders
+    /// are requested with STATUS and Notmuch folders are counted by
+    /// the database. Other backends fall back to listing the whole
+    /// folder, which costs as much as `envelope list --page-size 0`.
+    pub async fn folder_stats(&self, folder: &str) -> Result<FolderStats> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+
+        #[cfg(feature = "maildir")]
+        if let Some(path) = self.maildir_path(folder, backend_kind) {
+            return FolderStats::from_maildir(&path);
+        }
+
+        #[cfg(feature = "imap")]
+        if let (Some(BackendKind::Imap), Some(ctx)) = (backend_kind, &self.backend.context.imap) {
+            return self.imap_folder_stats(ctx, folder).await;
+        }
+
+        #[cfg(feature = "notmuch")]
+        if let (Some(BackendKind::Notmuch), Some(ctx)) =
+            (backend_kind, &self.backend.context.notmuch)
+        {
+            return self.notmuch_folder_stats(ctx, folder).await;
+        }
+
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
+        };
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        Ok(FolderStats::from_envelopes(envelopes.iter()))
+    }
+
+    /// Compute the statistics of the envelopes of the given folder
+    /// matching the given query, by listing them.
+    pub async fn search_stats(
+        &self,
+        folder: &str,
+        query: SearchEmailsQuery,
+    ) -> Result<FolderStats> {
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: Some(query),
+        };
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        Ok(FolderStats::from_envelopes(envelopes.iter()))
+    }
+
+    /// Request the statistics of the given IMAP folder with a single
+    /// STATUS command, without selecting it.
+    #[cfg(feature = "imap")]
+    async fn imap_folder_stats(&self, ctx: &ImapContextSync, folder: &str) -> Result<FolderStats> {
+        use imap_types::status::{StatusDataItem, StatusDataItemName};
+
+        let folder = self.backend.account_config.get_folder_alias(folder);
+        let folder = utf7_imap::encode_utf7_imap(folder);
+        let items = [
+            StatusDataItemName::Messages,
+            StatusDataItemName::Unseen,
+            StatusDataItemName::Recent,
+        ];
+        let status = ctx.client().await.status(folder.as_str(), items).await?;
+
+        let mut stats = FolderStats::default();
+        for item in status {
+            match item {
+                StatusDataItem::Messages(count) => stats.total = count as usize,
+                StatusDataItem::Unseen(count) => stats.unseen = count as usize,
+                StatusDataItem::Recent(count) => stats.recent = Some(count as usize),
+                _ => (),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Count the messages of the given Notmuch folder with the
+    /// database, without loading their envelopes.
+    #[cfg(feature = "notmuch")]
+    async fn notmuch_folder_stats(
+        &self,
+        ctx: &NotmuchContextSync,
+        folder: &str,
+    ) -> Result<FolderStats> {
+        let folder = self.backend.account_config.get_folder_alias(folder);
+        let ctx = ctx.lock().await;
+        let db = ctx.open_db()?;
+
+        let count = |query: String| -> Result<usize> {
+            Ok(db.create_query(&query)?.count_messages()? as usize)
+        };
+        let total = count(format!("folder:{folder:?}"))?;
+        let unseen = count(format!("folder:{folder:?} and tag:unread"))?;
+        db.close()?;
+
+        Ok(FolderStats {
+            total,
+            unseen,
+            ..Default::default()
+        })
+    }
+
+    /// Find the directory of the given folder, if the given backend
+    /// feature is handled by a Maildir.
+    ///
+    /// Maildir++ folders are prefixed with a dot, and the inbox is
+    /// the root directory itself.
+    #[cfg(feature = "maildir")]
+    fn maildir_path(
+        &self,
+        folder: &str,
+        backend_kind: Option<&BackendKind>,
+    ) -> Option<std::path::PathBuf> {
+        let Some(BackendKind::Maildir) = backend_kind else {
+            return None;
+        };
+
+        let root = &self.toml_account_config.maildir.as_ref()?.root_dir;
+        let folder = self.backend.account_config.get_folder_alias(folder);
+
+        let path = if folder.eq_ignore_ascii_case(email::folder::INBOX) {
+            root.clone()
+        } else {
+            let maildirpp = root.join(format!(".{folder}"));
+            if maildirpp.is_dir() {
+                maildirpp
+            } else {
+                root.join(folder)
+            }
+        };
+
+        path.join("cur").is_dir().then_some(path)
+    }
+
     pub async fn add_flags(&self, folder: &str, ids: &[usize], flags: &Flags) -> Result<()> {
         let backend_kind = self.toml_account_config.add_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.add_flags(folder, &ids, flags).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend.add_flags(folder, &backend_ids, flags).await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
     pub async fn add_flag(&self, folder: &str, ids: &[usize], flag: Flag) -> Result<()> {
         let backend_kind = self.toml_account_config.add_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.add_flag(folder, &ids, flag).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend.add_flag(folder, &backend_ids, flag).await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
     pub async fn set_flags(&self, folder: &str, ids: &[usize], flags: &Flags) -> Result<()> {
         let backend_kind = self.toml_account_config.set_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.set_flags(folder, &ids, flags).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend.set_flags(folder, &backend_ids, flags).await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
     pub async fn set_flag(&self, folder: &str, ids: &[usize], flag: Flag) -> Result<()> {
         let backend_kind = self.toml_account_config.set_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.set_flag(folder, &ids, flag).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend.set_flag(folder, &backend_ids, flag).await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
     pub async fn remove_flags(&self, folder: &str, ids: &[usize], flags: &Flags) -> Result<()> {
         let backend_kind = self.toml_account_config.remove_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.remove_flags(folder, &ids, flags).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend
+            .remove_flags(folder, &backend_ids, flags)
+            .await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
     pub async fn remove_flag(&self, folder: &str, ids: &[usize], flag: Flag) -> Result<()> {
         let backend_kind = self.toml_account_config.remove_flags_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.remove_flag(folder, &ids, flag).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+        let prev = self.get_journal_flags(folder, ids, &backend_ids).await;
+        let backend_ids = Id::multiple(backend_ids);
+        self.backend.remove_flag(folder, &backend_ids, flag).await?;
+        self.journal(folder, ids, JournalOp::Flags { prev });
         Ok(())
     }
 
@@ -767,6 +1368,202 @@ impl Backend {
         Ok(msgs)
     }
 
+    /// Get the details of the envelope matching the given id.
+    ///
+    /// Backends only expose the main headers of envelopes. When the
+    /// message is stored in a Maildir, its other headers are read
+    /// from the message file, without reading its body. Otherwise
+    /// details are limited to the envelope, see [`EnvelopeDetails`].
+    pub async fn get_envelope_details(&self, folder: &str, id: usize) -> Result<EnvelopeDetails> {
+        let backend_kind = self.toml_account_config.get_envelope_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let backend_id = id_mapper.get_id(id)?;
+        let envelope = self
+            .backend
+            .get_envelope(folder, &SingleId::from(backend_id.as_str()))
+            .await?;
+
+        #[allow(unused_mut)]
+        let mut headers = None;
+
+        #[cfg(feature = "maildir")]
+        if let Some(path) = self.maildir_path(folder, backend_kind) {
+            match read_maildir_headers(&path, &backend_id) {
+                Ok(found) => headers = found,
+                Err(err) => {
+                    debug!("cannot read headers of message {backend_id}: {err}");
+                    debug!("{err:?}");
+                }
+            }
+        }
+
+        let (raw_headers, size) = match &headers {
+            Some((raw_headers, size)) => (Some(raw_headers.as_slice()), Some(*size)),
+            None => (None, None),
+        };
+        let parsed_headers =
+            raw_headers.and_then(|raw| mail_parser::MessageParser::new().parse_headers(raw));
+
+        Ok(EnvelopeDetails::new(
+            &self.backend.account_config,
+            id,
+            folder,
+            &envelope,
+            parsed_headers.as_ref(),
+            size,
+        ))
+    }
+
+    /// Fill the envelopes details that backends do not provide
+    /// (Cc, size, attachment indicator and body preview).
+    ///
+    /// Those details are only available in messages, which are
+    /// peeked for the occasion from the folder of each envelope, or
+    /// from the given folder when unknown.
+    pub async fn load_envelopes_details(
+        &self,
+        folder: &str,
+        envelopes: &mut Envelopes,
+    ) -> Result<()> {
+        let mut idxs_by_folder: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, envelope) in envelopes.iter().enumerate() {
+            let folder = envelope.folder.as_deref().unwrap_or(folder);
+            idxs_by_folder
+                .entry(folder.to_owned())
+                .or_default()
+                .push(idx);
+        }
+
+        for (folder, idxs) in idxs_by_folder {
+            let ids = idxs
+                .iter()
+                .map(|idx| parse_alias(envelopes[*idx].id.clone()))
+                .collect::<Result<Vec<_>>>()?;
+
+            let msgs = self.peek_messages(&folder, &ids).await?;
+
+            for (idx, msg) in idxs.into_iter().zip(msgs.to_vec()) {
+                let envelope = &mut envelopes[idx];
+                envelope.size = Some(msg.raw()?.len());
+
+                let msg = msg.parsed()?;
+
+                envelope.cc = Mailbox::from_address(msg.cc());
+
+                envelope.has_attachment = Some(msg.attachment_count() > 0);
+
+                envelope.preview = msg
+                    .body_preview(PREVIEW_LEN)
+                    .map(|preview| preview.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Group the given envelopes by conversation.
+    ///
+    /// Envelopes are linked by their Message-ID and In-Reply-To
+    /// headers, which are part of backend envelopes, and by their
+    /// References header, see [`Self::get_references`]. Envelopes
+    /// listed by [`Self::list_envelopes`] are reused, other ones are
+    /// got from the folder of each envelope, or from the given folder
+    /// when unknown.
+    pub async fn thread_envelopes(
+        &self,
+        folder: &str,
+        envelopes: Envelopes,
+    ) -> Result<ThreadedEnvelopes> {
+        let mut idxs_by_folder: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, envelope) in envelopes.iter().enumerate() {
+            let folder = envelope.folder.as_deref().unwrap_or(folder);
+            idxs_by_folder.entry(folder).or_default().push(idx);
+        }
+
+        let mut links = vec![ThreadLinks::default(); envelopes.len()];
+        let strip =
+            |id: &str| Some(id.trim_matches(['<', '>']).to_owned()).filter(|id| !id.is_empty());
+
+        for (folder, idxs) in idxs_by_folder {
+            let backend_kind = self.toml_account_config.list_envelopes_kind();
+            let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+            let aliases: Vec<&str> = idxs.iter().map(|idx| envelopes[*idx].id.as_str()).collect();
+            let ids = id_mapper.get_ids(aliases)?;
+            let backend_envelopes = self.get_envelopes(folder, &ids).await;
+            let references = self.get_references(folder, &ids).await;
+
+            for ((idx, id), references) in idxs.into_iter().zip(&ids).zip(references) {
+                if let Some(envelope) = backend_envelopes.get(id) {
+                    links[idx] = ThreadLinks {
+                        message_id: strip(&envelope.message_id),
+                        in_reply_to: envelope.in_reply_to.as_deref().and_then(strip),
+                        references,
+                    };
+                }
+            }
+        }
+
+        Ok(ThreadedEnvelopes::new(envelopes, &links))
+    }
+
+    /// Get the References header of the messages matching the given
+    /// backend ids, as Message-IDs without angle brackets.
+    ///
+    /// Backends do not expose this header in envelopes. Maildir
+    /// messages are read up to their header section, other messages
+    /// are peeked at once. Messages that cannot be read get no
+    /// reference.
+    async fn get_references(&self, folder: &str, ids: &[String]) -> Vec<Vec<String>> {
+        let parse = |raw: &[u8]| -> Vec<String> {
+            mail_parser::MessageParser::new()
+                .parse_headers(raw)
+                .and_then(|msg| {
+                    let references = msg.references().as_text_list()?;
+                    Some(references.into_iter().map(ToOwned::to_owned).collect())
+                })
+                .unwrap_or_default()
+        };
+
+        #[cfg(feature = "maildir")]
+        if let Some(path) =
+            self.maildir_path(folder, self.toml_account_config.list_envelopes_kind())
+        {
+            return ids
+                .iter()
+                .map(|id| match read_maildir_headers(&path, id) {
+                    Ok(headers) => headers
+                        .map(|(headers, _)| parse(&headers))
+                        .unwrap_or_default(),
+                    Err(err) => {
+                        debug!("cannot read headers of message {id}: {err}");
+                        debug!("{err:?}");
+                        Vec::new()
+                    }
+                })
+                .collect();
+        }
+
+        let mut references = match self
+            .backend
+            .peek_messages(folder, &Id::multiple(ids.to_vec()))
+            .await
+        {
+            Ok(msgs) => msgs
+                .to_vec()
+                .into_iter()
+                .map(|msg| msg.raw().map(parse).unwrap_or_default())
+                .collect(),
+            Err(err) => {
+                debug!("cannot peek messages from folder {folder}: {err}");
+                debug!("{err:?}");
+                Vec::new()
+            }
+        };
+
+        references.resize(ids.len(), Vec::new());
+        references
+    }
+
     pub async fn get_messages(&self, folder: &str, ids: &[usize]) -> Result<Messages> {
         let backend_kind = self.toml_account_config.get_messages_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
@@ -775,41 +1572,382 @@ impl Backend {
         Ok(msgs)
     }
 
+    /// Copy the given messages to the given folder.
+    ///
+    /// Messages are copied by batches of [`BATCH_SIZE`], then
+    /// followed to the target folder, see [`Self::find_followed_ids`].
     pub async fn copy_messages(
         &self,
         from_folder: &str,
         to_folder: &str,
         ids: &[usize],
-    ) -> Result<()> {
-        let backend_kind = self.toml_account_config.move_messages_kind();
-        let id_mapper = self.build_id_mapper(from_folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend
-            .copy_messages(from_folder, to_folder, &ids)
-            .await?;
-        Ok(())
+    ) -> Result<FollowedAliases> {
+        let backend_kind = self.toml_account_config.copy_messages_kind();
+        let from_id_mapper = self.build_id_mapper(from_folder, backend_kind)?;
+        let to_id_mapper = self.build_id_mapper(to_folder, backend_kind)?;
+        let aliases: Vec<String> = ids.iter().map(ToString::to_string).collect();
+        let ids = from_id_mapper.get_ids(&aliases)?;
+        let message_ids = self.get_message_ids(from_folder, &ids).await;
+
+        let mut copied = 0;
+        let mut res: AnyResult<()> = Ok(());
+
+        for chunk in ids.chunks(BATCH_SIZE) {
+            res = self
+                .backend
+                .copy_messages(from_folder, to_folder, &Id::multiple(chunk.to_vec()))
+                .await;
+            if res.is_err() {
+                break;
+            }
+            copied += chunk.len();
+        }
+
+        // messages copied before a failure are still followed
+        let new_ids = self
+            .find_followed_ids(to_folder, &message_ids[..copied])
+            .await;
+        let mut followed_aliases = Vec::with_capacity(copied);
+
+        for (alias, new_id) in aliases.into_iter().zip(new_ids) {
+            let new_alias = match new_id {
+                Some(new_id) => Some(to_id_mapper.get_or_create_alias(new_id)?),
+                None => None,
+            };
+            followed_aliases.push(FollowedAlias { alias, new_alias });
+        }
+
+        self.journal(
+            from_folder,
+            followed_aliases.iter().map(|a| &a.alias),
+            JournalOp::Copy {
+                target: to_folder.to_owned(),
+                new_ids: followed_aliases
+                    .iter()
+                    .map(|a| a.new_alias.clone())
+                    .collect(),
+            },
+        );
+
+        res?;
+        Ok(FollowedAliases(followed_aliases))
     }
 
+    /// Move the given messages to the given folder.
+    ///
+    /// Messages are moved by batches of [`BATCH_SIZE`], then
+    /// followed to the target folder, see [`Self::find_followed_ids`].
     pub async fn move_messages(
         &self,
         from_folder: &str,
         to_folder: &str,
         ids: &[usize],
-    ) -> Result<()> {
+    ) -> Result<FollowedAliases> {
         let backend_kind = self.toml_account_config.move_messages_kind();
-        let id_mapper = self.build_id_mapper(from_folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend
-            .move_messages(from_folder, to_folder, &ids)
-            .await?;
-        Ok(())
+        let from_id_mapper = self.build_id_mapper(from_folder, backend_kind)?;
+        let to_id_mapper = self.build_id_mapper(to_folder, backend_kind)?;
+        let aliases: Vec<String> = ids.iter().map(ToString::to_string).collect();
+        let ids = from_id_mapper.get_ids(&aliases)?;
+        let message_ids = self.get_message_ids(from_folder, &ids).await;
+
+        let mut moved = 0;
+        let mut res: AnyResult<()> = Ok(());
+
+        for chunk in ids.chunks(BATCH_SIZE) {
+            res = self
+                .backend
+                .move_messages(from_folder, to_folder, &Id::multiple(chunk.to_vec()))
+                .await;
+            if res.is_err() {
+                break;
+            }
+            moved += chunk.len();
+        }
+
+        self.forget_envelopes(from_folder, Some(&ids[..moved]));
+
+        // messages moved before a failure are still followed
+        let new_ids = self
+            .find_followed_ids(to_folder, &message_ids[..moved])
+            .await;
+        let mut followed_aliases = Vec::with_capacity(moved);
+
+        for ((alias, id), new_id) in aliases.into_iter().zip(&ids).zip(new_ids) {
+            from_id_mapper.remove_id(id)?;
+            let new_alias = match new_id {
+                Some(new_id) => Some(to_id_mapper.get_or_create_alias(new_id)?),
+                None => None,
+            };
+            followed_aliases.push(FollowedAlias { alias, new_alias });
+        }
+
+        self.journal(
+            from_folder,
+            followed_aliases.iter().map(|a| &a.alias),
+            JournalOp::Move {
+                target: to_folder.to_owned(),
+                new_ids: followed_aliases
+                    .iter()
+                    .map(|a| a.new_alias.clone())
+                    .collect(),
+            },
+        );
+
+        res?;
+        Ok(FollowedAliases(followed_aliases))
+    }
+
+    /// Get the Message-ID of all envelopes of the given folder,
+    /// without angle brackets.
+    pub async fn list_message_ids(&self, folder: &str) -> Result<HashSet<String>> {
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
+        };
+
+        let message_ids = self
+            .backend
+            .list_envelopes(folder, opts)
+            .await?
+            .iter()
+            .map(|envelope| envelope.message_id.trim_matches(['<', '>']).to_owned())
+            .filter(|message_id| !message_id.is_empty())
+            .collect();
+
+        Ok(message_ids)
+    }
+
+    /// Get the Message-ID of each envelope matching the given backend
+    /// ids, see [`Self::get_envelopes`].
+    ///
+    /// Envelopes that cannot be found or without Message-ID get none,
+    /// so they are simply not followed.
+    async fn get_message_ids(&self, folder: &str, ids: &[String]) -> Vec<Option<String>> {
+        let envelopes = self.get_envelopes(folder, ids).await;
+
+        ids.iter()
+            .map(|id| {
+                let envelope = envelopes.get(id)?;
+                Some(envelope.message_id.clone()).filter(|message_id| !message_id.is_empty())
+            })
+            .collect()
+    }
+
+    /// Find the new backend ids of messages that have just been moved
+    /// or copied to the given folder, by Message-ID.
+    ///
+    /// Backends do not return the ids of copied messages, so they are
+    /// searched by Message-ID in the target folder, see
+    /// [`Self::search_message_ids`]. Messages sharing a Message-ID
+    /// are given distinct ids, see [`follow_message_ids`].
+    async fn find_followed_ids(
+        &self,
+        folder: &str,
+        message_ids: &[Option<String>],
+    ) -> Vec<Option<String>> {
+        // the folder changed since it was last listed
+        self.forget_envelopes(folder, None);
+
+        let searched: HashSet<&str> = message_ids.iter().flatten().map(String::as_str).collect();
+
+        if searched.is_empty() {
+            return vec![None; message_ids.len()];
+        }
+
+        match self.search_message_ids(folder, &searched).await {
+            Ok(candidates) => follow_message_ids(message_ids, candidates),
+            Err(err) => {
+                debug!("cannot search message ids in folder {folder}: {err}");
+                debug!("{err:?}");
+                vec![None; message_ids.len()]
+            }
+        }
+    }
+
+    /// Search the backend ids of envelopes of the given folder
+    /// matching the given Message-IDs, by Message-ID.
+    ///
+    /// IMAP folders are searched by header and Notmuch folders are
+    /// queried by id, so that only matching envelopes are fetched.
+    /// Other backends fall back to listing the whole folder.
+    async fn search_message_ids(
+        &self,
+        folder: &str,
+        message_ids: &HashSet<&str>,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        #[allow(unused_variables)]
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+
+        #[cfg(feature = "imap")]
+        if let (Some(BackendKind::Imap), Some(ctx)) = (backend_kind, &self.backend.context.imap) {
+            return self.imap_search_message_ids(ctx, folder, message_ids).await;
+        }
+
+        #[cfg(feature = "notmuch")]
+        if let (Some(BackendKind::Notmuch), Some(ctx)) =
+            (backend_kind, &self.backend.context.notmuch)
+        {
+            return self
+                .notmuch_search_message_ids(ctx, folder, message_ids)
+                .await;
+        }
+
+        let mut candidates: HashMap<String, Vec<String>> = HashMap::new();
+        for envelope in self.list_backend_envelopes(folder).await?.iter() {
+            if message_ids.contains(envelope.message_id.as_str()) {
+                candidates
+                    .entry(envelope.message_id.clone())
+                    .or_default()
+                    .push(envelope.id.clone());
+            }
+        }
+
+        Ok(candidates)
     }
 
+    /// Search the UIDs of the given IMAP folder matching the given
+    /// Message-IDs, with one UID SEARCH by Message-ID.
+    #[cfg(feature = "imap")]
+    async fn imap_search_message_ids(
+        &self,
+        ctx: &ImapContextSync,
+        folder: &str,
+        message_ids: &HashSet<&str>,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        use imap_types::{core::AString, search::SearchKey};
+
+        let folder = self.backend.account_config.get_folder_alias(folder);
+        let folder = utf7_imap::encode_utf7_imap(folder);
+        let mut client = ctx.client().await;
+        client.select_mailbox(folder.as_str()).await?;
+
+        let mut candidates = HashMap::new();
+        for message_id in message_ids {
+            let header = AString::try_from("Message-ID")
+                .map_err(|err| eyre!("cannot build IMAP search key: {err}"))?;
+            let value = AString::try_from(*message_id)
+                .map_err(|err| eyre!("invalid Message-ID {message_id}: {err}"))?;
+            let uids = client
+                .search_uids([SearchKey::Header(header, value)])
+                .await?;
+            let uids = uids.into_iter().map(|uid| uid.to_string()).collect();
+            candidates.insert(message_id.to_string(), uids);
+        }
+
+        Ok(candidates)
+    }
+
+    /// Search the given Message-IDs in the given Notmuch folder.
+    ///
+    /// Notmuch envelopes are identified by their Message-ID, so the
+    /// database only needs to tell whether they belong to the folder.
+    #[cfg(feature = "notmuch")]
+    async fn notmuch_search_message_ids(
+        &self,
+        ctx: &NotmuchContextSync,
+        folder: &str,
+        message_ids: &HashSet<&str>,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let folder = self.backend.account_config.get_folder_alias(folder);
+        let ctx = ctx.lock().await;
+        let db = ctx.open_db()?;
+
+        let mut candidates = HashMap::new();
+        for message_id in message_ids {
+            let id = message_id.trim_matches(['<', '>']);
+            let query = format!("folder:{folder:?} and id:{id:?}");
+            if db.create_query(&query)?.count_messages()? > 0 {
+                candidates.insert(message_id.to_string(), vec![id.to_owned()]);
+            }
+        }
+        db.close()?;
+
+        Ok(candidates)
+    }
+
+    /// Delete the given messages.
+    ///
+    /// Messages are moved to the trash, unless the folder is the
+    /// trash itself or the delete style is set to flag, in which case
+    /// the deleted flag is added to their envelope.
     pub async fn delete_messages(&self, folder: &str, ids: &[usize]) -> Result<()> {
         let backend_kind = self.toml_account_config.delete_messages_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
-        let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.delete_messages(folder, &ids).await?;
+        let backend_ids = id_mapper.get_ids(ids)?;
+
+        let account_config = &self.backend.account_config;
+        let trash = account_config.get_folder_alias(TRASH);
+        let flag_style = self
+            .toml_account_config
+            .message
+            .as_ref()
+            .and_then(|c| c.delete.as_ref())
+            .and_then(|c| c.style.as_ref())
+            .is_some_and(|style| matches!(style, DeleteMessageStyle::Flag));
+        let to_trash = !flag_style && account_config.get_folder_alias(folder) != trash;
+
+        let message_ids = if to_trash {
+            self.get_message_ids(folder, &backend_ids).await
+        } else {
+            Vec::new()
+        };
+
+        let mut deleted = 0;
+        let mut res: AnyResult<()> = Ok(());
+
+        for chunk in backend_ids.chunks(BATCH_SIZE) {
+            res = self
+                .backend
+                .delete_messages(folder, &Id::multiple(chunk.to_vec()))
+                .await;
+            if res.is_err() {
+                break;
+            }
+            deleted += chunk.len();
+        }
+
+        let backend_ids = &backend_ids[..deleted];
+        self.forget_envelopes(folder, Some(backend_ids));
+
+        // messages moved to the trash are followed like moved ones,
+        // so that they can be restored
+        let op = if to_trash {
+            let trash_id_mapper = self.build_id_mapper(&trash, backend_kind)?;
+            let mut new_ids = Vec::with_capacity(backend_ids.len());
+
+            let followed_ids = self
+                .find_followed_ids(&trash, &message_ids[..deleted])
+                .await;
+
+            for (id, new_id) in backend_ids.iter().zip(followed_ids) {
+                id_mapper.remove_id(id)?;
+                new_ids.push(match new_id {
+                    Some(new_id) => Some(trash_id_mapper.get_or_create_alias(new_id)?),
+                    None => None,
+                });
+            }
+
+            JournalOp::Delete {
+                trash: Some(trash),
+                new_ids,
+            }
+        } else {
+            JournalOp::Delete {
+                trash: None,
+                new_ids: Vec::new(),
+            }
+        };
+
+        self.journal(folder, &ids[..deleted], op);
+
+        res?;
+        Ok(())
+    }
+
+    pub async fn expunge_folder(&self, folder: &str) -> Result<()> {
+        self.backend.expunge_folder(folder).await?;
+        self.journal(folder, Vec::<String>::new(), JournalOp::Expunge);
         Ok(())
     }
 
@@ -824,6 +1962,82 @@ impl Backend {
     }
 }
 
+/// Read the headers of the message matching the given id from the
+/// given Maildir folder, together with the size of the message file.
+///
+/// Messages are searched in both `cur` and `new` directories, by
+/// their file name without Maildir info. Only the header section is
+/// read, up to the first blank line.
+#[cfg(feature = "maildir")]
+fn read_maildir_headers(
+    folder: &std::path::Path,
+    id: &str,
+) -> std::io::Result<Option<(Vec<u8>, usize)>> {
+    use std::{
+        fs::{self, File},
+        io::{BufRead, BufReader},
+    };
+
+    for dir in ["cur", "new"] {
+        for entry in fs::read_dir(folder.join(dir))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let entry_id = name.split_once([':', ';']).map_or(&*name, |(id, _)| id);
+            if entry_id != id {
+                continue;
+            }
+
+            let size = entry.metadata()?.len() as usize;
+            let mut reader = BufReader::new(File::open(entry.path())?);
+            let mut headers = Vec::new();
+
+            loop {
+                let start = headers.len();
+                if reader.read_until(b'\n', &mut headers)? == 0 {
+                    break;
+                }
+                if matches!(&headers[start..], b"\n" | b"\r\n") {
+                    break;
+                }
+            }
+
+            return Ok(Some((headers, size)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Pair the given Message-IDs with the given candidate backend ids,
+/// found by Message-ID in the target folder of a copy or a move.
+///
+/// A Message-ID may match several envelopes, for example when the
+/// target folder already holds a copy of the message, or when several
+/// copied messages share it. Each message is then given a distinct
+/// id, the greatest numeric ones first since IMAP UIDs grow with
+/// arrival, so that newly added envelopes are preferred.
+fn follow_message_ids(
+    message_ids: &[Option<String>],
+    mut candidates: HashMap<String, Vec<String>>,
+) -> Vec<Option<String>> {
+    for ids in candidates.values_mut() {
+        ids.sort_by_key(|id| id.parse::<u64>().ok());
+    }
+
+    message_ids
+        .iter()
+        .map(|message_id| candidates.get_mut(message_id.as_deref()?)?.pop())
+        .collect()
+}
+
+/// Parse an alias as an envelope id.
+fn parse_alias(alias: String) -> Result<usize> {
+    alias
+        .parse()
+        .map_err(|err| eyre!("cannot parse alias {alias} as envelope id: {err}"))
+}
+
 impl Deref for Backend {
     type Target = email::backend::Backend<BackendContext>;
 
@@ -831,3 +2045,68 @@ impl Deref for Backend {
         &self.backend
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::follow_message_ids;
+
+    fn by_message_id(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(message_id, ids)| {
+                let ids = ids.iter().map(ToString::to_string).collect();
+                (message_id.to_string(), ids)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn follow_unique_message_ids() {
+        let message_ids = [Some("<a@x>".into()), None, Some("<b@x>".into())];
+        let candidates = by_message_id(&[("<a@x>", &["3"]), ("<b@x>", &["4"])]);
+
+        assert_eq!(
+            follow_message_ids(&message_ids, candidates),
+            vec![Some("3".into()), None, Some("4".into())],
+        );
+    }
+
+    #[test]
+    fn follow_missing_message_ids() {
+        let message_ids = [Some("<a@x>".into())];
+
+        assert_eq!(follow_message_ids(&message_ids, HashMap::new()), vec![None]);
+    }
+
+    #[test]
+    fn follow_duplicated_message_ids() {
+        // the target already held a copy with uid 2
+        let message_ids = [Some("<a@x>".into())];
+        let candidates = by_message_id(&[("<a@x>", &["10", "2"])]);
+
+        assert_eq!(
+            follow_message_ids(&message_ids, candidates),
+            vec![Some("10".into())],
+        );
+
+        // two copied messages share the same Message-ID
+        let message_ids = [Some("<a@x>".into()), Some("<a@x>".into())];
+        let candidates = by_message_id(&[("<a@x>", &["7", "9", "2"])]);
+
+        assert_eq!(
+            follow_message_ids(&message_ids, candidates),
+            vec![Some("9".into()), Some("7".into())],
+        );
+
+        // more messages than matching envelopes
+        let message_ids = [Some("<a@x>".into()), Some("<a@x>".into())];
+        let candidates = by_message_id(&[("<a@x>", &["5"])]);
+
+        assert_eq!(
+            follow_message_ids(&message_ids, candidates),
+            vec![Some("5".into()), None],
+        );
+    }
+}
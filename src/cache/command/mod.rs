@@ -0,0 +1,28 @@
+mod prune;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::prune::CachePruneCommand;
+
+/// Manage caches.
+///
+/// Some backends do not expose short ids, like Maildir or
+/// Notmuch. For those backends, himalaya maintains a local cache
+/// mapping backend ids to short aliases. This subcommand allows you
+/// to manage it.
+#[derive(Debug, Subcommand)]
+pub enum CacheSubcommand {
+    #[command(alias = "gc")]
+    Prune(CachePruneCommand),
+}
+
+impl CacheSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Prune(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
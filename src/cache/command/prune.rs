@@ -0,0 +1,58 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::backend::feature::BackendFeatureSource;
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+};
+
+/// Prune the id mapper cache of a folder.
+///
+/// This command removes aliases whose backend ids do not exist
+/// anymore in the given folder, for example because messages have
+/// been moved or deleted from another client.
+#[derive(Debug, Parser)]
+pub struct CachePruneCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl CachePruneCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing prune cache command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            list_envelopes_kind,
+            |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let count = backend.prune_id_mapper(folder).await?;
+
+        printer.print(format!(
+            "{count} stale alias(es) successfully pruned from {folder}!"
+        ))
+    }
+}
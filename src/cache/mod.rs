@@ -1,16 +1,22 @@
 pub mod arg;
+pub mod command;
 
 use color_eyre::{eyre::eyre, eyre::Context, Result};
 use dirs::data_dir;
 use email::account::config::AccountConfig;
-use sled::{Config, Db};
-use std::collections::HashSet;
+use sled::{transaction::ConflictableTransactionResult, Batch, Config, Db, Transactional, Tree};
+use std::{collections::HashSet, path::Path};
 use tracing::debug;
 
+/// The name of the sled tree holding the reverse alias → id
+/// mapping. The forward id → alias mapping lives in the default tree
+/// of the database, which keeps existing id mappers compatible.
+const ALIASES_TREE: &str = "aliases";
+
 #[derive(Debug)]
 pub enum IdMapper {
     Dummy,
-    Mapper(Db),
+    Mapper { ids: Db, aliases: Tree },
 }
 
 impl IdMapper {
@@ -22,13 +28,38 @@ impl IdMapper {
             .join(".id-mappers")
             .join(format!("{digest:x}"));
 
-        let conn = Config::new()
-            .path(&db_path)
+        Self::open(&db_path)
+    }
+
+    /// Open the id mapper database at the given path.
+    fn open(db_path: &Path) -> Result<Self> {
+        let ids = Config::new()
+            .path(db_path)
             .idgen_persist_interval(1)
             .open()
             .with_context(|| format!("cannot open id mapper database at {db_path:?}"))?;
 
-        Ok(Self::Mapper(conn))
+        let aliases = ids
+            .open_tree(ALIASES_TREE)
+            .with_context(|| format!("cannot open id mapper aliases at {db_path:?}"))?;
+
+        // id mappers created before the reverse tree existed need to
+        // be indexed once, in a single batch so that an interrupted
+        // indexing does not leave a partial reverse tree behind
+        if aliases.is_empty() && !ids.is_empty() {
+            debug!("indexing aliases of id mapper at {db_path:?}…");
+            let mut batch = Batch::default();
+            for entry in ids.iter() {
+                let (id, alias) = entry.context("cannot read id mapper entry")?;
+                batch.insert(alias, id);
+            }
+            aliases
+                .apply_batch(batch)
+                .context("cannot index id mapper aliases")?;
+            aliases.flush().context("cannot flush id mapper aliases")?;
+        }
+
+        Ok(Self::Mapper { ids, aliases })
     }
 
     pub fn create_alias<I>(&self, id: I) -> Result<String>
@@ -38,17 +69,24 @@ impl IdMapper {
         let id = id.as_ref();
         match self {
             Self::Dummy => Ok(id.to_owned()),
-            Self::Mapper(conn) => {
+            Self::Mapper { ids, aliases } => {
                 debug!("creating alias for id {id}…");
 
-                let alias = conn
+                let alias = ids
                     .generate_id()
                     .with_context(|| format!("cannot create alias for id {id}"))?
                     .to_string();
                 debug!("created alias {alias} for id {id}");
 
-                conn.insert(&id, alias.as_bytes())
-                    .with_context(|| format!("cannot insert alias {alias} for id {id}"))?;
+                (&**ids, aliases)
+                    .transaction(|(ids, aliases)| -> ConflictableTransactionResult<()> {
+                        if let Some(prev_alias) = ids.insert(id, alias.as_bytes())? {
+                            aliases.remove(prev_alias)?;
+                        }
+                        aliases.insert(alias.as_bytes(), id)?;
+                        Ok(())
+                    })
+                    .map_err(|err| eyre!("cannot insert alias {alias} for id {id}: {err:?}"))?;
 
                 Ok(alias)
             }
@@ -62,10 +100,10 @@ impl IdMapper {
         let id = id.as_ref();
         match self {
             Self::Dummy => Ok(id.to_owned()),
-            Self::Mapper(conn) => {
+            Self::Mapper { ids, .. } => {
                 debug!("getting alias for id {id}…");
 
-                let alias = conn
+                let alias = ids
                     .get(id)
                     .with_context(|| format!("cannot get alias for id {id}"))?;
 
@@ -94,20 +132,13 @@ impl IdMapper {
 
         match self {
             Self::Dummy => Ok(alias.to_string()),
-            Self::Mapper(conn) => {
+            Self::Mapper { aliases, .. } => {
                 debug!("getting id from alias {alias}…");
 
-                let id = conn
-                    .iter()
-                    .flat_map(|entry| entry)
-                    .find_map(|(entry_id, entry_alias)| {
-                        if entry_alias.as_ref() == alias.as_bytes() {
-                            let entry_id = String::from_utf8_lossy(entry_id.as_ref());
-                            Some(entry_id.to_string())
-                        } else {
-                            None
-                        }
-                    })
+                let id = aliases
+                    .get(&alias)
+                    .with_context(|| format!("cannot get id from alias {alias}"))?
+                    .map(|id| String::from_utf8_lossy(id.as_ref()).to_string())
                     .ok_or_else(|| eyre!("cannot get id from alias {alias}"))?;
                 debug!("found id {id} from alias {alias}");
 
@@ -117,28 +148,181 @@ impl IdMapper {
     }
 
     pub fn get_ids(&self, aliases: impl IntoIterator<Item = impl ToString>) -> Result<Vec<String>> {
-        let aliases: Vec<String> = aliases.into_iter().map(|alias| alias.to_string()).collect();
+        aliases
+            .into_iter()
+            .map(|alias| self.get_id(alias))
+            .collect()
+    }
+
+    /// Return `true` if the given alias is known by the mapper.
+    ///
+    /// A dummy mapper knows every alias, since aliases are ids.
+    pub fn contains_alias<A>(&self, alias: A) -> Result<bool>
+    where
+        A: ToString,
+    {
+        let alias = alias.to_string();
+
+        match self {
+            Self::Dummy => Ok(true),
+            Self::Mapper { aliases, .. } => aliases
+                .contains_key(&alias)
+                .with_context(|| format!("cannot check existence of alias {alias}")),
+        }
+    }
 
+    /// Remove the alias of the given id, in both directions.
+    pub fn remove_id<I>(&self, id: I) -> Result<()>
+    where
+        I: AsRef<str>,
+    {
+        let id = id.as_ref();
         match self {
-            Self::Dummy => Ok(aliases),
-            Self::Mapper(conn) => {
-                let aliases: HashSet<&str> = aliases.iter().map(|alias| alias.as_str()).collect();
-                let ids: Vec<String> = conn
-                    .iter()
-                    .flat_map(|entry| entry)
-                    .filter_map(|(entry_id, entry_alias)| {
-                        let alias = String::from_utf8_lossy(entry_alias.as_ref());
-                        if aliases.contains(alias.as_ref()) {
-                            let entry_id = String::from_utf8_lossy(entry_id.as_ref());
-                            Some(entry_id.to_string())
-                        } else {
-                            None
+            Self::Dummy => Ok(()),
+            Self::Mapper { ids, aliases } => {
+                debug!("removing alias of id {id}…");
+
+                (&**ids, aliases)
+                    .transaction(|(ids, aliases)| -> ConflictableTransactionResult<()> {
+                        if let Some(alias) = ids.remove(id)? {
+                            aliases.remove(alias)?;
                         }
+                        Ok(())
                     })
+                    .map_err(|err| eyre!("cannot remove alias of id {id}: {err:?}"))?;
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove aliases whose ids are not part of the given existing
+    /// ids anymore.
+    ///
+    /// Returns the amount of removed aliases.
+    pub fn prune(&self, existing_ids: impl IntoIterator<Item = impl AsRef<str>>) -> Result<usize> {
+        match self {
+            Self::Dummy => Ok(0),
+            Self::Mapper { ids, .. } => {
+                let existing_ids: HashSet<String> = existing_ids
+                    .into_iter()
+                    .map(|id| id.as_ref().to_owned())
                     .collect();
 
-                Ok(ids)
+                let mut stale_ids = Vec::new();
+                for entry in ids.iter() {
+                    let (id, _) = entry.context("cannot read id mapper entry")?;
+                    let id = String::from_utf8_lossy(id.as_ref()).to_string();
+                    if !existing_ids.contains(&id) {
+                        stale_ids.push(id);
+                    }
+                }
+
+                debug!("pruning {} stale alias(es)…", stale_ids.len());
+
+                for id in &stale_ids {
+                    self.remove_id(id)?;
+                }
+
+                ids.flush().context("cannot flush id mapper database")?;
+
+                Ok(stale_ids.len())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, path::PathBuf};
+
+    use super::IdMapper;
+
+    fn db_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "himalaya-test-id-mapper-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn alias_to_id_and_back() {
+        let path = db_path("roundtrip");
+        let mapper = IdMapper::open(&path).unwrap();
+
+        let alias = mapper.create_alias("id-a").unwrap();
+        assert_eq!(mapper.get_or_create_alias("id-a").unwrap(), alias);
+        assert_eq!(mapper.get_id(&alias).unwrap(), "id-a");
+        assert!(mapper.contains_alias(&alias).unwrap());
+
+        let other = mapper.get_or_create_alias("id-b").unwrap();
+        assert_ne!(other, alias);
+        assert_eq!(
+            mapper.get_ids([&alias, &other]).unwrap(),
+            vec!["id-a", "id-b"]
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn recreated_alias_replaces_previous_one() {
+        let path = db_path("recreate");
+        let mapper = IdMapper::open(&path).unwrap();
+
+        let prev = mapper.create_alias("id").unwrap();
+        let next = mapper.create_alias("id").unwrap();
+        assert_ne!(prev, next);
+        assert!(!mapper.contains_alias(&prev).unwrap());
+        assert_eq!(mapper.get_id(&next).unwrap(), "id");
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn remove_and_prune_ids() {
+        let path = db_path("prune");
+        let mapper = IdMapper::open(&path).unwrap();
+
+        let a = mapper.create_alias("id-a").unwrap();
+        let b = mapper.create_alias("id-b").unwrap();
+        let c = mapper.create_alias("id-c").unwrap();
+
+        mapper.remove_id("id-a").unwrap();
+        assert!(!mapper.contains_alias(&a).unwrap());
+        assert!(mapper.get_id(&a).is_err());
+
+        assert_eq!(mapper.prune(["id-b"]).unwrap(), 1);
+        assert!(mapper.contains_alias(&b).unwrap());
+        assert!(!mapper.contains_alias(&c).unwrap());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn index_aliases_of_legacy_mapper() {
+        let path = db_path("legacy");
+        {
+            let db = sled::open(&path).unwrap();
+            db.insert("id-a", "1").unwrap();
+            db.insert("id-b", "2").unwrap();
+            db.flush().unwrap();
+        }
+
+        let mapper = IdMapper::open(&path).unwrap();
+        assert_eq!(mapper.get_id("1").unwrap(), "id-a");
+        assert_eq!(mapper.get_id("2").unwrap(), "id-b");
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn dummy_mapper_is_identity() {
+        let mapper = IdMapper::Dummy;
+        assert_eq!(mapper.get_or_create_alias("id").unwrap(), "id");
+        assert_eq!(mapper.get_id("id").unwrap(), "id");
+        assert!(mapper.contains_alias("anything").unwrap());
+    }
+}
@@ -4,6 +4,7 @@ use std::path::PathBuf;
 
 use crate::{
     account::command::AccountSubcommand,
+    cache::command::CacheSubcommand,
     completion::command::CompletionGenerateCommand,
     config::{self, TomlConfig},
     envelope::command::EnvelopeSubcommand,
@@ -14,8 +15,11 @@ use crate::{
         attachment::command::AttachmentSubcommand, command::MessageSubcommand,
         template::command::TemplateSubcommand,
     },
-    output::{ColorFmt, OutputFmt},
+    output::{template::OutputTemplate, ColorFmt, OutputFmt},
     printer::Printer,
+    rule::command::RuleSubcommand,
+    status::command::StatusCommand,
+    undo::command::UndoCommand,
 };
 
 #[derive(Parser, Debug)]
@@ -44,7 +48,19 @@ pub struct Cli {
     ///
     /// The possible values are:
     ///
-    ///  - json: output will be in a form of a JSON-compatible object
+    ///  - json: output will be in a form of a JSON-compatible object.
+    ///    Listing commands output an array of objects: folders {name,
+    ///    desc} or accounts {name, backend, default}. Envelopes are
+    ///    listed as a page {page, page_size, count, total, has_more,
+    ///    items}, items being envelopes {id, flags, subject, from, to,
+    ///    date, folder}
+    ///
+    ///  - ndjson: same as json, except that lists are written one
+    ///    object per line
+    ///
+    ///  - csv, tsv: output will be in a form of comma or tab
+    ///    separated values, with a header line. Nested fields are
+    ///    flattened using dots (from.addr)
     ///
     ///  - plain: output will be in a form of either a plain text or
     ///    table, depending on the command
@@ -52,6 +68,19 @@ pub struct Cli {
     #[arg(value_name = "FORMAT", value_enum, default_value_t = Default::default())]
     pub output: OutputFmt,
 
+    /// Customize the output using a template.
+    ///
+    /// The template is rendered once per item (envelope, folder…),
+    /// followed by a line break. Placeholders are serialized field
+    /// names enclosed in braces, nested fields are reached using dots
+    /// ({from.addr}). Escapes \t, \n and \\ are interpreted, and
+    /// literal braces are written {{ and }}. Takes precedence over
+    /// the output format.
+    ///
+    /// Example: '{id}\t{from.addr}\t{subject}'
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    pub format: Option<OutputTemplate>,
+
     /// Control when to use colors
     ///
     /// The default setting is 'auto', which means himalaya will try
@@ -122,6 +151,19 @@ pub enum HimalayaCommand {
     #[command(alias = "templates", alias = "tpls", alias = "tpl")]
     Template(TemplateSubcommand),
 
+    #[command(subcommand)]
+    #[command(alias = "caches")]
+    Cache(CacheSubcommand),
+
+    #[command(alias = "st")]
+    Status(StatusCommand),
+
+    #[command(subcommand)]
+    #[command(alias = "rules")]
+    Rule(RuleSubcommand),
+
+    Undo(UndoCommand),
+
     #[command(arg_required_else_help = true)]
     #[command(alias = "manuals", alias = "mans")]
     Manual(ManualGenerateCommand),
@@ -162,6 +204,22 @@ impl HimalayaCommand {
                 let config = TomlConfig::from_paths_or_default(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
+            Self::Cache(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Status(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Rule(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Undo(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
             Self::Manual(cmd) => cmd.execute(printer).await,
             Self::Completion(cmd) => cmd.execute().await,
         }
@@ -18,7 +18,7 @@ use tracing::debug;
 
 #[cfg(feature = "account-sync")]
 use crate::backend::BackendKind;
-use crate::{account::config::TomlAccountConfig, wizard_warn};
+use crate::{account::config::TomlAccountConfig, theme::config::ThemeConfig, wizard_warn};
 
 /// Represents the user config file.
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
@@ -29,6 +29,7 @@ pub struct TomlConfig {
     pub signature: Option<String>,
     pub signature_delim: Option<String>,
     pub downloads_dir: Option<PathBuf>,
+    pub theme: Option<ThemeConfig>,
     pub accounts: HashMap<String, TomlAccountConfig>,
 }
 
@@ -159,6 +160,15 @@ impl TomlConfig {
             .filter(|p| p.exists())
     }
 
+    /// Get the theme of the given account, merged with the global
+    /// one.
+    pub fn theme(&self, toml_account_config: Option<&TomlAccountConfig>) -> ThemeConfig {
+        ThemeConfig::merge(
+            self.theme.as_ref(),
+            toml_account_config.and_then(|c| c.theme.as_ref()),
+        )
+    }
+
     pub fn into_toml_account_config(
         &self,
         account_name: Option<&str>,
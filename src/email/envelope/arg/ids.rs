@@ -1,17 +1,364 @@
 use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+use std::{fmt, str::FromStr};
+
+use crate::envelope::arg::query::EnvelopesQueryFlags;
+
+/// The maximum amount of ids an id range can span.
+pub const MAX_RANGE_LEN: usize = 10_000;
 
 /// The envelope id argument parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdArg {
     /// The envelope id.
+    ///
+    /// The id can be an envelope id, a Message-ID enclosed in angle
+    /// brackets (<abc@host>), "first" for the oldest envelope or
+    /// "last" for the most recent one. The id can be qualified by an
+    /// account (work:42), as printed by multi-account listings.
     #[arg(value_name = "ID", required = true)]
-    pub id: usize,
+    pub id: IdExpr,
 }
 
 /// The envelopes ids arguments parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdsArgs {
     /// The list of envelopes ids.
+    ///
+    /// Each id can be an envelope id, an inclusive range of ids
+    /// (1-20, up to 10000 ids), a Message-ID enclosed in angle
+    /// brackets (<abc@host>), "first" for the oldest envelope or
+    /// "last" for the most recent one. Ids can also be separated by
+    /// commas (5,7,9-12), and qualified by an account (work:42).
     #[arg(value_name = "ID", required = true)]
-    pub ids: Vec<usize>,
+    pub ids: Vec<IdExprs>,
+}
+
+impl EnvelopeIdsArgs {
+    /// Return the id expressions of all arguments.
+    pub fn ids(&self) -> Vec<IdExpr> {
+        IdExprs::flatten(&self.ids)
+    }
+}
+
+/// The envelopes ids or query arguments parser.
+#[derive(Debug, Parser)]
+pub struct EnvelopeIdsOrQueryArgs {
+    /// The list of envelopes ids.
+    ///
+    /// Each id can be an envelope id, an inclusive range of ids
+    /// (1-20, up to 10000 ids), a Message-ID enclosed in angle
+    /// brackets (<abc@host>), "first" for the oldest envelope or
+    /// "last" for the most recent one. Ids can also be separated by
+    /// commas (5,7,9-12), and qualified by an account (work:42).
+    #[arg(value_name = "ID", conflicts_with = "query")]
+    pub ids: Vec<IdExprs>,
+
+    #[command(flatten)]
+    pub query: EnvelopesQueryFlags,
+}
+
+impl EnvelopeIdsOrQueryArgs {
+    /// Return the id expressions of all arguments.
+    pub fn ids(&self) -> Vec<IdExpr> {
+        IdExprs::flatten(&self.ids)
+    }
+}
+
+/// A comma-separated list of envelope id expressions, as given by a
+/// single argument.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdExprs(pub Vec<IdExpr>);
+
+impl IdExprs {
+    /// Concatenate the id expressions of the given lists.
+    pub fn flatten(exprs: &[IdExprs]) -> Vec<IdExpr> {
+        exprs.iter().flat_map(|exprs| exprs.0.clone()).collect()
+    }
+}
+
+impl FromStr for IdExprs {
+    type Err = String;
+
+    fn from_str(exprs: &str) -> Result<Self, Self::Err> {
+        IdExpr::parse_many(exprs).map(Self)
+    }
+}
+
+/// The envelope id expression.
+///
+/// Expressions are resolved against the folder they apply on, see
+/// [`crate::backend::Backend::resolve_ids`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum IdExpr {
+    /// A plain envelope id.
+    Id(usize),
+    /// An inclusive range of envelope ids.
+    Range(usize, usize),
+    /// A Message-ID, without its angle brackets.
+    MessageId(String),
+    /// The oldest envelope of the folder.
+    First,
+    /// The most recent envelope of the folder.
+    Last,
+    /// An expression qualified by an account, like `work:42`.
+    Account(String, Box<IdExpr>),
+}
+
+impl IdExpr {
+    /// Parse a comma-separated list of id expressions.
+    ///
+    /// Commas enclosed in angle brackets belong to Message-IDs, they
+    /// do not separate expressions.
+    pub fn parse_many(exprs: &str) -> Result<Vec<Self>, String> {
+        let mut parsed = Vec::new();
+        let mut start = 0;
+        let mut in_message_id = false;
+
+        for (i, c) in exprs.char_indices() {
+            match c {
+                '<' => in_message_id = true,
+                '>' => in_message_id = false,
+                ',' if !in_message_id => {
+                    parsed.push(exprs[start..i].parse()?);
+                    start = i + 1;
+                }
+                _ => (),
+            }
+        }
+
+        parsed.push(exprs[start..].parse()?);
+        Ok(parsed)
+    }
+
+    /// Return the expression without its account qualifier.
+    pub fn unqualified(&self) -> &Self {
+        match self {
+            Self::Account(_, expr) => expr,
+            expr => expr,
+        }
+    }
+
+    /// Find the name of the account targeted by the given account
+    /// flag and id expressions.
+    ///
+    /// Expressions qualified by an account select it, unless the
+    /// account is explicitly given. All qualified expressions must
+    /// target the same account.
+    pub fn account_name<'a>(
+        account: Option<&'a str>,
+        exprs: impl IntoIterator<Item = &'a IdExpr>,
+    ) -> Result<Option<&'a str>> {
+        let mut name = account;
+
+        for expr in exprs {
+            if let Self::Account(qualifier, _) = expr {
+                match name {
+                    None => name = Some(qualifier),
+                    Some(name) if name == qualifier => (),
+                    Some(name) => {
+                        return Err(eyre!(
+                            "cannot use id {expr} from account {qualifier} with account {name}"
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(name)
+    }
+}
+
+impl FromStr for IdExpr {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expr = expr.trim();
+
+        if let Some(message_id) = expr.strip_prefix('<').and_then(|e| e.strip_suffix('>')) {
+            if message_id.is_empty() {
+                return Err(String::from("Message-ID cannot be empty"));
+            }
+            return Ok(Self::MessageId(message_id.to_owned()));
+        }
+
+        if let Some((account, id)) = expr.split_once(':') {
+            let account = account.trim();
+            if account.is_empty() {
+                return Err(format!(
+                    "invalid envelope id {expr}: account cannot be empty"
+                ));
+            }
+            return match id.parse()? {
+                Self::Account(..) => Err(format!("invalid envelope id {expr}: too many accounts")),
+                id => Ok(Self::Account(account.to_owned(), Box::new(id))),
+            };
+        }
+
+        if expr.eq_ignore_ascii_case("first") {
+            return Ok(Self::First);
+        }
+
+        if expr.eq_ignore_ascii_case("last") {
+            return Ok(Self::Last);
+        }
+
+        if let Some((start, end)) = expr.split_once('-') {
+            let start = parse_id(start)?;
+            let end = parse_id(end)?;
+            if start > end {
+                return Err(format!("invalid id range {expr}: {start} > {end}"));
+            }
+            if end - start >= MAX_RANGE_LEN {
+                return Err(format!(
+                    "invalid id range {expr}: cannot span more than {MAX_RANGE_LEN} ids"
+                ));
+            }
+            return Ok(Self::Range(start, end));
+        }
+
+        parse_id(expr).map(Self::Id)
+    }
+}
+
+impl fmt::Display for IdExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Range(start, end) => write!(f, "{start}-{end}"),
+            Self::MessageId(message_id) => write!(f, "<{message_id}>"),
+            Self::First => write!(f, "first"),
+            Self::Last => write!(f, "last"),
+            Self::Account(account, expr) => write!(f, "{account}:{expr}"),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<usize, String> {
+    id.trim()
+        .parse()
+        .map_err(|err| format!("invalid envelope id {id}: {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdExpr, IdExprs, MAX_RANGE_LEN};
+
+    fn parse(expr: &str) -> Result<IdExpr, String> {
+        expr.parse()
+    }
+
+    #[test]
+    fn id() {
+        assert_eq!(parse("42"), Ok(IdExpr::Id(42)));
+        assert_eq!(parse(" 42 "), Ok(IdExpr::Id(42)));
+        assert!(parse("abc").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(parse("1-20"), Ok(IdExpr::Range(1, 20)));
+        assert_eq!(parse("5 - 5"), Ok(IdExpr::Range(5, 5)));
+        assert!(parse("20-1").is_err());
+        assert!(parse("1-").is_err());
+        assert!(parse("1-2-3").is_err());
+    }
+
+    #[test]
+    fn range_len() {
+        let max = format!("1-{MAX_RANGE_LEN}");
+        assert_eq!(parse(&max), Ok(IdExpr::Range(1, MAX_RANGE_LEN)));
+
+        let too_long = format!("1-{}", MAX_RANGE_LEN + 1);
+        assert!(parse(&too_long).is_err());
+        assert!(parse("0-18446744073709551615").is_err());
+    }
+
+    #[test]
+    fn message_id() {
+        assert_eq!(
+            parse("<abc@host>"),
+            Ok(IdExpr::MessageId(String::from("abc@host")))
+        );
+        assert!(parse("<>").is_err());
+    }
+
+    #[test]
+    fn first_last() {
+        assert_eq!(parse("first"), Ok(IdExpr::First));
+        assert_eq!(parse("LAST"), Ok(IdExpr::Last));
+    }
+
+    #[test]
+    fn account() {
+        assert_eq!(
+            parse("work:42"),
+            Ok(IdExpr::Account(
+                String::from("work"),
+                Box::new(IdExpr::Id(42))
+            ))
+        );
+        assert_eq!(
+            parse("work:1-3"),
+            Ok(IdExpr::Account(
+                String::from("work"),
+                Box::new(IdExpr::Range(1, 3))
+            ))
+        );
+        assert!(parse(":42").is_err());
+        assert!(parse("a:b:42").is_err());
+    }
+
+    #[test]
+    fn parse_many() {
+        assert_eq!(
+            IdExpr::parse_many("5,7,9-12"),
+            Ok(vec![IdExpr::Id(5), IdExpr::Id(7), IdExpr::Range(9, 12)])
+        );
+        assert!(IdExpr::parse_many("5,,7").is_err());
+    }
+
+    #[test]
+    fn parse_many_message_ids() {
+        assert_eq!(
+            IdExpr::parse_many("<a,b@host>,5"),
+            Ok(vec![
+                IdExpr::MessageId(String::from("a,b@host")),
+                IdExpr::Id(5)
+            ])
+        );
+        assert_eq!(
+            IdExpr::parse_many("work:<a,b@host>"),
+            Ok(vec![IdExpr::Account(
+                String::from("work"),
+                Box::new(IdExpr::MessageId(String::from("a,b@host")))
+            )])
+        );
+        assert!(IdExpr::parse_many("<a,b@host").is_err());
+    }
+
+    #[test]
+    fn flatten() {
+        let exprs: Vec<IdExprs> = ["5,7", "<a,b@host>"]
+            .iter()
+            .map(|exprs| exprs.parse().unwrap())
+            .collect();
+        assert_eq!(
+            IdExprs::flatten(&exprs),
+            vec![
+                IdExpr::Id(5),
+                IdExpr::Id(7),
+                IdExpr::MessageId(String::from("a,b@host"))
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        for expr in ["42", "1-20", "<abc@host>", "first", "last", "work:42"] {
+            assert_eq!(parse(expr).unwrap().to_string(), expr);
+        }
+    }
 }
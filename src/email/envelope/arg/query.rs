@@ -0,0 +1,19 @@
+use clap::Parser;
+
+/// The envelopes query flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct EnvelopesQueryFlags {
+    /// Apply the command on envelopes matching the given query.
+    ///
+    /// The query follows the same syntax as the envelope list
+    /// command filter query (see envelope list --help). Matching
+    /// envelopes are resolved across all pages, then the command is
+    /// applied by batches once confirmed.
+    #[arg(long, short = 'q', value_name = "QUERY")]
+    pub query: Option<String>,
+
+    /// Do not ask for confirmation before applying the command on
+    /// envelopes matching the query or the saved search.
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+}
@@ -0,0 +1,431 @@
+use comfy_table::{Cell, Row};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops, str::FromStr};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::theme::{self, config::ThemeConfig};
+
+use super::Envelope;
+
+/// The kind of information displayed by an envelope table column.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum EnvelopeColumnKind {
+    Id,
+    Flags,
+    Subject,
+    From,
+    To,
+    Cc,
+    Date,
+    Size,
+    Attachment,
+    Folder,
+    Account,
+    Preview,
+}
+
+impl EnvelopeColumnKind {
+    const ALL: [Self; 12] = [
+        Self::Id,
+        Self::Flags,
+        Self::Subject,
+        Self::From,
+        Self::To,
+        Self::Cc,
+        Self::Date,
+        Self::Size,
+        Self::Attachment,
+        Self::Folder,
+        Self::Account,
+        Self::Preview,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Flags => "flags",
+            Self::Subject => "subject",
+            Self::From => "from",
+            Self::To => "to",
+            Self::Cc => "cc",
+            Self::Date => "date",
+            Self::Size => "size",
+            Self::Attachment => "attachment",
+            Self::Folder => "folder",
+            Self::Account => "account",
+            Self::Preview => "preview",
+        }
+    }
+
+    /// Return `true` if the column needs the whole message to be
+    /// fetched, since envelopes do not carry the information.
+    pub fn requires_message(&self) -> bool {
+        matches!(
+            self,
+            Self::Cc | Self::Size | Self::Attachment | Self::Preview
+        )
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            Self::Id => "ID",
+            Self::Flags => "FLAGS",
+            Self::Subject => "SUBJECT",
+            Self::From => "FROM",
+            Self::To => "TO",
+            Self::Cc => "CC",
+            Self::Date => "DATE",
+            Self::Size => "SIZE",
+            Self::Attachment => "ATT",
+            Self::Folder => "FOLDER",
+            Self::Account => "ACCOUNT",
+            Self::Preview => "PREVIEW",
+        }
+    }
+
+    fn content(&self, envelope: &Envelope, theme: &ThemeConfig) -> String {
+        match self {
+            Self::Id => envelope.id.clone(),
+            Self::Flags => theme.flags_glyphs(&envelope.flags),
+            Self::Subject => envelope.subject.clone(),
+            Self::From => envelope.from.to_string(),
+            Self::To => envelope.to.to_string(),
+            Self::Cc => envelope
+                .cc
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            Self::Date => envelope.date.clone(),
+            Self::Size => envelope.size.map(format_size).unwrap_or_default(),
+            Self::Attachment => match envelope.has_attachment {
+                Some(true) => String::from("📎"),
+                _ => String::new(),
+            },
+            Self::Folder => envelope.folder.clone().unwrap_or_default(),
+            Self::Account => envelope.account.clone().unwrap_or_default(),
+            Self::Preview => envelope.preview.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for EnvelopeColumnKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        let kind = kind.trim();
+
+        Self::ALL
+            .into_iter()
+            .find(|k| k.as_str().eq_ignore_ascii_case(kind))
+            .ok_or_else(|| {
+                let kinds: Vec<_> = Self::ALL.iter().map(Self::as_str).collect();
+                format!(
+                    "invalid envelope column {kind}, expected one of: {}",
+                    kinds.join(", ")
+                )
+            })
+    }
+}
+
+impl TryFrom<String> for EnvelopeColumnKind {
+    type Error = String;
+
+    fn try_from(kind: String) -> Result<Self, Self::Error> {
+        kind.parse()
+    }
+}
+
+impl From<EnvelopeColumnKind> for String {
+    fn from(kind: EnvelopeColumnKind) -> Self {
+        kind.to_string()
+    }
+}
+
+impl fmt::Display for EnvelopeColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An envelope table column, with an optional maximum width.
+///
+/// Columns are written as their kind optionally followed by a colon
+/// and a maximum width in characters, for example `subject:40`.
+/// Content exceeding the width is truncated with an ellipsis.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EnvelopeColumn {
+    pub kind: EnvelopeColumnKind,
+    pub max_width: Option<usize>,
+}
+
+impl EnvelopeColumn {
+    pub fn new(kind: EnvelopeColumnKind) -> Self {
+        Self {
+            kind,
+            max_width: None,
+        }
+    }
+
+    fn cell(&self, envelope: &Envelope, theme: &ThemeConfig) -> Cell {
+        let content = self.kind.content(envelope, theme);
+        let content = match self.max_width {
+            Some(width) => truncate(&content, width),
+            None => content,
+        };
+
+        let color = theme.envelope_color(&self.kind);
+        let attrs = theme.envelope_attributes(&envelope.flags);
+
+        theme::cell(content, Some(color), attrs)
+    }
+}
+
+impl FromStr for EnvelopeColumn {
+    type Err = String;
+
+    fn from_str(column: &str) -> Result<Self, Self::Err> {
+        match column.split_once(':') {
+            None => Ok(Self::new(column.parse()?)),
+            Some((kind, width)) => {
+                let width = width
+                    .trim()
+                    .parse()
+                    .map_err(|err| format!("invalid width for envelope column {column}: {err}"))?;
+
+                Ok(Self {
+                    kind: kind.parse()?,
+                    max_width: Some(width),
+                })
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for EnvelopeColumn {
+    type Error = String;
+
+    fn try_from(column: String) -> Result<Self, Self::Error> {
+        column.parse()
+    }
+}
+
+impl From<EnvelopeColumn> for String {
+    fn from(column: EnvelopeColumn) -> Self {
+        column.to_string()
+    }
+}
+
+impl fmt::Display for EnvelopeColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_width {
+            Some(width) => write!(f, "{}:{width}", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// The ordered list of columns of an envelope table.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct EnvelopeColumns(Vec<EnvelopeColumn>);
+
+impl EnvelopeColumns {
+    /// Return `true` if one of the columns needs the whole message
+    /// to be fetched.
+    pub fn requires_message(&self) -> bool {
+        self.iter().any(|column| column.kind.requires_message())
+    }
+
+    /// Insert a column of the given kind after the id column, unless
+    /// already present.
+    pub fn with(mut self, kind: EnvelopeColumnKind) -> Self {
+        if !self.iter().any(|c| c.kind == kind) {
+            let idx = self
+                .iter()
+                .position(|c| c.kind == EnvelopeColumnKind::Id)
+                .map_or(0, |idx| idx + 1);
+            self.0.insert(idx, EnvelopeColumn::new(kind));
+        }
+        self
+    }
+
+    pub fn header(&self, theme: &ThemeConfig) -> Row {
+        theme.header(self.iter().map(|column| column.kind.header()))
+    }
+
+    pub fn row(&self, envelope: &Envelope, theme: &ThemeConfig) -> Row {
+        Row::from(self.iter().map(|column| column.cell(envelope, theme)))
+    }
+}
+
+impl Default for EnvelopeColumns {
+    fn default() -> Self {
+        Self(vec![
+            EnvelopeColumn::new(EnvelopeColumnKind::Id),
+            EnvelopeColumn::new(EnvelopeColumnKind::Flags),
+            EnvelopeColumn::new(EnvelopeColumnKind::Subject),
+            EnvelopeColumn::new(EnvelopeColumnKind::From),
+            EnvelopeColumn::new(EnvelopeColumnKind::Date),
+        ])
+    }
+}
+
+impl From<Vec<EnvelopeColumn>> for EnvelopeColumns {
+    fn from(columns: Vec<EnvelopeColumn>) -> Self {
+        Self(columns)
+    }
+}
+
+impl ops::Deref for EnvelopeColumns {
+    type Target = Vec<EnvelopeColumn>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Truncate the given content to the given display width, ending
+/// with an ellipsis when truncated.
+///
+/// Widths are measured in terminal columns, so that wide characters
+/// like CJK ones or emojis count twice.
+fn truncate(content: &str, width: usize) -> String {
+    if content.width() <= width {
+        return content.to_owned();
+    }
+
+    if width == 0 {
+        return String::new();
+    }
+
+    // keep one column for the ellipsis
+    let mut truncated = String::new();
+    let mut truncated_width = 0;
+    for c in content.chars() {
+        let c_width = c.width().unwrap_or_default();
+        if truncated_width + c_width >= width {
+            break;
+        }
+        truncated.push(c);
+        truncated_width += c_width;
+    }
+
+    truncated.push('…');
+    truncated
+}
+
+/// Format the given size in bytes with a binary unit (B, K, M, G).
+pub fn format_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+
+    let mut size = size as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size}{}", UNITS[unit])
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_size, truncate, EnvelopeColumn, EnvelopeColumnKind, EnvelopeColumns};
+
+    #[test]
+    fn parse_column() {
+        assert_eq!(
+            "subject".parse(),
+            Ok(EnvelopeColumn::new(EnvelopeColumnKind::Subject))
+        );
+        assert_eq!(
+            " FROM ".parse(),
+            Ok(EnvelopeColumn::new(EnvelopeColumnKind::From))
+        );
+        assert_eq!(
+            "subject:40".parse(),
+            Ok(EnvelopeColumn {
+                kind: EnvelopeColumnKind::Subject,
+                max_width: Some(40),
+            })
+        );
+        assert_eq!(
+            "preview: 20".parse(),
+            Ok(EnvelopeColumn {
+                kind: EnvelopeColumnKind::Preview,
+                max_width: Some(20),
+            })
+        );
+        assert!("unknown".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:-1".parse::<EnvelopeColumn>().is_err());
+        assert!("unknown:40".parse::<EnvelopeColumn>().is_err());
+    }
+
+    #[test]
+    fn display_column() {
+        for column in ["id", "subject:40", "attachment", "account:10"] {
+            let parsed: EnvelopeColumn = column.parse().unwrap();
+            assert_eq!(parsed.to_string(), column);
+        }
+    }
+
+    #[test]
+    fn with_column() {
+        let columns = EnvelopeColumns::default().with(EnvelopeColumnKind::Account);
+        let kinds: Vec<_> = columns.iter().map(|c| c.kind.as_str()).collect();
+        assert_eq!(kinds, ["id", "account", "flags", "subject", "from", "date"]);
+
+        let columns = columns.with(EnvelopeColumnKind::Account);
+        assert_eq!(columns.len(), 6);
+
+        let columns = EnvelopeColumns::from(vec![]).with(EnvelopeColumnKind::Folder);
+        assert_eq!(columns[0].kind, EnvelopeColumnKind::Folder);
+    }
+
+    #[test]
+    fn requires_message() {
+        assert!(!EnvelopeColumns::default().requires_message());
+
+        let columns = EnvelopeColumns::default().with(EnvelopeColumnKind::Preview);
+        assert!(columns.requires_message());
+    }
+
+    #[test]
+    fn truncate_content() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello", 5), "hello");
+        assert_eq!(truncate("hello world", 5), "hell…");
+        assert_eq!(truncate("héllo wörld", 6), "héllo…");
+        assert_eq!(truncate("hello", 1), "…");
+        assert_eq!(truncate("hello", 0), "");
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn truncate_wide_content() {
+        assert_eq!(truncate("日本語", 6), "日本語");
+        assert_eq!(truncate("日本語", 5), "日本…");
+        assert_eq!(truncate("日本語", 4), "日…");
+        assert_eq!(truncate("日本語", 1), "…");
+        assert_eq!(truncate("a😀b", 3), "a…");
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(0), "0B");
+        assert_eq!(format_size(1023), "1023B");
+        assert_eq!(format_size(1024), "1.0K");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0M");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0G");
+        assert_eq!(format_size(2048 * 1024 * 1024 * 1024), "2048.0G");
+    }
+}
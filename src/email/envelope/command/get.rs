@@ -0,0 +1,69 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::backend::feature::BackendFeatureSource;
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::{EnvelopeIdArg, IdExpr},
+    folder::arg::name::FolderNameOptionalFlag,
+    printer::Printer,
+};
+
+/// Get an envelope.
+///
+/// This command allows you to display the details of the given
+/// envelope: all addresses, Message-ID, In-Reply-To, flags, date and
+/// size. The message is peeked in order to read headers missing from
+/// the envelope, so it is not marked as seen.
+#[derive(Debug, Parser)]
+pub struct GetEnvelopeCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl GetEnvelopeCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing get envelope command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            IdExpr::account_name(self.account.name.as_deref(), [&self.envelope.id])?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            get_envelope_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let id = backend.resolve_id(folder, &self.envelope.id).await?;
+        let envelope = backend.get_envelope_details(folder, id).await?;
+
+        printer.print(envelope)
+    }
+}
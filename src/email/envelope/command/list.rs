@@ -1,33 +1,48 @@
-use ariadne::{Color, Label, Report, ReportKind, Source};
 use clap::Parser;
-use color_eyre::Result;
+use color_eyre::{eyre::bail, Result};
 use email::{
-    backend::feature::BackendFeatureSource, email::search_query,
-    envelope::list::ListEnvelopesOptions, search_query::SearchEmailsQuery,
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    envelope::list::ListEnvelopesOptions,
+    folder::{list::ListFolders, INBOX},
 };
-use std::process::exit;
-use tracing::info;
+use futures::future;
+use std::{mem, sync::Arc};
+use tracing::{debug, info};
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+    account::{
+        arg::name::{AccountNameFlag, AccountNamesFlag},
+        config::TomlAccountConfig,
+    },
+    backend::Backend,
+    config::TomlConfig,
+    envelope::{
+        column::{EnvelopeColumn, EnvelopeColumnKind, EnvelopeColumns},
+        query, Envelopes,
+    },
+    folder::{arg::name::FolderNamesOptionalFlag, search},
+    printer::{Page, Printer},
 };
 
 /// List all envelopes.
 ///
 /// This command allows you to list all envelopes included in the
-/// given folder.
+/// given folders. Envelopes of several folders are merged according
+/// to the sort query, and their folder is displayed in a dedicated
+/// column. The same goes for envelopes of several accounts.
 #[derive(Debug, Parser)]
 pub struct ListEnvelopesCommand {
     #[command(flatten)]
-    pub folder: FolderNameOptionalFlag,
+    pub folder: FolderNamesOptionalFlag,
 
     /// The page number.
     ///
-    /// The page number starts from 1 (which is the default). Giving a
-    /// page number to big will result in a out of bound error.
+    /// The page number starts from 1 (which is the default). Pages
+    /// past the last one are empty, unless a query is given, in which
+    /// case backends may refuse to list them.
     #[arg(long, short, value_name = "NUMBER", default_value = "1")]
     pub page: usize,
 
@@ -37,6 +52,23 @@ pub struct ListEnvelopesCommand {
     #[arg(long, short = 's', value_name = "NUMBER")]
     pub page_size: Option<usize>,
 
+    /// List envelopes of all pages.
+    ///
+    /// Pages are fetched one after the other, starting from the given
+    /// page. With line-oriented output formats (ndjson or a
+    /// template), envelopes are printed as soon as their page
+    /// arrives.
+    #[arg(long, short = 'A')]
+    pub all: bool,
+
+    /// The maximum amount of envelopes to list.
+    ///
+    /// Pages are fetched one after the other, starting from the given
+    /// page, until the limit is reached. The limit does not depend on
+    /// the page size.
+    #[arg(long, short = 'l', value_name = "NUMBER")]
+    pub limit: Option<usize>,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -44,6 +76,9 @@ pub struct ListEnvelopesCommand {
     #[command(flatten)]
     pub account: AccountNameFlag,
 
+    #[command(flatten)]
+    pub accounts: AccountNamesFlag,
+
     /// The maximum width the table should not exceed.
     ///
     /// This argument will force the table not to exceed the given
@@ -53,6 +88,30 @@ pub struct ListEnvelopesCommand {
     #[arg(name = "table_max_width", value_name = "PIXELS")]
     pub table_max_width: Option<u16>,
 
+    /// The columns of the table, in order.
+    ///
+    /// Available columns are id, flags, subject, from, to, cc, date,
+    /// size, attachment, folder, account and preview. A column can be
+    /// followed by a colon and a maximum width in characters
+    /// (subject:40).
+    /// Columns cc, size, attachment and preview require messages to
+    /// be fetched, which makes the listing slower. Defaults to the
+    /// envelope.list.columns account option, or to
+    /// id,flags,subject,from,date.
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Option<Vec<EnvelopeColumn>>,
+
+    /// Group envelopes by conversation.
+    ///
+    /// Replies are displayed below the envelope they reply to, based
+    /// on their In-Reply-To and References headers. References are
+    /// read from message headers, which makes the listing slower
+    /// outside of Maildir folders. Only envelopes of the current page
+    /// are threaded together. Threading is not available for
+    /// multi-account listings.
+    #[arg(long, short = 't', conflicts_with_all = ["account_names", "all_accounts"])]
+    pub threaded: bool,
+
     /// The list envelopes filter and sort query.
     ///
     /// The query can be a filter query, a sort query or both
@@ -93,6 +152,10 @@ pub struct ListEnvelopesCommand {
     ///
     ///  ◦ flag <flag> → filter envelopes matching the given flag
     ///
+    /// Dates can also be relative: today, yesterday, tomorrow, an
+    /// amount of days, weeks, months or years ago (7d, 2w, 3m, 1y) or
+    /// the last given day of the week (last-monday).
+    ///
     /// A sort query starts by "order by", and is composed of kinds
     /// and orders. There is 4 kinds and 2 orders:
     ///
@@ -122,6 +185,13 @@ pub struct ListEnvelopesCommand {
     /// combination of the 2 previous examples
     #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
     pub query: Option<Vec<String>>,
+
+    /// Explain the query instead of listing envelopes.
+    ///
+    /// Relative dates are resolved, then the syntax tree of the query
+    /// is printed.
+    #[arg(long)]
+    pub explain: bool,
 }
 
 impl Default for ListEnvelopesCommand {
@@ -130,11 +200,17 @@ impl Default for ListEnvelopesCommand {
             folder: Default::default(),
             page: 1,
             page_size: Default::default(),
+            all: Default::default(),
+            limit: Default::default(),
             #[cfg(feature = "account-sync")]
             cache: Default::default(),
             account: Default::default(),
+            accounts: Default::default(),
             query: Default::default(),
             table_max_width: Default::default(),
+            columns: Default::default(),
+            threaded: Default::default(),
+            explain: Default::default(),
         }
     }
 }
@@ -143,64 +219,532 @@ impl ListEnvelopesCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing list envelopes command");
 
+        if self.accounts.is_multiple() {
+            let accounts = self.accounts.resolve(config)?;
+            return self.execute_accounts(printer, config, accounts).await;
+        }
+
         let (toml_account_config, account_config) = config.clone().into_account_configs(
             self.account.name.as_deref(),
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
-        let folder = &self.folder.name;
         let page = 1.max(self.page) - 1;
         let page_size = self
             .page_size
             .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+        let take = self.take(page_size);
+
+        let mut columns: EnvelopeColumns = self
+            .columns
+            .or_else(|| toml_account_config.list_envelopes_columns().cloned())
+            .map(Into::into)
+            .unwrap_or_default();
+        let requires_message = columns.requires_message();
+
+        let query = self.query.map(|query| query.join(" "));
+        let (folder, query) = resolve_search(&toml_account_config, &self.folder, query.as_deref())?;
+
+        if self.explain {
+            return explain(printer, query.as_deref());
+        }
 
-        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+        let requires_folders = folder.requires_folders();
 
-        let backend = Backend::new(
+        let backend = build_backend(
             toml_account_config.clone(),
             account_config.clone(),
-            list_envelopes_kind,
-            |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+            requires_message,
+            requires_folders,
+            self.threaded,
         )
         .await?;
 
-        let query = self
-            .query
-            .map(|query| query.join(" ").parse::<SearchEmailsQuery>());
-        let query = match query {
-            None => None,
-            Some(Ok(query)) => Some(query),
-            Some(Err(main_err)) => {
-                let source = "query";
-                let search_query::error::Error::ParseError(errs, query) = &main_err;
-                for err in errs {
-                    Report::build(ReportKind::Error, source, err.span().start)
-                        .with_message(main_err.to_string())
-                        .with_label(
-                            Label::new((source, err.span().into_range()))
-                                .with_message(err.reason().to_string())
-                                .with_color(Color::Red),
-                        )
-                        .finish()
-                        .eprint((source, Source::from(&query)))
-                        .unwrap();
+        let folders = resolve_folders(&backend, &folder).await?;
+
+        let opts = |page, page_size| ListEnvelopesOptions {
+            page,
+            page_size,
+            query: query.as_deref().map(query::parse_or_exit),
+        };
+
+        let theme = config.theme(Some(toml_account_config.as_ref()));
+        let iterate = self.all || self.limit.is_some();
+        let stream = iterate && !self.threaded && printer.can_stream();
+
+        let mut envelopes = Envelopes::default();
+        let mut count = 0;
+        let mut total = None;
+
+        let has_more = match folders.as_slice() {
+            [] => false,
+            [folder] => {
+                // without query, the total is the size of the folder,
+                // which bounds the pages to list
+                if query.is_none() {
+                    total = match backend.folder_stats(folder).await {
+                        Ok(stats) => Some(stats.total),
+                        Err(err) => {
+                            debug!("cannot get statistics of folder {folder}: {err}");
+                            debug!("{err:?}");
+                            None
+                        }
+                    };
                 }
 
-                exit(0)
+                let mut page_idx = page;
+
+                let has_more = loop {
+                    if total.is_some_and(|total| page_size > 0 && page_idx * page_size >= total) {
+                        break false;
+                    }
+
+                    let mut page_envelopes = backend
+                        .list_envelopes(folder, opts(page_idx, page_size))
+                        .await?;
+
+                    let len = page_envelopes.len();
+                    let full = page_size > 0 && len == page_size;
+                    let remaining = self.limit.map(|limit| limit - count);
+                    if let Some(remaining) = remaining {
+                        page_envelopes.truncate(remaining);
+                    }
+                    let truncated = page_envelopes.len() < len;
+                    let limit_reached = remaining.is_some_and(|remaining| len >= remaining);
+
+                    count += page_envelopes.len();
+
+                    let mut page_envelopes =
+                        load_details(&backend, folder, &columns, page_envelopes).await?;
+
+                    // line-oriented formats do not need the whole
+                    // listing, so pages are printed as soon as they
+                    // arrive
+                    if stream {
+                        printer.print_table(page_envelopes, self.table_max_width)?;
+                    } else {
+                        envelopes.append(&mut page_envelopes);
+                    }
+
+                    if truncated {
+                        break true;
+                    }
+
+                    if !full {
+                        break false;
+                    }
+
+                    // a full page may be the last one
+                    let next = (page_idx + 1) * page_size;
+                    let more = match total {
+                        Some(total) => next < total,
+                        None => peek_envelope(&backend, folder, opts(next, 1)).await,
+                    };
+
+                    if !more || !iterate || limit_reached {
+                        break more;
+                    }
+
+                    page_idx += 1;
+                };
+
+                if stream {
+                    return Ok(());
+                }
+
+                has_more
             }
+            folders => {
+                columns = columns.with(EnvelopeColumnKind::Folder);
+
+                let (merged, has_more) = list_merged_envelopes(
+                    &backend,
+                    folders,
+                    query.as_deref(),
+                    page * page_size,
+                    take,
+                )
+                .await?;
+
+                count = merged.len();
+                envelopes = load_details(&backend, &folders[0], &columns, merged).await?;
+
+                has_more
+            }
+        };
+
+        envelopes.set_columns(columns);
+        envelopes.set_theme(theme);
+
+        let total = total.or_else(|| (!has_more).then_some(page * page_size + count));
+
+        if self.threaded {
+            let folder = folders.first().map(String::as_str).unwrap_or_default();
+            let threads = backend.thread_envelopes(folder, envelopes).await?;
+            let page = Page {
+                page: page + 1,
+                page_size,
+                count,
+                total,
+                has_more,
+                items: threads,
+            };
+            printer.print_page(page, self.table_max_width)?;
+        } else {
+            let page = Page {
+                page: page + 1,
+                page_size,
+                count,
+                total,
+                has_more,
+                items: envelopes,
+            };
+            printer.print_page(page, self.table_max_width)?;
+        }
+
+        Ok(())
+    }
+
+    /// List envelopes of several accounts concurrently, then merge
+    /// them according to the sort query.
+    ///
+    /// Display options (page size, columns and theme) are taken from
+    /// the first account. Ids are qualified by their account, so that
+    /// they can be given back to other commands.
+    async fn execute_accounts(
+        self,
+        printer: &mut impl Printer,
+        config: &TomlConfig,
+        accounts: Vec<String>,
+    ) -> Result<()> {
+        let Some(first_account) = accounts.first() else {
+            bail!("cannot list envelopes: no account found");
         };
 
+        #[cfg(feature = "account-sync")]
+        let disable_cache = self.cache.disable;
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            Some(first_account),
+            #[cfg(feature = "account-sync")]
+            disable_cache,
+        )?;
+
+        let page = 1.max(self.page) - 1;
+        let page_size = self
+            .page_size
+            .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+        let skip = page * page_size;
+        let take = self.take(page_size);
+
+        let mut columns: EnvelopeColumns = self
+            .columns
+            .or_else(|| toml_account_config.list_envelopes_columns().cloned())
+            .map(Into::into)
+            .unwrap_or_default();
+        let query = self.query.map(|query| query.join(" "));
+        // saved searches are resolved per account, the first one
+        // gives the sort query used to merge envelopes
+        let (folder, sort_query) =
+            resolve_search(&toml_account_config, &self.folder, query.as_deref())?;
+
+        if self.explain {
+            return explain(printer, sort_query.as_deref());
+        }
+
+        if folder.requires_folders() || folder.names.len() > 1 {
+            columns = columns.with(EnvelopeColumnKind::Folder);
+        }
+        let columns = columns.with(EnvelopeColumnKind::Account);
+        let requires_message = columns.requires_message();
+
+        let listings = accounts.iter().map(|account| {
+            let folder = &self.folder;
+            let query = query.as_deref();
+
+            async move {
+                let (toml_account_config, account_config) = config.clone().into_account_configs(
+                    Some(account),
+                    #[cfg(feature = "account-sync")]
+                    disable_cache,
+                )?;
+
+                let (folder, query) = resolve_search(&toml_account_config, folder, query)?;
+                let requires_folders = folder.requires_folders();
+
+                let backend = build_backend(
+                    toml_account_config,
+                    account_config,
+                    requires_message,
+                    requires_folders,
+                    false,
+                )
+                .await?;
+
+                let folders = resolve_folders(&backend, &folder).await?;
+                let window = take.map(|take| skip + take);
+                let (mut envelopes, has_more) =
+                    list_merged_envelopes(&backend, &folders, query.as_deref(), 0, window).await?;
+
+                for envelope in envelopes.iter_mut() {
+                    envelope.account = Some(account.clone());
+                }
+
+                Result::<_>::Ok((backend, envelopes, has_more))
+            }
+        });
+
+        let mut backends = Vec::with_capacity(accounts.len());
+        let mut envelopes = Envelopes::default();
+        let mut has_more = false;
+
+        for (account, (backend, mut listing, more)) in
+            accounts.iter().zip(future::try_join_all(listings).await?)
+        {
+            envelopes.append(&mut listing);
+            backends.push((account, backend));
+            has_more |= more;
+        }
+
+        has_more |= window(&mut envelopes, sort_query.as_deref(), skip, take);
+
+        if requires_message {
+            for (account, backend) in &backends {
+                let idxs: Vec<usize> = envelopes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, envelope)| envelope.account.as_ref() == Some(*account))
+                    .map(|(idx, _)| idx)
+                    .collect();
+
+                let mut details = Envelopes::default();
+                details.extend(idxs.iter().map(|idx| mem::take(&mut envelopes[*idx])));
+                backend.load_envelopes_details(INBOX, &mut details).await?;
+
+                for (idx, envelope) in idxs.into_iter().zip(details.drain(..)) {
+                    envelopes[idx] = envelope;
+                }
+            }
+        }
+
+        for envelope in envelopes.iter_mut() {
+            if let Some(account) = &envelope.account {
+                envelope.id = format!("{account}:{}", envelope.id);
+            }
+        }
+
+        let count = envelopes.len();
+
+        envelopes.set_columns(columns);
+        envelopes.set_theme(config.theme(Some(toml_account_config.as_ref())));
+
+        let page = Page {
+            page: page + 1,
+            page_size,
+            count,
+            total: (!has_more).then_some(skip + count),
+            has_more,
+            items: envelopes,
+        };
+
+        printer.print_page(page, self.table_max_width)
+    }
+
+    /// The maximum amount of envelopes to list when envelopes are
+    /// merged from several sources, or `None` for all of them.
+    fn take(&self, page_size: usize) -> Option<usize> {
+        match self.limit {
+            Some(limit) => Some(limit),
+            None if self.all || page_size == 0 => None,
+            None => Some(page_size),
+        }
+    }
+}
+
+/// Print the explanation of the given query.
+fn explain(printer: &mut impl Printer, query: Option<&str>) -> Result<()> {
+    match query {
+        Some(query) => printer.print(query::explain(query)),
+        None => bail!("cannot explain query: no query given"),
+    }
+}
+
+/// Return `true` if the given folder has an envelope at the position
+/// of the given single-envelope page.
+///
+/// Backends refuse to list pages past the last one, so a failure
+/// means that no envelope follows.
+async fn peek_envelope(backend: &Backend, folder: &str, opts: ListEnvelopesOptions) -> bool {
+    match backend.list_envelopes(folder, opts).await {
+        Ok(envelopes) => !envelopes.is_empty(),
+        Err(err) => {
+            debug!("cannot peek next envelope of folder {folder}, assuming none: {err}");
+            debug!("{err:?}");
+            false
+        }
+    }
+}
+
+/// Build a backend able to list envelopes, and optionally to get
+/// messages, to list folders and to thread envelopes.
+async fn build_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    requires_message: bool,
+    requires_folders: bool,
+    threaded: bool,
+) -> Result<Backend> {
+    let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+    let get_messages_kind = requires_message
+        .then(|| toml_account_config.get_messages_kind())
+        .flatten();
+    let list_folders_kind = requires_folders
+        .then(|| toml_account_config.list_folders_kind())
+        .flatten();
+    let get_envelope_kind = threaded
+        .then(|| toml_account_config.get_envelope_kind())
+        .flatten();
+    let peek_messages_kind = threaded
+        .then(|| toml_account_config.peek_messages_kind())
+        .flatten();
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        list_envelopes_kind
+            .into_iter()
+            .chain(get_messages_kind)
+            .chain(list_folders_kind)
+            .chain(get_envelope_kind)
+            .chain(peek_messages_kind),
+        |builder| {
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+            if requires_message {
+                builder.set_get_messages(BackendFeatureSource::Context);
+            }
+            if requires_folders {
+                builder.set_list_folders(BackendFeatureSource::Context);
+            }
+            if threaded {
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+            }
+        },
+    )
+    .await
+}
+
+/// Resolve the saved search targeted by the given folder names, if
+/// any.
+///
+/// A saved search is replaced by its folders, and its query is
+/// combined with the given one.
+fn resolve_search(
+    config: &TomlAccountConfig,
+    folder: &FolderNamesOptionalFlag,
+    query: Option<&str>,
+) -> Result<(FolderNamesOptionalFlag, Option<String>)> {
+    let is_search = |name: &String| name.starts_with(search::PREFIX);
+
+    if !folder.all && folder.names.iter().any(is_search) {
+        if folder.names.len() > 1 {
+            bail!("cannot combine a saved search with other folders");
+        }
+
+        if let Some((name, search)) = search::find(config, &folder.names[0])? {
+            let folder = FolderNamesOptionalFlag {
+                names: search.folders(),
+                all: false,
+            };
+            let query = search.combine_query(name, query)?;
+            return Ok((folder, Some(query)));
+        }
+    }
+
+    Ok((folder.clone(), query.map(ToOwned::to_owned)))
+}
+
+/// Resolve the given folder names, listing the folders of the
+/// account only when needed.
+async fn resolve_folders(
+    backend: &Backend,
+    folder: &FolderNamesOptionalFlag,
+) -> Result<Vec<String>> {
+    if folder.requires_folders() {
+        let folders = backend.list_folders().await?;
+        folder.resolve(folders.iter().map(|folder| folder.name.as_str()))
+    } else {
+        Ok(folder.names.clone())
+    }
+}
+
+/// List envelopes of several folders concurrently, then merge them
+/// according to the sort query.
+///
+/// Backends cannot paginate across folders, so each folder is listed
+/// from its first envelope up to the end of the requested window,
+/// plus one envelope in order to know if more envelopes follow.
+async fn list_merged_envelopes(
+    backend: &Backend,
+    folders: &[String],
+    query: Option<&str>,
+    skip: usize,
+    take: Option<usize>,
+) -> Result<(Envelopes, bool)> {
+    let page_size = take.map(|take| skip + take + 1).unwrap_or_default();
+
+    let listings = folders.iter().map(|folder| {
         let opts = ListEnvelopesOptions {
-            page,
+            page: 0,
             page_size,
-            query,
+            query: query.map(query::parse_or_exit),
         };
+        backend.list_envelopes(folder, opts)
+    });
 
-        let envelopes = backend.list_envelopes(folder, opts).await?;
+    let mut envelopes = Envelopes::default();
+    for mut listing in future::try_join_all(listings).await? {
+        envelopes.append(&mut listing);
+    }
 
-        printer.print_table(envelopes, self.table_max_width)?;
+    let has_more = window(&mut envelopes, query, skip, take);
 
-        Ok(())
+    Ok((envelopes, has_more))
+}
+
+/// Sort the given merged envelopes according to the sort query, then
+/// keep the requested window.
+///
+/// Returns `true` if envelopes follow the window.
+fn window(
+    envelopes: &mut Envelopes,
+    query: Option<&str>,
+    skip: usize,
+    take: Option<usize>,
+) -> bool {
+    query::sort_envelopes(envelopes, query);
+    envelopes.drain(..skip.min(envelopes.len()));
+
+    let has_more = take.is_some_and(|take| envelopes.len() > take);
+    if let Some(take) = take {
+        envelopes.truncate(take);
+    }
+
+    has_more
+}
+
+/// Load the envelopes details required by the given columns.
+async fn load_details(
+    backend: &Backend,
+    folder: &str,
+    columns: &EnvelopeColumns,
+    mut envelopes: Envelopes,
+) -> Result<Envelopes> {
+    if columns.requires_message() {
+        backend
+            .load_envelopes_details(folder, &mut envelopes)
+            .await?;
     }
+    Ok(envelopes)
 }
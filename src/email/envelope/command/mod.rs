@@ -1,3 +1,4 @@
+pub mod get;
 pub mod list;
 pub mod watch;
 
@@ -6,7 +7,7 @@ use clap::Subcommand;
 
 use crate::{config::TomlConfig, printer::Printer};
 
-use self::{list::ListEnvelopesCommand, watch::WatchEnvelopesCommand};
+use self::{get::GetEnvelopeCommand, list::ListEnvelopesCommand, watch::WatchEnvelopesCommand};
 
 /// Manage envelopes.
 ///
@@ -19,6 +20,9 @@ pub enum EnvelopeSubcommand {
     #[command(alias = "lst")]
     List(ListEnvelopesCommand),
 
+    #[command()]
+    Get(GetEnvelopeCommand),
+
     #[command()]
     Watch(WatchEnvelopesCommand),
 }
@@ -28,6 +32,7 @@ impl EnvelopeSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
             Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Get(cmd) => cmd.execute(printer, config).await,
             Self::Watch(cmd) => cmd.execute(printer, config).await,
         }
     }
@@ -1,23 +1,39 @@
 use clap::Parser;
 use color_eyre::Result;
-use email::backend::feature::BackendFeatureSource;
+use email::{backend::feature::BackendFeatureSource, folder::list::ListFolders};
+use futures::future;
+use std::time::Duration;
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+    account::arg::name::{AccountNameFlag, AccountNamesFlag},
+    backend::Backend,
+    config::TomlConfig,
+    envelope::watch::{WatchEventsFmt, Watcher},
+    folder::arg::name::FolderNamesOptionalFlag,
+    printer::Printer,
+    rule::{self, RuleHistory},
 };
 
 /// Watch envelopes for changes.
 ///
-/// This command allows you to watch a folder and execute hooks when
-/// changes occur on envelopes.
+/// This command allows you to watch folders and execute hooks when
+/// changes occur on envelopes. Folders of several accounts can be
+/// watched at once: each folder is watched concurrently, using the
+/// hooks of its account, and reconnects with an increasing delay
+/// after a failure.
+///
+/// With --events, hooks are not executed: changes are written to the
+/// standard output instead, as a stream of events.
+///
+/// The rules of the account bound to the watched folders are applied
+/// on new envelopes.
 #[derive(Debug, Parser)]
 pub struct WatchEnvelopesCommand {
     #[command(flatten)]
-    pub folder: FolderNameOptionalFlag,
+    pub folder: FolderNamesOptionalFlag,
 
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
@@ -25,33 +41,109 @@ pub struct WatchEnvelopesCommand {
 
     #[command(flatten)]
     pub account: AccountNameFlag,
+
+    #[command(flatten)]
+    pub accounts: AccountNamesFlag,
+
+    /// Write changes as a stream of events.
+    ///
+    /// One event is written per change: new envelope (with all its
+    /// fields), flags change (with old and new flags) and removal.
+    /// Changes are found by listing the whole folder each time the
+    /// backend notifies a change.
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    pub events: Option<WatchEventsFmt>,
+
+    /// The maximum amount of seconds between two listings of the
+    /// folder.
+    ///
+    /// Folders are listed in order to write events, and to apply
+    /// rules on new envelopes. A listing fetches all the envelopes of
+    /// the folder: it is triggered by changes notified by the
+    /// backend, and after this interval otherwise. Large folders
+    /// watched by backends that do not notify changes should use a
+    /// longer interval.
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub interval: u64,
 }
 
 impl WatchEnvelopesCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing watch envelopes command");
 
-        let folder = &self.folder.name;
-        let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
-            #[cfg(feature = "account-sync")]
-            self.cache.disable,
-        )?;
+        #[cfg(feature = "account-sync")]
+        let disable_cache = self.cache.disable;
+
+        let accounts = if self.accounts.is_multiple() {
+            self.accounts.resolve(config)?
+        } else {
+            let (_, account_config) = config.clone().into_account_configs(
+                self.account.name.as_deref(),
+                #[cfg(feature = "account-sync")]
+                disable_cache,
+            )?;
+            vec![account_config.name.clone()]
+        };
+
+        let events = self.events;
+        let interval = Duration::from_secs(self.interval.max(1));
+
+        let mut watchers = Vec::new();
+
+        for account in &accounts {
+            let (toml_account_config, account_config) = config.clone().into_account_configs(
+                Some(account),
+                #[cfg(feature = "account-sync")]
+                disable_cache,
+            )?;
+
+            // the history cannot be opened twice, so it is shared by
+            // the watchers of the account
+            let rule_history = if rule::rules(&toml_account_config).is_empty() {
+                None
+            } else {
+                Some(RuleHistory::new(&account_config)?)
+            };
+
+            let folders = if self.folder.requires_folders() {
+                let list_folders_kind = toml_account_config.list_folders_kind();
+                let backend = Backend::new(
+                    toml_account_config.clone(),
+                    account_config.clone(),
+                    list_folders_kind,
+                    |builder| builder.set_list_folders(BackendFeatureSource::Context),
+                )
+                .await?;
+                let folders = backend.list_folders().await?;
+                self.folder
+                    .resolve(folders.iter().map(|folder| folder.name.as_str()))?
+            } else {
+                self.folder.names.clone()
+            };
 
-        let watch_envelopes_kind = toml_account_config.watch_envelopes_kind();
+            for folder in folders {
+                // logs would be mixed with events
+                if events.is_none() {
+                    printer.print_log(format!(
+                        "Start watching folder {folder} ({account}) for envelopes changes…"
+                    ))?;
+                } else {
+                    info!("start watching folder {folder} ({account}) for envelopes changes");
+                }
 
-        let backend = Backend::new(
-            toml_account_config.clone(),
-            account_config,
-            watch_envelopes_kind,
-            |builder| builder.set_watch_envelopes(BackendFeatureSource::Context),
-        )
-        .await?;
+                watchers.push(Watcher {
+                    toml_account_config: toml_account_config.clone(),
+                    account_config: account_config.clone(),
+                    folder,
+                    events,
+                    interval,
+                    rule_history: rule_history.clone(),
+                });
+            }
+        }
 
-        printer.print_log(format!(
-            "Start watching folder {folder} for envelopes changes…"
-        ))?;
+        future::join_all(watchers.into_iter().map(Watcher::run)).await;
 
-        backend.watch_envelopes(folder).await
+        Ok(())
     }
 }
@@ -3,7 +3,7 @@ use email::envelope::sync::config::EnvelopeSyncConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-use crate::backend::BackendKind;
+use crate::{backend::BackendKind, envelope::column::EnvelopeColumn};
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
 pub struct EnvelopeConfig {
@@ -38,6 +38,9 @@ impl EnvelopeConfig {
 pub struct ListEnvelopesConfig {
     pub backend: Option<BackendKind>,
 
+    /// The columns of the envelopes table, in order.
+    pub columns: Option<Vec<EnvelopeColumn>>,
+
     #[serde(flatten)]
     pub remote: email::envelope::list::config::EnvelopeListConfig,
 }
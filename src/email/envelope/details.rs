@@ -0,0 +1,172 @@
+use color_eyre::Result;
+use email::account::config::AccountConfig;
+use serde::Serialize;
+
+use crate::{
+    flag::Flags,
+    printer::{Print, WriteColor},
+};
+
+use super::{column::format_size, Mailbox};
+
+/// Represents the date of an envelope, in several forms.
+///
+/// JSON schema: `{"raw": string, "local": string, "timestamp":
+/// number}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct EnvelopeDate {
+    /// The date as written in the message, in RFC 2822 format.
+    pub raw: String,
+    /// The date formatted according to the account configuration.
+    pub local: String,
+    /// The date as a UNIX timestamp.
+    pub timestamp: i64,
+}
+
+/// Represents the details of an envelope.
+///
+/// Cc, Bcc and Reply-To addresses and the size are only known when
+/// the headers of the message can be read without fetching it, see
+/// [`crate::backend::Backend::get_envelope_details`].
+///
+/// JSON schema: `{"id": string, "folder": string, "message_id":
+/// string | null, "in_reply_to": string | null, "flags": [string],
+/// "subject": string, "from": [mailbox], "to": [mailbox], "cc":
+/// [mailbox], "bcc": [mailbox], "reply_to": [mailbox], "date": date,
+/// "size": number | null}`. Message-IDs are given without angle
+/// brackets.
+#[derive(Clone, Debug, Serialize)]
+pub struct EnvelopeDetails {
+    pub id: String,
+    pub folder: String,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub flags: Flags,
+    pub subject: String,
+    pub from: Vec<Mailbox>,
+    pub to: Vec<Mailbox>,
+    pub cc: Vec<Mailbox>,
+    pub bcc: Vec<Mailbox>,
+    pub reply_to: Vec<Mailbox>,
+    pub date: EnvelopeDate,
+    /// The size of the raw message, in bytes.
+    pub size: Option<usize>,
+}
+
+impl EnvelopeDetails {
+    /// Build the details of the given envelope, completed by the
+    /// given parsed headers of its message, if any.
+    pub fn new(
+        config: &AccountConfig,
+        id: impl ToString,
+        folder: impl ToString,
+        envelope: &email::envelope::Envelope,
+        headers: Option<&mail_parser::Message>,
+        size: Option<usize>,
+    ) -> Self {
+        let strip = |id: &str| id.trim_matches(['<', '>']).to_owned();
+
+        let message_id = headers
+            .and_then(|headers| headers.message_id())
+            .map(strip)
+            .or_else(|| Some(strip(&envelope.message_id)))
+            .filter(|id| !id.is_empty());
+
+        let in_reply_to = headers
+            .and_then(|headers| headers.in_reply_to().as_text())
+            .or(envelope.in_reply_to.as_deref())
+            .map(strip)
+            .filter(|id| !id.is_empty());
+
+        let mut from = headers
+            .map(|headers| Mailbox::from_address(headers.from()))
+            .unwrap_or_default();
+        if from.is_empty() {
+            from.push(Mailbox {
+                name: envelope.from.name.clone(),
+                addr: envelope.from.addr.clone(),
+            });
+        }
+
+        let mut to = headers
+            .map(|headers| Mailbox::from_address(headers.to()))
+            .unwrap_or_default();
+        if to.is_empty() {
+            to.push(Mailbox {
+                name: envelope.to.name.clone(),
+                addr: envelope.to.addr.clone(),
+            });
+        }
+
+        Self {
+            id: id.to_string(),
+            folder: folder.to_string(),
+            message_id,
+            in_reply_to,
+            flags: envelope.flags.clone().into(),
+            subject: envelope.subject.clone(),
+            from,
+            to,
+            cc: headers
+                .map(|headers| Mailbox::from_address(headers.cc()))
+                .unwrap_or_default(),
+            bcc: headers
+                .map(|headers| Mailbox::from_address(headers.bcc()))
+                .unwrap_or_default(),
+            reply_to: headers
+                .map(|headers| Mailbox::from_address(headers.reply_to()))
+                .unwrap_or_default(),
+            date: EnvelopeDate {
+                raw: envelope.date.to_rfc2822(),
+                local: envelope.format_date(config),
+                timestamp: envelope.date.timestamp(),
+            },
+            size,
+        }
+    }
+}
+
+impl Print for EnvelopeDetails {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        let mailboxes = |mailboxes: &[Mailbox]| {
+            mailboxes
+                .iter()
+                .map(Mailbox::to_full_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut flags: Vec<_> = self.flags.iter().collect();
+        flags.sort();
+        let flags: Vec<_> = flags.into_iter().map(|flag| flag.as_str()).collect();
+
+        writeln!(writer, "ID:          {}", self.id)?;
+        writeln!(writer, "Folder:      {}", self.folder)?;
+        if let Some(message_id) = &self.message_id {
+            writeln!(writer, "Message-ID:  <{message_id}>")?;
+        }
+        if let Some(in_reply_to) = &self.in_reply_to {
+            writeln!(writer, "In-Reply-To: <{in_reply_to}>")?;
+        }
+        writeln!(writer, "Flags:       {}", flags.join(", "))?;
+        writeln!(writer, "Subject:     {}", self.subject)?;
+        writeln!(writer, "From:        {}", mailboxes(&self.from))?;
+        writeln!(writer, "To:          {}", mailboxes(&self.to))?;
+        if !self.cc.is_empty() {
+            writeln!(writer, "Cc:          {}", mailboxes(&self.cc))?;
+        }
+        if !self.bcc.is_empty() {
+            writeln!(writer, "Bcc:         {}", mailboxes(&self.bcc))?;
+        }
+        if !self.reply_to.is_empty() {
+            writeln!(writer, "Reply-To:    {}", mailboxes(&self.reply_to))?;
+        }
+        writeln!(writer, "Date:        {}", self.date.raw)?;
+        writeln!(writer, "Local date:  {}", self.date.local)?;
+        if let Some(size) = self.size {
+            writeln!(writer, "Size:        {}", format_size(size))?;
+        }
+
+        Ok(writer.reset()?)
+    }
+}
@@ -2,41 +2,53 @@ use clap::Parser;
 use email::flag::{Flag, Flags};
 use tracing::debug;
 
+use crate::envelope::arg::{ids::IdExpr, query::EnvelopesQueryFlags};
+
 /// The ids and/or flags arguments parser.
 #[derive(Debug, Parser)]
 pub struct IdsAndFlagsArgs {
     /// The list of ids and/or flags.
     ///
-    /// Every argument that can be parsed as an integer is considered
-    /// an id, otherwise it is considered as a flag.
+    /// Every argument that can be parsed as an id expression is
+    /// considered an id, otherwise it is considered as a flag. An id
+    /// expression can be an envelope id, an inclusive range of ids
+    /// (1-20), a Message-ID enclosed in angle brackets (<abc@host>),
+    /// "first", "last" or a comma-separated list of those (5,7,9-12).
+    ///
+    /// When a query is given, only flags are accepted.
     #[arg(value_name = "ID-OR-FLAG", required = true)]
     pub ids_and_flags: Vec<IdOrFlag>,
+
+    #[command(flatten)]
+    pub query: EnvelopesQueryFlags,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum IdOrFlag {
-    Id(usize),
+    Ids(Vec<IdExpr>),
     Flag(Flag),
 }
 
 impl From<&str> for IdOrFlag {
     fn from(value: &str) -> Self {
-        value.parse::<usize>().map(Self::Id).unwrap_or_else(|err| {
-            let flag = Flag::from(value);
-            debug!("cannot parse {value} as usize, parsing it as flag {flag}");
-            debug!("{err:?}");
-            Self::Flag(flag)
-        })
+        IdExpr::parse_many(value)
+            .map(Self::Ids)
+            .unwrap_or_else(|err| {
+                let flag = Flag::from(value);
+                debug!("cannot parse {value} as id expression, parsing it as flag {flag}");
+                debug!("{err:?}");
+                Self::Flag(flag)
+            })
     }
 }
 
-pub fn into_tuple(ids_and_flags: &[IdOrFlag]) -> (Vec<usize>, Flags) {
+pub fn into_tuple(ids_and_flags: &[IdOrFlag]) -> (Vec<IdExpr>, Flags) {
     ids_and_flags.iter().fold(
         (Vec::default(), Flags::default()),
         |(mut ids, mut flags), arg| {
             match arg {
-                IdOrFlag::Id(id) => {
-                    ids.push(*id);
+                IdOrFlag::Ids(exprs) => {
+                    ids.extend(exprs.iter().cloned());
                 }
                 IdOrFlag::Flag(flag) => {
                     flags.insert(flag.to_owned());
@@ -9,8 +9,12 @@ use crate::{
     account::arg::name::AccountNameFlag,
     backend::Backend,
     config::TomlConfig,
+    envelope::{
+        arg::ids::IdExpr,
+        query::{self, BATCH_SIZE},
+    },
     flag::arg::ids_and_flags::{into_tuple, IdsAndFlagsArgs},
-    folder::arg::name::FolderNameOptionalFlag,
+    folder::{arg::name::FolderOrSearchNameOptionalFlag, search},
     printer::Printer,
 };
 
@@ -21,7 +25,7 @@ use crate::{
 #[derive(Debug, Parser)]
 pub struct FlagAddCommand {
     #[command(flatten)]
-    pub folder: FolderNameOptionalFlag,
+    pub folder: FolderOrSearchNameOptionalFlag,
 
     #[command(flatten)]
     pub args: IdsAndFlagsArgs,
@@ -38,25 +42,60 @@ impl FlagAddCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing add flag(s) command");
 
-        let folder = &self.folder.name;
         let (ids, flags) = into_tuple(&self.args.ids_and_flags);
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), &ids)?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
+        let (folder, query) = search::resolve_folder(
+            &toml_account_config,
+            &self.folder.name,
+            self.args.query.query.as_deref(),
+            !ids.is_empty(),
+        )?;
+        let folder = &folder;
+
         let add_flags_kind = toml_account_config.add_flags_kind();
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config,
-            add_flags_kind,
-            |builder| builder.set_add_flags(BackendFeatureSource::Context),
+            add_flags_kind
+                .into_iter()
+                .chain(get_envelope_kind)
+                .chain(list_envelopes_kind),
+            |builder| {
+                builder.set_add_flags(BackendFeatureSource::Context);
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let ids = query::resolve_or_confirm(
+            printer,
+            &backend,
+            folder,
+            &ids,
+            query.as_deref(),
+            self.args.query.yes,
+            "add flag(s)",
+            |n| format!("Add flag(s) {flags} to {n} envelope(s) from {folder}?"),
         )
         .await?;
 
-        backend.add_flags(folder, &ids, &flags).await?;
+        // no envelope matches, or the command was aborted
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        for ids in ids.chunks(BATCH_SIZE) {
+            backend.add_flags(folder, ids, &flags).await?;
+        }
 
         printer.print(format!("Flag(s) {flags} successfully added!"))
     }
@@ -2,8 +2,8 @@ mod add;
 mod remove;
 mod set;
 
-use color_eyre::Result;
 use clap::Subcommand;
+use color_eyre::Result;
 
 use crate::{config::TomlConfig, printer::Printer};
 
@@ -2,11 +2,14 @@ pub mod arg;
 pub mod command;
 pub mod config;
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::{collections::HashSet, ops};
 
 /// Represents the flag variants.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize)]
+///
+/// Flags are serialized as lowercase strings (seen, answered,
+/// flagged, deleted and draft), custom flags as their keyword.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd)]
 pub enum Flag {
     Seen,
     Answered,
@@ -30,9 +33,40 @@ impl From<&email::flag::Flag> for Flag {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+impl Flag {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Flag::Seen => "seen",
+            Flag::Answered => "answered",
+            Flag::Flagged => "flagged",
+            Flag::Deleted => "deleted",
+            Flag::Draft => "draft",
+            Flag::Custom(flag) => flag,
+        }
+    }
+}
+
+impl Serialize for Flag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Represents the set of flags of an envelope.
+///
+/// Flags are serialized as a sorted array, so that the output stays
+/// stable.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Flags(pub HashSet<Flag>);
 
+impl Serialize for Flags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut flags: Vec<_> = self.iter().collect();
+        flags.sort();
+        serializer.collect_seq(flags)
+    }
+}
+
 impl ops::Deref for Flags {
     type Target = HashSet<Flag>;
 
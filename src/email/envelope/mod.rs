@@ -1,177 +1,144 @@
 pub mod arg;
+pub mod column;
 pub mod command;
 pub mod config;
+pub mod details;
 pub mod flag;
+pub mod query;
+pub mod thread;
+pub mod watch;
 
 use color_eyre::Result;
-use comfy_table::{presets, Attribute, Cell, Color, ContentArrangement, Row, Table};
+use comfy_table::{ContentArrangement, Row, Table};
 use email::account::config::AccountConfig;
 use serde::Serialize;
-use std::ops;
+use std::{fmt, ops};
 
 use crate::{
     cache::IdMapper,
-    flag::{Flag, Flags},
+    flag::Flags,
     printer::{PrintTable, WriteColor},
+    theme::config::ThemeConfig,
 };
 
+use self::column::EnvelopeColumns;
+
+/// Represents an envelope mailbox.
+///
+/// JSON schema: `{"name": string | null, "addr": string}`.
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Mailbox {
     pub name: Option<String>,
     pub addr: String,
 }
 
+impl Mailbox {
+    /// Collect the mailboxes of the given parsed address header.
+    ///
+    /// Addresses without email address are skipped.
+    pub fn from_address(address: Option<&mail_parser::Address>) -> Vec<Self> {
+        address
+            .into_iter()
+            .flat_map(|address| address.iter())
+            .filter_map(|addr| {
+                Some(Mailbox {
+                    name: addr.name().map(ToOwned::to_owned),
+                    addr: addr.address()?.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Render the mailbox with both its name and its address.
+    pub fn to_full_string(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{name} <{}>", self.addr),
+            None => self.addr.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.addr),
+        }
+    }
+}
+
+/// Represents an envelope.
+///
+/// JSON schema: `{"id": string, "flags": [string], "subject": string,
+/// "from": mailbox, "to": mailbox, "date": string, "folder": string |
+/// null}`, plus `account` for multi-account listings and `cc`,
+/// `size`, `has_attachment` and `preview` when the matching columns
+/// are displayed. Threaded listings add a
+/// `replies` array of envelopes to each envelope.
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Envelope {
+    /// The envelope id, as used by other commands.
     pub id: String,
+    /// The flags, see [`Flags`] for their JSON representation.
     pub flags: Flags,
     pub subject: String,
     pub from: Mailbox,
     pub to: Mailbox,
+    /// The date, formatted according to the account configuration.
     pub date: String,
+    /// The date as a UNIX timestamp, used to sort envelopes.
+    #[serde(skip)]
+    pub timestamp: i64,
+    /// The folder the envelope belongs to.
+    pub folder: Option<String>,
+    /// The account the envelope belongs to, only set by
+    /// multi-account listings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+
+    // The following fields are not part of envelopes returned by
+    // backends: they are only filled when the whole message is
+    // fetched, see [`crate::backend::Backend::load_envelopes_details`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<Mailbox>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_attachment: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
 }
+
 impl From<Envelope> for Row {
     fn from(envelope: Envelope) -> Self {
-        let mut all_attributes = vec![];
-
-        let unseen = !envelope.flags.contains(&Flag::Seen);
-        if unseen {
-            all_attributes.push(Attribute::Bold)
-        }
-
-        let flags = {
-            let mut flags = String::new();
-            flags.push(if !unseen { ' ' } else { '✷' });
-            flags.push(if envelope.flags.contains(&Flag::Answered) {
-                '↵'
-            } else {
-                ' '
-            });
-            flags.push(if envelope.flags.contains(&Flag::Flagged) {
-                '⚑'
-            } else {
-                ' '
-            });
-            flags
-        };
-
-        let mut row = Row::new();
-
-        row.add_cell(
-            Cell::new(envelope.id)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::Red),
-        )
-        .add_cell(
-            Cell::new(flags)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::White),
-        )
-        .add_cell(
-            Cell::new(envelope.subject)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::Green),
-        )
-        .add_cell(
-            Cell::new(if let Some(name) = envelope.from.name {
-                name
-            } else {
-                envelope.from.addr
-            })
-            .add_attributes(all_attributes.clone())
-            .fg(Color::Blue),
-        )
-        .add_cell(
-            Cell::new(envelope.date)
-                .add_attributes(all_attributes)
-                .fg(Color::Yellow),
-        );
-
-        row
+        EnvelopeColumns::default().row(&envelope, &ThemeConfig::default())
     }
 }
 
 impl From<&Envelope> for Row {
     fn from(envelope: &Envelope) -> Self {
-        let mut all_attributes = vec![];
-
-        let unseen = !envelope.flags.contains(&Flag::Seen);
-        if unseen {
-            all_attributes.push(Attribute::Bold)
-        }
-
-        let flags = {
-            let mut flags = String::new();
-            flags.push(if !unseen { ' ' } else { '✷' });
-            flags.push(if envelope.flags.contains(&Flag::Answered) {
-                '↵'
-            } else {
-                ' '
-            });
-            flags.push(if envelope.flags.contains(&Flag::Flagged) {
-                '⚑'
-            } else {
-                ' '
-            });
-            flags
-        };
-
-        let mut row = Row::new();
-
-        row.add_cell(
-            Cell::new(&envelope.id)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::Red),
-        )
-        .add_cell(
-            Cell::new(flags)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::White),
-        )
-        .add_cell(
-            Cell::new(&envelope.subject)
-                .add_attributes(all_attributes.clone())
-                .fg(Color::Green),
-        )
-        .add_cell(
-            Cell::new(if let Some(name) = &envelope.from.name {
-                name
-            } else {
-                &envelope.from.addr
-            })
-            .add_attributes(all_attributes.clone())
-            .fg(Color::Blue),
-        )
-        .add_cell(
-            Cell::new(&envelope.date)
-                .add_attributes(all_attributes)
-                .fg(Color::Yellow),
-        );
-
-        row
+        EnvelopeColumns::default().row(envelope, &ThemeConfig::default())
     }
 }
 
 /// Represents the list of envelopes.
+///
+/// JSON schema: an array of envelopes.
 #[derive(Clone, Debug, Default, Serialize)]
-pub struct Envelopes(Vec<Envelope>);
+#[serde(transparent)]
+pub struct Envelopes {
+    envelopes: Vec<Envelope>,
+    /// The columns used to display envelopes as a table.
+    #[serde(skip)]
+    columns: EnvelopeColumns,
+    /// The theme used to display envelopes as a table.
+    #[serde(skip)]
+    theme: ThemeConfig,
+}
 
 impl From<Envelopes> for Table {
     fn from(envelopes: Envelopes) -> Self {
-        let mut table = Table::new();
-        table
-            .load_preset(presets::NOTHING)
-            .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(Row::from([
-                Cell::new("ID").add_attribute(Attribute::Reverse),
-                Cell::new("FLAGS").add_attribute(Attribute::Reverse),
-                Cell::new("SUBJECT").add_attribute(Attribute::Reverse),
-                Cell::new("FROM").add_attribute(Attribute::Reverse),
-                Cell::new("DATE").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(envelopes.0.into_iter().map(Row::from));
-
-        table
+        Table::from(&envelopes)
     }
 }
 
@@ -179,16 +146,14 @@ impl From<&Envelopes> for Table {
     fn from(envelopes: &Envelopes) -> Self {
         let mut table = Table::new();
         table
-            .load_preset(presets::NOTHING)
+            .load_preset(envelopes.theme.preset())
             .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(Row::from([
-                Cell::new("ID").add_attribute(Attribute::Reverse),
-                Cell::new("FLAGS").add_attribute(Attribute::Reverse),
-                Cell::new("SUBJECT").add_attribute(Attribute::Reverse),
-                Cell::new("FROM").add_attribute(Attribute::Reverse),
-                Cell::new("DATE").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(envelopes.0.iter().map(Row::from));
+            .set_header(envelopes.columns.header(&envelopes.theme))
+            .add_rows(
+                envelopes
+                    .iter()
+                    .map(|envelope| envelopes.columns.row(envelope, &envelopes.theme)),
+            );
 
         table
     }
@@ -216,11 +181,33 @@ impl Envelopes {
                         addr: envelope.to.addr.clone(),
                     },
                     date: envelope.format_date(config),
+                    timestamp: envelope.date.timestamp(),
+                    ..Default::default()
                 })
             })
             .collect::<Result<Vec<_>>>()?;
 
-        Ok(Envelopes(envelopes))
+        Ok(Envelopes {
+            envelopes,
+            columns: Default::default(),
+            theme: Default::default(),
+        })
+    }
+
+    pub fn columns(&self) -> &EnvelopeColumns {
+        &self.columns
+    }
+
+    pub fn set_columns(&mut self, columns: impl Into<EnvelopeColumns>) {
+        self.columns = columns.into();
+    }
+
+    pub fn theme(&self) -> &ThemeConfig {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: ThemeConfig) {
+        self.theme = theme;
     }
 }
 
@@ -228,7 +215,13 @@ impl ops::Deref for Envelopes {
     type Target = Vec<Envelope>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.envelopes
+    }
+}
+
+impl ops::DerefMut for Envelopes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.envelopes
     }
 }
 
@@ -0,0 +1,596 @@
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use email::{email::search_query, search_query::SearchEmailsQuery};
+use std::{cmp::Ordering, iter, ops::Range, process::exit};
+
+use crate::{backend::Backend, printer::Printer, ui::prompt};
+
+use super::{arg::ids::IdExpr, Envelope};
+
+/// The amount of envelopes mutated at once by commands applied on a
+/// query.
+pub const BATCH_SIZE: usize = 100;
+
+/// Parse the given filter and sort query.
+///
+/// Relative dates are resolved first, see [`resolve_dates`]. Parse
+/// errors are reported to stderr with their location in the query,
+/// then the program exits with a failure status.
+pub fn parse_or_exit(query: &str) -> SearchEmailsQuery {
+    let query = resolve_dates_or_exit(query);
+
+    match query.parse::<SearchEmailsQuery>() {
+        Ok(query) => query,
+        Err(main_err) => {
+            let search_query::error::Error::ParseError(errs, query) = &main_err;
+            for err in errs {
+                report(
+                    query,
+                    main_err.to_string(),
+                    err.span().into_range(),
+                    err.reason().to_string(),
+                );
+            }
+
+            exit(1)
+        }
+    }
+}
+
+/// Parse the given filter and sort query, after resolving its
+/// relative dates.
+///
+/// Unlike [`parse_or_exit`], errors are returned, which suits
+/// queries coming from the configuration.
+pub fn parse(query: &str) -> Result<SearchEmailsQuery> {
+    let resolved = resolve_dates(query, Local::now().date_naive())
+        .map_err(|(_, reason)| eyre!("cannot resolve relative date of query {query}: {reason}"))?;
+
+    resolved
+        .parse()
+        .map_err(|err| eyre!("cannot parse query {query}: {err}"))
+}
+
+/// Resolve relative dates of the given query, see
+/// [`resolve_dates`].
+///
+/// Invalid dates are reported to stderr with their location in the
+/// query, then the program exits with a failure status.
+pub fn resolve_dates_or_exit(query: &str) -> String {
+    match resolve_dates(query, Local::now().date_naive()) {
+        Ok(query) => query,
+        Err((span, reason)) => {
+            report(query, "cannot resolve relative date", span, reason);
+            exit(1)
+        }
+    }
+}
+
+/// Explain the given query: relative dates are resolved, then the
+/// query is parsed and its syntax tree pretty-printed.
+pub fn explain(query: &str) -> String {
+    let resolved = resolve_dates_or_exit(query);
+    let ast = parse_or_exit(&resolved);
+    format!("Query: {resolved}\n\n{ast:#?}")
+}
+
+fn report(query: &str, message: impl ToString, span: Range<usize>, reason: impl ToString) {
+    let source = "query";
+    Report::build(ReportKind::Error, source, span.start)
+        .with_message(message.to_string())
+        .with_label(
+            Label::new((source, span))
+                .with_message(reason.to_string())
+                .with_color(Color::Red),
+        )
+        .finish()
+        .eprint((source, Source::from(query)))
+        .unwrap();
+}
+
+/// Resolve relative dates of the given query into absolute
+/// yyyy-mm-dd dates, relatively to the given day.
+///
+/// Relative dates are accepted by the date, before and after
+/// conditions:
+///
+///  • today, yesterday and tomorrow
+///
+///  • an amount of days, weeks, months or years ago (7d, 2w, 3m, 1y)
+///
+///  • the last given day of the week, before today (last-monday)
+///
+/// Returns the span and the reason of the first invalid date.
+pub fn resolve_dates(query: &str, today: NaiveDate) -> Result<String, (Range<usize>, String)> {
+    let mut resolved = String::with_capacity(query.len());
+    let mut last = 0;
+    let mut expect = Expect::Condition;
+
+    for (span, token) in tokenize(query) {
+        expect = match expect {
+            Expect::Sort => Expect::Sort,
+            Expect::Pattern => Expect::Condition,
+            Expect::Date => {
+                let date = resolve_date(token, today).map_err(|reason| (span.clone(), reason))?;
+                if let Some(date) = date {
+                    resolved.push_str(&query[last..span.start]);
+                    resolved.push_str(&date.format("%Y-%m-%d").to_string());
+                    last = span.end;
+                }
+                Expect::Condition
+            }
+            Expect::Condition => Expect::after_condition(token),
+        };
+    }
+
+    resolved.push_str(&query[last..]);
+    Ok(resolved)
+}
+
+/// Split the given query into its filter and sort parts.
+///
+/// The query is scanned like [`resolve_dates`] does, so that the
+/// sort part only starts where a condition is expected, and not
+/// inside a pattern like `subject "order by"`.
+pub fn split_sort(query: &str) -> (&str, &str) {
+    let mut expect = Expect::Condition;
+
+    for (span, token) in tokenize(query) {
+        expect = match expect {
+            Expect::Condition => Expect::after_condition(token),
+            _ => Expect::Condition,
+        };
+
+        if let Expect::Sort = expect {
+            return (query[..span.start].trim(), query[span.start..].trim());
+        }
+    }
+
+    (query.trim(), "")
+}
+
+/// The kind of token expected while scanning a query.
+enum Expect {
+    Condition,
+    Pattern,
+    Date,
+    Sort,
+}
+
+impl Expect {
+    /// The kind of token expected after the given condition token.
+    fn after_condition(token: &str) -> Self {
+        match token.to_ascii_lowercase().as_str() {
+            "date" | "before" | "after" => Self::Date,
+            "from" | "to" | "subject" | "body" | "flag" => Self::Pattern,
+            "order" => Self::Sort,
+            _ => Self::Condition,
+        }
+    }
+}
+
+/// Split the given query into tokens separated by whitespaces, with
+/// their span. Quoted tokens can contain whitespaces.
+fn tokenize(query: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        let quoted = c == '"';
+
+        while let Some(&(idx, c)) = chars.peek() {
+            if quoted {
+                chars.next();
+                end = idx + c.len_utf8();
+                if c == '"' {
+                    break;
+                }
+            } else if c.is_whitespace() {
+                break;
+            } else {
+                chars.next();
+                end = idx + c.len_utf8();
+            }
+        }
+
+        tokens.push((start..end, &query[start..end]));
+    }
+
+    tokens
+}
+
+/// Resolve the given relative date, relatively to the given day.
+///
+/// Returns `None` for absolute dates, which are left as they are.
+fn resolve_date(date: &str, today: NaiveDate) -> Result<Option<NaiveDate>, String> {
+    if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() {
+        return Ok(None);
+    }
+
+    let out_of_range = || format!("cannot resolve date {date}: out of range");
+    let invalid = || {
+        format!(
+            "invalid date {date}, expected yyyy-mm-dd, today, yesterday, tomorrow, \
+             an amount of days, weeks, months or years ago (7d, 2w, 3m, 1y) \
+             or last-<weekday> (last-monday)"
+        )
+    };
+
+    let lowercase = date.to_ascii_lowercase();
+
+    let resolved = match lowercase.as_str() {
+        "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        "tomorrow" => today.succ_opt(),
+        date => {
+            if let Some(weekday) = date.strip_prefix("last-") {
+                let weekday: Weekday = weekday.parse().map_err(|_| invalid())?;
+                iter::successors(today.pred_opt(), NaiveDate::pred_opt)
+                    .take(7)
+                    .find(|day| day.weekday() == weekday)
+            } else {
+                let idx = date.len().saturating_sub(1);
+                if !date.is_char_boundary(idx) {
+                    return Err(invalid());
+                }
+                let (amount, unit) = date.split_at(idx);
+                let amount: u32 = amount.parse().map_err(|_| invalid())?;
+
+                match unit {
+                    "d" => today.checked_sub_days(Days::new(amount.into())),
+                    "w" => today.checked_sub_days(Days::new(u64::from(amount) * 7)),
+                    "m" => today.checked_sub_months(Months::new(amount)),
+                    "y" => amount
+                        .checked_mul(12)
+                        .and_then(|months| today.checked_sub_months(Months::new(months))),
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+    };
+
+    resolved.map(Some).ok_or_else(out_of_range)
+}
+
+/// Search envelopes of the given folder matching the given query,
+/// then ask the user to confirm the action to apply on them.
+///
+/// Returns an empty list if no envelope matches the query or if the
+/// action is not confirmed.
+pub async fn search_and_confirm(
+    printer: &mut impl Printer,
+    backend: &Backend,
+    folder: &str,
+    query: &str,
+    yes: bool,
+    prompt: impl Fn(usize) -> String,
+) -> Result<Vec<usize>> {
+    let ids = backend.search_ids(folder, parse_or_exit(query)).await?;
+
+    if ids.is_empty() {
+        printer.print_log("No envelope matches the query.")?;
+        return Ok(ids);
+    }
+
+    printer.print_log(format!("{} envelope(s) match the query.", ids.len()))?;
+
+    if !yes && !prompt::confirm(&prompt(ids.len()))? {
+        printer.print_log("Command aborted.")?;
+        return Ok(Vec::new());
+    }
+
+    Ok(ids)
+}
+
+/// Resolve the envelopes a command applies on: the envelopes of the
+/// given id expressions, or the envelopes matching the given query
+/// once confirmed, see [`search_and_confirm`].
+///
+/// The given action names the command in errors, like "add
+/// flag(s)". Ids and query cannot be combined.
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve_or_confirm(
+    printer: &mut impl Printer,
+    backend: &Backend,
+    folder: &str,
+    exprs: &[IdExpr],
+    query: Option<&str>,
+    yes: bool,
+    action: &str,
+    prompt: impl Fn(usize) -> String,
+) -> Result<Vec<usize>> {
+    match query {
+        None if exprs.is_empty() => bail!("cannot {action}: no envelope id given"),
+        None => backend.resolve_ids(folder, exprs).await,
+        Some(_) if !exprs.is_empty() => {
+            bail!("cannot {action}: envelope ids cannot be combined with a query")
+        }
+        Some(query) => search_and_confirm(printer, backend, folder, query, yes, prompt).await,
+    }
+}
+
+/// The kind of a sort criterion.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortKind {
+    Date,
+    From,
+    To,
+    Subject,
+}
+
+/// Extract the sort criteria of the given query, as pairs of kind
+/// and descending order.
+///
+/// Queries without sort part are sorted by descending date, like
+/// backends do.
+pub fn sort_criteria(query: Option<&str>) -> Vec<(SortKind, bool)> {
+    let (_, sort) = split_sort(query.unwrap_or_default());
+    let sort = sort.to_lowercase();
+    let mut criteria: Vec<(SortKind, bool)> = Vec::new();
+
+    if let Some(sort) = sort.strip_prefix("order") {
+        for token in sort.split_whitespace() {
+            match token {
+                "date" => criteria.push((SortKind::Date, false)),
+                "from" => criteria.push((SortKind::From, false)),
+                "to" => criteria.push((SortKind::To, false)),
+                "subject" => criteria.push((SortKind::Subject, false)),
+                "asc" | "desc" => {
+                    if let Some((_, desc)) = criteria.last_mut() {
+                        *desc = token == "desc";
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    if criteria.is_empty() {
+        criteria.push((SortKind::Date, true));
+    }
+
+    criteria
+}
+
+/// Sort the given envelopes according to the sort part of the given
+/// query.
+///
+/// Backends sort envelopes of a single folder, this function is
+/// used to merge envelopes coming from several folders.
+pub fn sort_envelopes(envelopes: &mut [Envelope], query: Option<&str>) {
+    let criteria = sort_criteria(query);
+
+    envelopes.sort_by(|a, b| {
+        criteria
+            .iter()
+            .map(|(kind, desc)| {
+                let ord = match kind {
+                    SortKind::Date => a.timestamp.cmp(&b.timestamp),
+                    SortKind::From => cmp_ignore_case(&a.from.to_string(), &b.from.to_string()),
+                    SortKind::To => cmp_ignore_case(&a.to.to_string(), &b.to.to_string()),
+                    SortKind::Subject => cmp_ignore_case(&a.subject, &b.subject),
+                };
+                if *desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+fn cmp_ignore_case(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::{resolve_date, resolve_dates, sort_criteria, split_sort, tokenize, SortKind};
+
+    /// Saturday, 2024-03-16.
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 16).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens: Vec<_> = tokenize("  from  \"John Doe\" and\tdate 7d")
+            .into_iter()
+            .map(|(span, token)| (span.start, token))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (2, "from"),
+                (8, "\"John Doe\""),
+                (19, "and"),
+                (23, "date"),
+                (28, "7d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        let tokens: Vec<_> = tokenize("subject \"a b")
+            .into_iter()
+            .map(|(_, token)| token)
+            .collect();
+
+        assert_eq!(tokens, ["subject", "\"a b"]);
+    }
+
+    #[test]
+    fn unicode_tokens() {
+        let query = "subject é date 1d";
+        for (span, token) in tokenize(query) {
+            assert_eq!(&query[span], token);
+        }
+    }
+
+    #[test]
+    fn absolute_date() {
+        assert_eq!(resolve_date("2024-01-31", today()), Ok(None));
+    }
+
+    #[test]
+    fn named_dates() {
+        assert_eq!(resolve_date("today", today()), Ok(date(2024, 3, 16)));
+        assert_eq!(resolve_date("Yesterday", today()), Ok(date(2024, 3, 15)));
+        assert_eq!(resolve_date("TOMORROW", today()), Ok(date(2024, 3, 17)));
+    }
+
+    #[test]
+    fn amounts_ago() {
+        assert_eq!(resolve_date("7d", today()), Ok(date(2024, 3, 9)));
+        assert_eq!(resolve_date("0d", today()), Ok(date(2024, 3, 16)));
+        assert_eq!(resolve_date("2w", today()), Ok(date(2024, 3, 2)));
+        assert_eq!(resolve_date("3m", today()), Ok(date(2023, 12, 16)));
+        assert_eq!(resolve_date("1y", today()), Ok(date(2023, 3, 16)));
+        // months are clamped to their last day
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert_eq!(resolve_date("1m", today), Ok(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn last_weekday() {
+        assert_eq!(resolve_date("last-monday", today()), Ok(date(2024, 3, 11)));
+        assert_eq!(resolve_date("last-friday", today()), Ok(date(2024, 3, 15)));
+        // the same day of the week means one week ago
+        assert_eq!(resolve_date("last-saturday", today()), Ok(date(2024, 3, 9)));
+        assert_eq!(resolve_date("last-sun", today()), Ok(date(2024, 3, 10)));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        for date in [
+            "",
+            "d",
+            "7",
+            "7x",
+            "-7d",
+            "last-",
+            "last-day",
+            "2024-13-01",
+            "7é",
+        ] {
+            assert!(resolve_date(date, today()).is_err(), "{date}");
+        }
+    }
+
+    #[test]
+    fn out_of_range_dates() {
+        let err = resolve_date("4294967295y", today()).unwrap_err();
+        assert!(err.contains("out of range"), "{err}");
+
+        let err = resolve_date("4294967295d", today()).unwrap_err();
+        assert!(err.contains("out of range"), "{err}");
+
+        let err = resolve_date("yesterday", NaiveDate::MIN).unwrap_err();
+        assert!(err.contains("out of range"), "{err}");
+    }
+
+    #[test]
+    fn query_dates() {
+        assert_eq!(
+            resolve_dates("date today and after 7d", today()).unwrap(),
+            "date 2024-03-16 and after 2024-03-09"
+        );
+        assert_eq!(
+            resolve_dates("BEFORE last-monday", today()).unwrap(),
+            "BEFORE 2024-03-11"
+        );
+        assert_eq!(
+            resolve_dates("date 2024-01-31", today()).unwrap(),
+            "date 2024-01-31"
+        );
+    }
+
+    #[test]
+    fn query_patterns() {
+        // values after from, subject and co. are patterns, never
+        // dates, even when they look like conditions
+        assert_eq!(
+            resolve_dates("subject date and from today and body 7d", today()).unwrap(),
+            "subject date and from today and body 7d"
+        );
+        assert_eq!(
+            resolve_dates("subject \"date 7d\" and date 7d", today()).unwrap(),
+            "subject \"date 7d\" and date 2024-03-09"
+        );
+    }
+
+    #[test]
+    fn query_sort() {
+        assert_eq!(
+            resolve_dates("after yesterday order by date desc", today()).unwrap(),
+            "after 2024-03-15 order by date desc"
+        );
+        assert_eq!(
+            resolve_dates("order by date today", today()).unwrap(),
+            "order by date today"
+        );
+    }
+
+    #[test]
+    fn query_errors() {
+        let (span, reason) = resolve_dates("from a and date 7x", today()).unwrap_err();
+        assert_eq!(span, 16..18);
+        assert!(reason.contains("invalid date 7x"), "{reason}");
+
+        let (span, reason) = resolve_dates("before 99999999y", today()).unwrap_err();
+        assert_eq!(span, 7..16);
+        assert!(reason.contains("out of range"), "{reason}");
+    }
+
+    #[test]
+    fn split_sort_part() {
+        assert_eq!(
+            split_sort("not flag seen order by date desc"),
+            ("not flag seen", "order by date desc")
+        );
+        assert_eq!(split_sort(" order by date "), ("", "order by date"));
+        assert_eq!(split_sort("from john"), ("from john", ""));
+        assert_eq!(split_sort(""), ("", ""));
+    }
+
+    #[test]
+    fn split_sort_in_pattern() {
+        assert_eq!(
+            split_sort("subject \"order by\" order by from"),
+            ("subject \"order by\"", "order by from")
+        );
+        assert_eq!(split_sort("subject order"), ("subject order", ""));
+        assert_eq!(split_sort("body \"order by\""), ("body \"order by\"", ""));
+    }
+
+    #[test]
+    fn sort_criteria_in_pattern() {
+        assert_eq!(
+            sort_criteria(Some("subject \"order by\"")),
+            [(SortKind::Date, true)]
+        );
+        assert_eq!(
+            sort_criteria(Some("subject order order by from asc date")),
+            [(SortKind::From, false), (SortKind::Date, false)]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use color_eyre::Result;
+use comfy_table::{ContentArrangement, Table};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    ops,
+};
+
+use crate::{
+    printer::{PrintTable, WriteColor},
+    theme::config::ThemeConfig,
+};
+
+use super::{column::EnvelopeColumns, Envelope, Envelopes};
+
+/// The threading headers of an envelope.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ThreadLinks {
+    /// The Message-ID of the envelope.
+    pub message_id: Option<String>,
+    /// The Message-ID of the envelope this one replies to.
+    pub in_reply_to: Option<String>,
+    /// The Message-IDs of the whole conversation, from the oldest
+    /// to the most recent.
+    pub references: Vec<String>,
+}
+
+/// Represents an envelope and the envelopes replying to it.
+#[derive(Clone, Debug, Serialize)]
+pub struct ThreadedEnvelope {
+    #[serde(flatten)]
+    pub envelope: Envelope,
+    pub replies: Vec<ThreadedEnvelope>,
+}
+
+/// Represents the list of envelopes grouped by conversation.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ThreadedEnvelopes {
+    threads: Vec<ThreadedEnvelope>,
+    /// The columns used to display envelopes as a table.
+    #[serde(skip)]
+    columns: EnvelopeColumns,
+    /// The theme used to display envelopes as a table.
+    #[serde(skip)]
+    theme: ThemeConfig,
+}
+
+impl ThreadedEnvelopes {
+    /// Group the given envelopes by conversation.
+    ///
+    /// The given links are the threading headers of the given
+    /// envelopes, in the same order. The parent of an envelope is the
+    /// envelope matching its In-Reply-To header, or the most recent
+    /// envelope of its References header. Envelopes without parent in
+    /// the given list are considered as roots. The order of the given
+    /// envelopes is preserved among roots and among replies.
+    pub fn new(envelopes: Envelopes, links: &[ThreadLinks]) -> Self {
+        let columns = envelopes.columns;
+        let theme = envelopes.theme;
+        let envelopes = envelopes.envelopes;
+
+        // envelopes are identified by their position, since ids are
+        // only unique within a folder
+        let mut idx_by_message_id = HashMap::new();
+        for (idx, links) in links.iter().enumerate() {
+            if let Some(message_id) = &links.message_id {
+                idx_by_message_id.insert(message_id.as_str(), idx);
+            }
+        }
+
+        let parents: HashMap<usize, usize> = links
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, links)| {
+                let parent = links
+                    .in_reply_to
+                    .iter()
+                    .chain(links.references.iter().rev())
+                    .filter_map(|message_id| idx_by_message_id.get(message_id.as_str()))
+                    .find(|parent| **parent != idx)?;
+                Some((idx, *parent))
+            })
+            .collect();
+
+        let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut roots = Vec::new();
+        for idx in 0..envelopes.len() {
+            match parents.get(&idx) {
+                Some(parent) => children.entry(*parent).or_default().push(idx),
+                None => roots.push(idx),
+            }
+        }
+
+        let mut visited = HashSet::new();
+        let mut threads: Vec<ThreadedEnvelope> = roots
+            .into_iter()
+            .map(|root| Self::build(&envelopes, root, &children, &mut visited))
+            .collect();
+
+        // envelopes referencing each other in a loop cannot be
+        // reached from a root, so they become roots themselves
+        for idx in 0..envelopes.len() {
+            if !visited.contains(&idx) {
+                threads.push(Self::build(&envelopes, idx, &children, &mut visited));
+            }
+        }
+
+        Self {
+            threads,
+            columns,
+            theme,
+        }
+    }
+
+    fn build(
+        envelopes: &[Envelope],
+        idx: usize,
+        children: &HashMap<usize, Vec<usize>>,
+        visited: &mut HashSet<usize>,
+    ) -> ThreadedEnvelope {
+        visited.insert(idx);
+
+        let mut replies = Vec::new();
+        for reply in children.get(&idx).into_iter().flatten() {
+            if !visited.contains(reply) {
+                replies.push(Self::build(envelopes, *reply, children, visited));
+            }
+        }
+
+        ThreadedEnvelope {
+            envelope: envelopes[idx].clone(),
+            replies,
+        }
+    }
+
+    fn add_rows(
+        &self,
+        table: &mut Table,
+        threads: &[ThreadedEnvelope],
+        prefix: &str,
+        depth: usize,
+    ) {
+        for (i, thread) in threads.iter().enumerate() {
+            let last = i + 1 == threads.len();
+            let mut envelope = thread.envelope.clone();
+
+            if depth > 0 {
+                let branch = if last { "└─ " } else { "├─ " };
+                envelope.subject = format!("{prefix}{branch}{}", envelope.subject);
+            }
+
+            table.add_row(self.columns.row(&envelope, &self.theme));
+
+            let prefix = match depth {
+                0 => String::new(),
+                _ if last => format!("{prefix}   "),
+                _ => format!("{prefix}│  "),
+            };
+
+            self.add_rows(table, &thread.replies, &prefix, depth + 1);
+        }
+    }
+}
+
+impl ops::Deref for ThreadedEnvelopes {
+    type Target = Vec<ThreadedEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.threads
+    }
+}
+
+impl From<&ThreadedEnvelopes> for Table {
+    fn from(threads: &ThreadedEnvelopes) -> Self {
+        let mut table = Table::new();
+        table
+            .load_preset(threads.theme.preset())
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(threads.columns.header(&threads.theme));
+
+        threads.add_rows(&mut table, &threads.threads, "", 0);
+
+        table
+    }
+}
+
+impl PrintTable for ThreadedEnvelopes {
+    fn print_table(&self, writer: &mut dyn WriteColor, table_max_width: Option<u16>) -> Result<()> {
+        let mut table = Table::from(self);
+        if let Some(width) = table_max_width {
+            table.set_width(width);
+        }
+        writeln!(writer)?;
+        write!(writer, "{}", table)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Envelope, Envelopes, ThreadLinks, ThreadedEnvelope, ThreadedEnvelopes};
+
+    fn envelopes(ids: &[&str]) -> Envelopes {
+        Envelopes {
+            envelopes: ids
+                .iter()
+                .map(|id| Envelope {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            columns: Default::default(),
+            theme: Default::default(),
+        }
+    }
+
+    fn links(message_id: &str, in_reply_to: Option<&str>, references: &[&str]) -> ThreadLinks {
+        ThreadLinks {
+            message_id: Some(message_id.to_owned()),
+            in_reply_to: in_reply_to.map(ToOwned::to_owned),
+            references: references.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Represent threads as nested ids, like `1(2(3),4)`.
+    fn tree(threads: &[ThreadedEnvelope]) -> String {
+        threads
+            .iter()
+            .map(|thread| {
+                if thread.replies.is_empty() {
+                    thread.envelope.id.clone()
+                } else {
+                    format!("{}({})", thread.envelope.id, tree(&thread.replies))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn in_reply_to() {
+        let threads = ThreadedEnvelopes::new(
+            envelopes(&["1", "2", "3", "4"]),
+            &[
+                links("a", None, &[]),
+                links("b", Some("a"), &[]),
+                links("c", Some("b"), &[]),
+                links("d", Some("a"), &[]),
+            ],
+        );
+
+        assert_eq!(tree(&threads), "1(2(3),4)");
+    }
+
+    #[test]
+    fn references() {
+        let threads = ThreadedEnvelopes::new(
+            envelopes(&["1", "2", "3"]),
+            &[
+                links("a", None, &[]),
+                links("b", Some("a"), &["a"]),
+                // the direct parent is missing, the most recent
+                // known reference is used instead
+                links("c", Some("missing"), &["a", "b", "missing"]),
+            ],
+        );
+
+        assert_eq!(tree(&threads), "1(2(3))");
+    }
+
+    #[test]
+    fn missing_parent() {
+        let threads = ThreadedEnvelopes::new(
+            envelopes(&["1", "2"]),
+            &[links("a", Some("missing"), &[]), links("b", None, &[])],
+        );
+
+        assert_eq!(tree(&threads), "1,2");
+    }
+
+    #[test]
+    fn order() {
+        let threads = ThreadedEnvelopes::new(
+            envelopes(&["3", "2", "1"]),
+            &[
+                links("c", Some("a"), &[]),
+                links("b", Some("a"), &[]),
+                links("a", None, &[]),
+            ],
+        );
+
+        assert_eq!(tree(&threads), "1(3,2)");
+    }
+
+    #[test]
+    fn self_reply() {
+        let threads = ThreadedEnvelopes::new(envelopes(&["1"]), &[links("a", Some("a"), &["a"])]);
+
+        assert_eq!(tree(&threads), "1");
+    }
+
+    #[test]
+    fn reply_loop() {
+        let threads = ThreadedEnvelopes::new(
+            envelopes(&["1", "2", "3"]),
+            &[
+                links("a", Some("b"), &[]),
+                links("b", Some("a"), &[]),
+                links("c", None, &[]),
+            ],
+        );
+
+        // every envelope is kept exactly once
+        assert_eq!(tree(&threads), "3,1(2)");
+    }
+
+    #[test]
+    fn without_links() {
+        let threads = ThreadedEnvelopes::new(envelopes(&["1", "2"]), &[ThreadLinks::default(); 2]);
+
+        assert_eq!(tree(&threads), "1,2");
+    }
+}
@@ -0,0 +1,461 @@
+//! Module related to envelope watchers.
+//!
+//! A watcher either runs the hooks of its account when envelopes of
+//! its folder change, or writes change events. Backends only expose
+//! changes through hooks, so events are found by comparing successive
+//! listings of a folder. A listing is triggered by a hook of the
+//! backend watcher, or after an interval when the backend does not
+//! notify changes. Those listings are also used to apply the rules of
+//! the account on new envelopes.
+
+use clap::ValueEnum;
+use color_eyre::{eyre::Context, Result};
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    envelope::{list::ListEnvelopesOptions, watch::config::WatchEnvelopeConfig},
+    watch::config::{WatchFn, WatchHook},
+};
+use futures::future;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::{
+    account::config::TomlAccountConfig,
+    backend::Backend,
+    flag::Flags,
+    rule::{self, RuleHistory},
+};
+
+use super::{Envelope, Envelopes};
+
+/// The format of envelope change events.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum WatchEventsFmt {
+    /// One JSON object per line.
+    #[default]
+    Ndjson,
+}
+
+/// Represents an envelope change event.
+///
+/// JSON schema: `{"event": "new", "account": string, "folder":
+/// string, "envelope": envelope}`, `{"event": "flags", "account":
+/// string, "folder": string, "id": string, "old": [string], "new":
+/// [string]}` or `{"event": "removed", "account": string, "folder":
+/// string, "id": string}`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum WatchEvent {
+    New {
+        account: String,
+        folder: String,
+        envelope: Envelope,
+    },
+    Flags {
+        account: String,
+        folder: String,
+        id: String,
+        old: Flags,
+        new: Flags,
+    },
+    Removed {
+        account: String,
+        folder: String,
+        id: String,
+    },
+}
+
+impl WatchEvent {
+    /// Write the event to the standard output, using the given
+    /// format.
+    pub fn write(&self, fmt: WatchEventsFmt) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+
+        match fmt {
+            WatchEventsFmt::Ndjson => {
+                serde_json::to_writer(&mut stdout, self).context("cannot write event")?;
+                writeln!(stdout)?;
+            }
+        }
+
+        Ok(stdout.flush()?)
+    }
+}
+
+/// Represents the last known state of a folder.
+#[derive(Debug)]
+pub struct FolderSnapshot {
+    account: String,
+    folder: String,
+    /// The envelopes of the folder, by id. Unset until the first
+    /// listing.
+    envelopes: Option<HashMap<String, Envelope>>,
+}
+
+impl FolderSnapshot {
+    pub fn new(account: impl ToString, folder: impl ToString) -> Self {
+        Self {
+            account: account.to_string(),
+            folder: folder.to_string(),
+            envelopes: None,
+        }
+    }
+
+    /// Replace the snapshot by the given envelopes, and return the
+    /// changes since the previous snapshot.
+    ///
+    /// The first snapshot does not emit any event.
+    pub fn update(&mut self, envelopes: Envelopes) -> Vec<WatchEvent> {
+        let next: HashMap<String, Envelope> = envelopes
+            .iter()
+            .map(|envelope| (envelope.id.clone(), envelope.clone()))
+            .collect();
+
+        let events = match &self.envelopes {
+            Some(prev) => self.diff(prev, &envelopes, &next),
+            None => Vec::new(),
+        };
+
+        self.envelopes = Some(next);
+        events
+    }
+
+    fn diff(
+        &self,
+        prev: &HashMap<String, Envelope>,
+        envelopes: &Envelopes,
+        next: &HashMap<String, Envelope>,
+    ) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+
+        // new envelopes and flag changes follow the listing order
+        for envelope in envelopes.iter() {
+            match prev.get(&envelope.id) {
+                None => events.push(WatchEvent::New {
+                    account: self.account.clone(),
+                    folder: self.folder.clone(),
+                    envelope: envelope.clone(),
+                }),
+                Some(prev) if prev.flags != envelope.flags => events.push(WatchEvent::Flags {
+                    account: self.account.clone(),
+                    folder: self.folder.clone(),
+                    id: envelope.id.clone(),
+                    old: prev.flags.clone(),
+                    new: envelope.flags.clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+
+        let mut removed: Vec<&String> = prev.keys().filter(|id| !next.contains_key(*id)).collect();
+        removed.sort();
+
+        for id in removed {
+            events.push(WatchEvent::Removed {
+                account: self.account.clone(),
+                folder: self.folder.clone(),
+                id: id.clone(),
+            });
+        }
+
+        events
+    }
+}
+
+/// The delay before the first reconnection of a watcher task, and
+/// before restarting a task that stopped cleanly.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The maximum delay between two reconnections of a watcher task. A
+/// task running longer than this delay before failing is considered
+/// healthy, and reconnects after the minimum delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Represents the delay before restarting a watcher task.
+struct Backoff(Duration);
+
+impl Backoff {
+    /// Log the end of a run of the given task, then wait before
+    /// restarting it.
+    ///
+    /// The delay is doubled after each consecutive failure. A clean
+    /// stop, or a failure after a healthy run, resets it.
+    async fn restart(&mut self, task: &str, res: Result<()>, elapsed: Duration) {
+        if res.is_ok() || elapsed > MAX_BACKOFF {
+            self.0 = MIN_BACKOFF;
+        }
+
+        match res {
+            Ok(()) => {
+                info!("{task} stopped, restarting");
+                tokio::time::sleep(self.0).await;
+            }
+            Err(err) => {
+                warn!("{task} failed, retrying in {}s: {err}", self.0.as_secs());
+                tokio::time::sleep(self.0).await;
+                self.0 = (self.0 * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Represents a watcher of a folder of an account.
+pub struct Watcher {
+    pub toml_account_config: Arc<TomlAccountConfig>,
+    pub account_config: Arc<AccountConfig>,
+    pub folder: String,
+    /// The format of change events. When unset, the hooks of the
+    /// account are executed instead.
+    pub events: Option<WatchEventsFmt>,
+    /// The maximum delay between two listings of the folder, used to
+    /// find change events and new envelopes to apply rules on.
+    pub interval: Duration,
+    /// The rules history of the account, shared by the watchers of
+    /// its folders. Unset when the account has no rule.
+    pub rule_history: Option<RuleHistory>,
+}
+
+impl Watcher {
+    /// Watch the folder forever.
+    ///
+    /// The backend watcher and the poller run as independent tasks,
+    /// so that a failing listing does not tear down the backend
+    /// watcher. Failures are logged, then the failing task restarts
+    /// after a delay doubled after each consecutive failure.
+    pub async fn run(self) {
+        if self.events.is_none() && self.rule_history.is_none() {
+            return self.supervise_hooks(None).await;
+        }
+
+        let changes = Arc::new(Notify::new());
+        let watch = self.supervise_hooks(Some(changes.clone()));
+        let poll = self.supervise_poll(&changes);
+        future::join(watch, poll).await;
+    }
+
+    /// Watch the folder with the backend forever, restarting after
+    /// failures.
+    async fn supervise_hooks(&self, changes: Option<Arc<Notify>>) {
+        let task = format!(
+            "watcher of folder {} ({})",
+            self.folder, self.account_config.name
+        );
+        let mut backoff = Backoff(MIN_BACKOFF);
+
+        loop {
+            let start = Instant::now();
+            let res = self.execute_hooks(changes.clone()).await;
+            backoff.restart(&task, res, start.elapsed()).await;
+        }
+    }
+
+    /// Poll the folder forever, restarting after failures.
+    async fn supervise_poll(&self, changes: &Notify) {
+        let account = &self.account_config.name;
+        let task = format!("poller of folder {} ({account})", self.folder);
+        let mut snapshot = FolderSnapshot::new(account, &self.folder);
+        let mut backoff = Backoff(MIN_BACKOFF);
+
+        loop {
+            let start = Instant::now();
+            let res = self.poll(&mut snapshot, changes).await;
+            backoff.restart(&task, res, start.elapsed()).await;
+        }
+    }
+
+    /// Watch the folder with the backend, executing the hooks of the
+    /// account.
+    ///
+    /// When a change notifier is given, it is notified on any change.
+    /// In events mode, it replaces the hooks of the account.
+    async fn execute_hooks(&self, changes: Option<Arc<Notify>>) -> Result<()> {
+        let watch_envelopes_kind = self.toml_account_config.watch_envelopes_kind();
+
+        let mut account_config = self.account_config.clone();
+        if let Some(changes) = changes {
+            let config = Arc::make_mut(&mut account_config);
+            let envelope = config.envelope.get_or_insert_with(Default::default);
+
+            if self.events.is_some() {
+                envelope.watch = Some(WatchEnvelopeConfig::default());
+            }
+
+            let watch = envelope.watch.get_or_insert_with(Default::default);
+            let any = watch.any.get_or_insert_with(WatchHook::default);
+            any.callback = Some(WatchFn::new(move |_| {
+                changes.notify_one();
+                async { Ok(()) }
+            }));
+        }
+
+        let backend = Backend::new(
+            self.toml_account_config.clone(),
+            account_config,
+            watch_envelopes_kind,
+            |builder| builder.set_watch_envelopes(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        backend.watch_envelopes(&self.folder).await
+    }
+
+    /// List the folder when the backend notifies a change, or after
+    /// the interval, in order to write change events and to apply
+    /// rules on new envelopes.
+    ///
+    /// Each listing fetches all the envelopes of the folder, so the
+    /// interval only bounds the delay when the backend does not
+    /// notify changes. The snapshot outlives reconnections, so that
+    /// changes occurring meanwhile are not lost. Rules failures are
+    /// logged, they do not interrupt the watcher.
+    async fn poll(&self, snapshot: &mut FolderSnapshot, changes: &Notify) -> Result<()> {
+        let backend = rule::build_backend(
+            self.toml_account_config.clone(),
+            self.account_config.clone(),
+        )
+        .await?;
+
+        loop {
+            let opts = ListEnvelopesOptions {
+                page: 0,
+                page_size: 0,
+                query: None,
+            };
+            let envelopes = backend.list_envelopes(&self.folder, opts).await?;
+            let events = snapshot.update(envelopes);
+
+            if let Some(fmt) = self.events {
+                for event in &events {
+                    event.write(fmt)?;
+                }
+            }
+
+            let new_ids: HashSet<usize> = events
+                .iter()
+                .filter_map(|event| match event {
+                    WatchEvent::New { envelope, .. } => envelope.id.parse().ok(),
+                    _ => None,
+                })
+                .collect();
+
+            if let Some(history) = self.rule_history.as_ref().filter(|_| !new_ids.is_empty()) {
+                let res = rule::apply_to_envelopes(
+                    &backend,
+                    history,
+                    &self.toml_account_config,
+                    &self.folder,
+                    &new_ids,
+                )
+                .await;
+
+                if let Err(err) = res {
+                    warn!("cannot apply rules to folder {}: {err}", self.folder);
+                }
+            }
+
+            // a change notified during the listing is kept by the
+            // notifier, and triggers the next listing immediately
+            let _ = tokio::time::timeout(self.interval, changes.notified()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::flag::{Flag, Flags};
+
+    use super::{Envelope, Envelopes, FolderSnapshot, WatchEvent};
+
+    fn envelopes(envelopes: &[(&str, &[Flag])]) -> Envelopes {
+        Envelopes {
+            envelopes: envelopes
+                .iter()
+                .map(|(id, flags)| Envelope {
+                    id: id.to_string(),
+                    flags: Flags(flags.iter().cloned().collect::<HashSet<_>>()),
+                    ..Default::default()
+                })
+                .collect(),
+            columns: Default::default(),
+            theme: Default::default(),
+        }
+    }
+
+    /// Represent events as short strings, like `new 1` or `removed 2`.
+    fn describe(events: &[WatchEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                WatchEvent::New { envelope, .. } => format!("new {}", envelope.id),
+                WatchEvent::Flags { id, old, new, .. } => {
+                    format!("flags {id} {} -> {}", old.0.len(), new.0.len())
+                }
+                WatchEvent::Removed { id, .. } => format!("removed {id}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_update() {
+        let mut snapshot = FolderSnapshot::new("account", "INBOX");
+        let events = snapshot.update(envelopes(&[("1", &[]), ("2", &[Flag::Seen])]));
+
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn new_envelopes() {
+        let mut snapshot = FolderSnapshot::new("account", "INBOX");
+        snapshot.update(envelopes(&[("1", &[])]));
+        let events = snapshot.update(envelopes(&[("3", &[]), ("1", &[]), ("2", &[])]));
+
+        // events follow the listing order
+        assert_eq!(describe(&events), vec!["new 3", "new 2"]);
+
+        match &events[0] {
+            WatchEvent::New {
+                account, folder, ..
+            } => {
+                assert_eq!(account, "account");
+                assert_eq!(folder, "INBOX");
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn flag_changes() {
+        let mut snapshot = FolderSnapshot::new("account", "INBOX");
+        snapshot.update(envelopes(&[("1", &[]), ("2", &[Flag::Seen])]));
+        let events = snapshot.update(envelopes(&[
+            ("1", &[Flag::Seen, Flag::Flagged]),
+            ("2", &[Flag::Seen]),
+        ]));
+
+        assert_eq!(describe(&events), vec!["flags 1 0 -> 2"]);
+    }
+
+    #[test]
+    fn removed_envelopes() {
+        let mut snapshot = FolderSnapshot::new("account", "INBOX");
+        snapshot.update(envelopes(&[("3", &[]), ("1", &[]), ("2", &[])]));
+        let events = snapshot.update(envelopes(&[("2", &[])]));
+
+        // removals are sorted by id, after other events
+        assert_eq!(describe(&events), vec!["removed 1", "removed 3"]);
+
+        // the snapshot is replaced by the last listing
+        let events = snapshot.update(envelopes(&[("2", &[]), ("4", &[])]));
+        assert_eq!(describe(&events), vec!["new 4"]);
+    }
+}
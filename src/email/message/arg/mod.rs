@@ -1,4 +1,12 @@
 use clap::Parser;
+use color_eyre::{eyre::Context, Result};
+use mail_parser::{Encoding, Message, MessageParser, MimeHeaders, PartType};
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    ops::Range,
+    path::PathBuf,
+};
 
 pub mod body;
 pub mod header;
@@ -11,10 +19,188 @@ pub struct MessageRawArg {
     #[arg(trailing_var_arg = true)]
     #[arg(name = "message_raw", value_name = "MESSAGE")]
     pub raw: Vec<String>,
+
+    /// Read the raw message from the given file.
+    ///
+    /// The file is read as is, which suits messages containing 8-bit
+    /// or binary parts.
+    #[arg(long, value_name = "PATH", conflicts_with = "message_raw")]
+    pub file: Option<PathBuf>,
 }
 
 impl MessageRawArg {
     pub fn raw(self) -> String {
         self.raw.join(" ").replace('\r', "").replace('\n', "\r\n")
     }
+
+    /// Read the raw message from the file, the arguments or the
+    /// standard input, in this order.
+    ///
+    /// The file and the standard input are read as bytes, then their
+    /// line endings are normalized, see [`normalize_line_endings`].
+    pub fn read(self) -> Result<Vec<u8>> {
+        if let Some(path) = &self.file {
+            let raw = fs::read(path).with_context(|| format!("cannot read message at {path:?}"))?;
+            return Ok(normalize_line_endings(&raw));
+        }
+
+        if !self.raw.is_empty() || io::stdin().is_terminal() {
+            return Ok(self.raw().into_bytes());
+        }
+
+        let mut raw = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut raw)
+            .context("cannot read message from standard input")?;
+
+        Ok(normalize_line_endings(&raw))
+    }
+}
+
+/// Turn bare line feeds of the given raw message into CRLF, as
+/// required by RFC 5322.
+///
+/// Existing CRLF are kept, and bytes are not decoded: invalid UTF-8
+/// sequences and 8-bit parts are preserved. Bodies of MIME parts
+/// declaring a binary transfer encoding are kept as is, since their
+/// line endings are part of their content.
+pub fn normalize_line_endings(raw: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(raw.len() + raw.len() / 32);
+    let mut pos = 0;
+
+    if let Some(msg) = MessageParser::new().parse(raw) {
+        let mut bodies = Vec::new();
+        binary_bodies(&msg, 0, &mut bodies);
+        bodies.sort_by_key(|body| body.start);
+
+        for body in bodies {
+            let start = body.start.max(pos).min(raw.len());
+            let end = body.end.min(raw.len());
+            // bodies nested in a previous binary body
+            if end <= start {
+                continue;
+            }
+
+            push_crlf(&mut normalized, raw, pos..start);
+            normalized.extend_from_slice(&raw[start..end]);
+            pos = end;
+        }
+    }
+
+    push_crlf(&mut normalized, raw, pos..raw.len());
+    normalized
+}
+
+/// Collect the ranges of the bodies of the parts of the given message
+/// declaring a binary transfer encoding, shifted by the given offset.
+///
+/// Nested messages are searched as well, unless they are encoded,
+/// since their parts are then not part of the raw message.
+fn binary_bodies(msg: &Message, offset: usize, bodies: &mut Vec<Range<usize>>) {
+    for part in &msg.parts {
+        let is_binary = part
+            .content_transfer_encoding()
+            .is_some_and(|encoding| encoding.trim().eq_ignore_ascii_case("binary"));
+
+        if is_binary {
+            bodies.push(offset + part.offset_body..offset + part.offset_end);
+            continue;
+        }
+
+        if let (PartType::Message(nested), Encoding::None) = (&part.body, part.encoding) {
+            binary_bodies(nested, offset + part.offset_body, bodies);
+        }
+    }
+}
+
+/// Push the given range of the given bytes, preceding bare line feeds
+/// by a carriage return.
+fn push_crlf(out: &mut Vec<u8>, bytes: &[u8], range: Range<usize>) {
+    let mut prev = range.start.checked_sub(1).map(|i| bytes[i]);
+
+    for b in &bytes[range] {
+        if *b == b'\n' && prev != Some(b'\r') {
+            out.push(b'\r');
+        }
+        out.push(*b);
+        prev = Some(*b);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalize_line_endings;
+
+    #[test]
+    fn normalize_bare_line_feeds() {
+        let raw = b"Subject: test\n\nline\r\nline\n";
+        assert_eq!(
+            normalize_line_endings(raw),
+            b"Subject: test\r\n\r\nline\r\nline\r\n"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_8bit() {
+        let raw = b"Subject: test\n\n\xff\xfe\n";
+        assert_eq!(
+            normalize_line_endings(raw),
+            b"Subject: test\r\n\r\n\xff\xfe\r\n"
+        );
+    }
+
+    #[test]
+    fn normalize_without_body() {
+        assert_eq!(
+            normalize_line_endings(b"Subject: test\n"),
+            b"Subject: test\r\n"
+        );
+        assert_eq!(normalize_line_endings(b""), b"");
+    }
+
+    #[test]
+    fn normalize_binary_message() {
+        let raw = b"Content-Transfer-Encoding: binary\n\n\x00\n\r\n";
+        assert_eq!(
+            normalize_line_endings(raw),
+            b"Content-Transfer-Encoding: binary\r\n\r\n\x00\n\r\n"
+        );
+    }
+
+    #[test]
+    fn normalize_binary_part() {
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=b\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/plain\n",
+            "\n",
+            "content-transfer-encoding: binary\n",
+            "--b\n",
+            "Content-Type: application/octet-stream\n",
+            "Content-Transfer-Encoding: BINARY\n",
+            "\n",
+            "bin\nary\n",
+            "--b--\n",
+        );
+        let normalized = concat!(
+            "Content-Type: multipart/mixed; boundary=b\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "content-transfer-encoding: binary\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Transfer-Encoding: BINARY\r\n",
+            "\r\n",
+            "bin\nary\r\n",
+            "--b--\r\n",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&normalize_line_endings(raw.as_bytes())),
+            normalized
+        );
+    }
 }
@@ -8,8 +8,11 @@ use uuid::Uuid;
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs, folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::{EnvelopeIdsArgs, IdExpr},
+    folder::arg::name::FolderNameOptionalFlag,
     printer::Printer,
 };
 
@@ -38,24 +41,29 @@ impl AttachmentDownloadCommand {
         info!("executing download attachment(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), &self.envelopes.ids())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
         let get_messages_kind = toml_account_config.get_messages_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config.clone(),
-            get_messages_kind,
-            |builder| builder.set_get_messages(BackendFeatureSource::Context),
+            get_messages_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_get_messages(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
         )
         .await?;
 
+        let ids = &backend.resolve_ids(folder, &self.envelopes.ids()).await?;
+
         let emails = backend.get_messages(folder, ids).await?;
 
         let mut emails_count = 0;
@@ -9,8 +9,14 @@ use crate::{
     account::arg::name::AccountNameFlag,
     backend::Backend,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs,
-    folder::arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+    envelope::{
+        arg::ids::{EnvelopeIdsOrQueryArgs, IdExpr},
+        query,
+    },
+    folder::{
+        arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+        search,
+    },
     printer::Printer,
 };
 
@@ -24,7 +30,7 @@ pub struct MessageCopyCommand {
     pub target_folder: TargetFolderNameArg,
 
     #[command(flatten)]
-    pub envelopes: EnvelopeIdsArgs,
+    pub envelopes: EnvelopeIdsOrQueryArgs,
 
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
@@ -38,30 +44,64 @@ impl MessageCopyCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing copy message(s) command");
 
-        let source = &self.source_folder.name;
         let target = &self.target_folder.name;
-        let ids = &self.envelopes.ids;
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), &self.envelopes.ids())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
+        let (source, query) = search::resolve_folder(
+            &toml_account_config,
+            &self.source_folder.name,
+            self.envelopes.query.query.as_deref(),
+            !self.envelopes.ids.is_empty(),
+        )?;
+        let source = &source;
+
         let copy_messages_kind = toml_account_config.copy_messages_kind();
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config,
-            copy_messages_kind,
-            |builder| builder.set_copy_messages(BackendFeatureSource::Context),
+            copy_messages_kind
+                .into_iter()
+                .chain(get_envelope_kind)
+                .chain(list_envelopes_kind),
+            |builder| {
+                builder.set_copy_messages(BackendFeatureSource::Context);
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
         )
         .await?;
 
-        backend.copy_messages(source, target, ids).await?;
+        let ids = query::resolve_or_confirm(
+            printer,
+            &backend,
+            source,
+            &self.envelopes.ids(),
+            query.as_deref(),
+            self.envelopes.query.yes,
+            "copy message(s)",
+            |n| format!("Copy {n} message(s) from {source} to {target}?"),
+        )
+        .await?;
+
+        // no envelope matches, or the command was aborted
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let aliases = backend.copy_messages(source, target, &ids).await?;
+
+        printer.print_log(format!(
+            "Message(s) successfully copied from {source} to {target}:"
+        ))?;
 
-        printer.print(format!(
-            "Message(s) successfully copied from {source} to {target}!"
-        ))
+        printer.print(aliases)
     }
 }
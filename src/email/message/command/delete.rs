@@ -6,8 +6,14 @@ use tracing::info;
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs, folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::{
+        arg::ids::{EnvelopeIdsOrQueryArgs, IdExpr},
+        query,
+    },
+    folder::{arg::name::FolderOrSearchNameOptionalFlag, search},
     printer::Printer,
 };
 
@@ -20,10 +26,10 @@ use crate::{
 #[derive(Debug, Parser)]
 pub struct MessageDeleteCommand {
     #[command(flatten)]
-    pub folder: FolderNameOptionalFlag,
+    pub folder: FolderOrSearchNameOptionalFlag,
 
     #[command(flatten)]
-    pub envelopes: EnvelopeIdsArgs,
+    pub envelopes: EnvelopeIdsOrQueryArgs,
 
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
@@ -37,26 +43,57 @@ impl MessageDeleteCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing delete message(s) command");
 
-        let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
-
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), &self.envelopes.ids())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
+        let (folder, query) = search::resolve_folder(
+            &toml_account_config,
+            &self.folder.name,
+            self.envelopes.query.query.as_deref(),
+            !self.envelopes.ids.is_empty(),
+        )?;
+        let folder = &folder;
+
         let delete_messages_kind = toml_account_config.delete_messages_kind();
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config,
-            delete_messages_kind,
-            |builder| builder.set_delete_messages(BackendFeatureSource::Context),
+            delete_messages_kind
+                .into_iter()
+                .chain(get_envelope_kind)
+                .chain(list_envelopes_kind),
+            |builder| {
+                builder.set_delete_messages(BackendFeatureSource::Context);
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
         )
         .await?;
 
-        backend.delete_messages(folder, ids).await?;
+        let ids = query::resolve_or_confirm(
+            printer,
+            &backend,
+            folder,
+            &self.envelopes.ids(),
+            query.as_deref(),
+            self.envelopes.query.yes,
+            "delete message(s)",
+            |n| format!("Delete {n} message(s) from {folder}?"),
+        )
+        .await?;
+
+        // no envelope matches, or the command was aborted
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        backend.delete_messages(folder, &ids).await?;
 
         printer.print(format!("Message(s) successfully removed from {folder}!"))
     }
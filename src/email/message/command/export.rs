@@ -0,0 +1,161 @@
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+use email::{backend::feature::BackendFeatureSource, envelope::list::ListEnvelopesOptions};
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use std::path::PathBuf;
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::{
+        arg::ids::{IdExpr, IdExprs},
+        query::{self, BATCH_SIZE},
+    },
+    folder::{arg::name::FolderOrSearchNameOptionalFlag, search},
+    message::export::{ExportFmt, Exporter},
+    printer::Printer,
+};
+
+/// Export messages to an mbox file or to .eml files.
+///
+/// This command exports messages unedited, as they are on the
+/// backend, without applying the "seen" flag. Messages are selected
+/// by ids, by a query, or the whole folder is exported when none of
+/// them is given. Messages are exported by date, oldest first.
+#[derive(Debug, Parser)]
+pub struct MessageExportCommand {
+    #[command(flatten)]
+    pub folder: FolderOrSearchNameOptionalFlag,
+
+    /// The list of envelopes ids.
+    ///
+    /// Each id can be an envelope id, an inclusive range of ids
+    /// (1-20), a Message-ID enclosed in angle brackets (<abc@host>),
+    /// "first" for the oldest envelope or "last" for the most recent
+    /// one. Ids can also be separated by commas (5,7,9-12).
+    #[arg(value_name = "ID", conflicts_with = "query")]
+    pub ids: Vec<IdExprs>,
+
+    /// Export envelopes matching the given query.
+    ///
+    /// The query follows the same syntax as the envelope list
+    /// command filter query (see envelope list --help).
+    #[arg(long, short = 'q', value_name = "QUERY")]
+    pub query: Option<String>,
+
+    /// The path of the mbox file or of the .eml directory.
+    ///
+    /// Messages are appended to existing mbox files. In a directory,
+    /// each message is written to a file named after its date and
+    /// subject (2024-01-31_142501_weekly-report.eml).
+    #[arg(long, short = 'p', value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// The format of exported messages.
+    #[arg(long = "as", value_name = "FORMAT", value_enum, default_value_t)]
+    pub fmt: ExportFmt,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl MessageExportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing export message(s) command");
+
+        let exprs = IdExprs::flatten(&self.ids);
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            IdExpr::account_name(self.account.name.as_deref(), &exprs)?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let (folder, query) = search::resolve_folder(
+            &toml_account_config,
+            &self.folder.name,
+            self.query.as_deref(),
+            !exprs.is_empty(),
+        )?;
+        let folder = &folder;
+
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            peek_messages_kind
+                .into_iter()
+                .chain(list_envelopes_kind)
+                .chain(get_envelope_kind),
+            |builder| {
+                builder.set_peek_messages(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+                // envelopes are retrieved to sort messages by date
+                builder.set_get_envelope(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let mut ids = match &query {
+            Some(query) => {
+                let query = query::parse_or_exit(query);
+                backend.search_ids(folder, query).await?
+            }
+            None if !exprs.is_empty() => backend.resolve_ids(folder, &exprs).await?,
+            None => {
+                let opts = ListEnvelopesOptions {
+                    page: 0,
+                    page_size: 0,
+                    query: None,
+                };
+                let envelopes = backend.list_envelopes(folder, opts).await?;
+                let ids: Result<Vec<usize>, _> = envelopes.iter().map(|e| e.id.parse()).collect();
+                ids?
+            }
+        };
+
+        if ids.is_empty() {
+            bail!("cannot export messages: no envelope found in folder {folder}");
+        }
+
+        // mbox files are ordered chronologically, whatever the order
+        // of ids, queries and listings
+        backend.sort_ids_by_date(folder, &mut ids).await?;
+
+        let mut exporter = Exporter::new(self.fmt, &self.path)?;
+
+        let progress = ProgressBar::new(ids.len() as u64)
+            .with_style(ProgressStyle::with_template(
+                " {spinner:.dim} Exporting messages… {pos}/{len}\n {wide_bar:.cyan/blue} ",
+            )?)
+            .with_finish(ProgressFinish::AndClear);
+
+        for ids in ids.chunks(BATCH_SIZE) {
+            let msgs = backend.peek_messages(folder, ids).await?;
+
+            for msg in msgs.to_vec() {
+                exporter.export(msg.raw()?, msg.parsed()?)?;
+                progress.inc(1);
+            }
+        }
+
+        exporter.finish()?;
+        progress.finish_using_style();
+
+        printer.print(format!(
+            "{} message(s) successfully exported to {}!",
+            ids.len(),
+            self.path.display()
+        ))
+    }
+}
@@ -0,0 +1,149 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::backend::feature::BackendFeatureSource;
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag,
+    message::import::{self, ImportFailure, ImportReport},
+    printer::Printer,
+};
+
+/// Import messages from mbox files, .eml files or Maildir
+/// directories.
+///
+/// This command adds each message of the given archives to the
+/// folder, unedited, with the flags recorded by the archive: the
+/// info suffix of Maildir file names, the Status and X-Status
+/// headers of mbox and .eml messages. Messages whose Message-ID
+/// already exists in the folder are skipped, and failures are
+/// reported per message, or per file when it cannot be read.
+/// Archives are read one file at a time.
+#[derive(Debug, Parser)]
+pub struct MessageImportCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    /// The paths of the archives to import.
+    ///
+    /// Directories containing a cur directory are read as Maildir,
+    /// other directories as a collection of .eml files. Files
+    /// starting with a "From " line are read as mbox, other files as
+    /// a single .eml message.
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl MessageImportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing import message(s) command");
+
+        let folder = &self.folder.name;
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let add_message_kind = toml_account_config.add_message_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            add_message_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_add_message(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let mut report = ImportReport::default();
+        let mut files = Vec::new();
+
+        for path in &self.paths {
+            match import::files(path) {
+                Ok(path_files) => files.extend(path_files),
+                Err(err) => {
+                    debug!("cannot list archive {path:?}: {err:?}");
+                    report.failed.push(ImportFailure::new(path.display(), &err));
+                }
+            }
+        }
+
+        let mut message_ids = backend.list_message_ids(folder).await?;
+
+        // the amount of messages is known once their file is read
+        let progress = ProgressBar::new(0)
+            .with_style(ProgressStyle::with_template(
+                " {spinner:.dim} Importing messages… {pos}/{len}\n {wide_bar:.cyan/blue} ",
+            )?)
+            .with_finish(ProgressFinish::AndClear);
+
+        for file in files {
+            let msgs = match file.read() {
+                Ok(msgs) => msgs,
+                Err(err) => {
+                    debug!("cannot read archive file {:?}: {err:?}", file.path());
+                    report
+                        .failed
+                        .push(ImportFailure::new(file.path().display(), &err));
+                    continue;
+                }
+            };
+
+            progress.inc_length(msgs.len() as u64);
+
+            for msg in msgs {
+                progress.inc(1);
+
+                let message_id = msg.message_id();
+
+                if let Some(message_id) = &message_id {
+                    if message_ids.contains(message_id) {
+                        debug!(
+                            "skipping duplicate message {message_id} from {}",
+                            msg.source
+                        );
+                        report.skipped += 1;
+                        continue;
+                    }
+                }
+
+                match backend
+                    .add_message_with_flags(folder, &msg.raw, &msg.flags)
+                    .await
+                {
+                    Ok(_) => {
+                        report.imported += 1;
+                        message_ids.extend(message_id);
+                    }
+                    Err(err) => {
+                        debug!("cannot import message from {}: {err:?}", msg.source);
+                        report.failed.push(ImportFailure::new(msg.source, &err));
+                    }
+                }
+            }
+        }
+
+        progress.finish_using_style();
+
+        printer.print(report)
+    }
+}
@@ -1,6 +1,8 @@
 pub mod copy;
 pub mod delete;
+pub mod export;
 pub mod forward;
+pub mod import;
 pub mod mailto;
 pub mod r#move;
 pub mod read;
@@ -15,10 +17,10 @@ use clap::Subcommand;
 use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
-    copy::MessageCopyCommand, delete::MessageDeleteCommand, forward::MessageForwardCommand,
-    mailto::MessageMailtoCommand, r#move::MessageMoveCommand, read::MessageReadCommand,
-    reply::MessageReplyCommand, save::MessageSaveCommand, send::MessageSendCommand,
-    write::MessageWriteCommand,
+    copy::MessageCopyCommand, delete::MessageDeleteCommand, export::MessageExportCommand,
+    forward::MessageForwardCommand, import::MessageImportCommand, mailto::MessageMailtoCommand,
+    r#move::MessageMoveCommand, read::MessageReadCommand, reply::MessageReplyCommand,
+    save::MessageSaveCommand, send::MessageSendCommand, write::MessageWriteCommand,
 };
 
 /// Manage messages.
@@ -59,6 +61,11 @@ pub enum MessageSubcommand {
     #[command(arg_required_else_help = true)]
     #[command(aliases = ["remove", "rm"])]
     Delete(MessageDeleteCommand),
+
+    Export(MessageExportCommand),
+
+    #[command(arg_required_else_help = true)]
+    Import(MessageImportCommand),
 }
 
 impl MessageSubcommand {
@@ -75,6 +82,8 @@ impl MessageSubcommand {
             Self::Copy(cmd) => cmd.execute(printer, config).await,
             Self::Move(cmd) => cmd.execute(printer, config).await,
             Self::Delete(cmd) => cmd.execute(printer, config).await,
+            Self::Export(cmd) => cmd.execute(printer, config).await,
+            Self::Import(cmd) => cmd.execute(printer, config).await,
         }
     }
 }
@@ -8,8 +8,12 @@ use tracing::info;
 use crate::cache::arg::disable::CacheDisableFlag;
 #[allow(unused)]
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs, folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::{EnvelopeIdsArgs, IdExpr},
+    folder::arg::name::FolderNameOptionalFlag,
+    message::html,
     printer::Printer,
 };
 
@@ -18,6 +22,10 @@ use crate::{
 /// This command allows you to read a message. When reading a message,
 /// the "seen" flag is automatically applied to the corresponding
 /// envelope. To prevent this behaviour, use the --preview flag.
+///
+/// HTML parts of messages without text/plain alternative are rendered
+/// as text, using the renderer set up by the message.read.html-renderer
+/// option of your TOML configuration file.
 #[derive(Debug, Parser)]
 pub struct MessageReadCommand {
     #[command(flatten)]
@@ -46,7 +54,8 @@ pub struct MessageReadCommand {
     ///
     /// This argument is useful when you need to read the HTML version
     /// of a message. Combined with --no-headers, you can write it to
-    /// a .html file and open it with your favourite browser.
+    /// a .html file and open it with your favourite browser. The HTML
+    /// is not rendered.
     #[arg(long)]
     #[arg(conflicts_with = "raw")]
     pub html: bool,
@@ -83,24 +92,30 @@ impl MessageReadCommand {
         info!("executing read message(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), &self.envelopes.ids())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
         let get_messages_kind = toml_account_config.get_messages_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config.clone(),
-            get_messages_kind,
-            |builder| builder.set_get_messages(BackendFeatureSource::Context),
+            get_messages_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_get_messages(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
         )
         .await?;
 
+        let html_renderer = toml_account_config.html_renderer();
+        let ids = &backend.resolve_ids(folder, &self.envelopes.ids()).await?;
+
         let emails = if self.preview {
             backend.peek_messages(folder, ids).await
         } else {
@@ -118,6 +133,12 @@ impl MessageReadCommand {
                 // display what can be displayed
                 bodies.push_str(&String::from_utf8_lossy(email.raw()?));
             } else {
+                let html_body = if self.html {
+                    None
+                } else {
+                    html::html_only_body(email.parsed()?)
+                };
+
                 let tpl = email
                     .to_read_tpl(&account_config, |mut tpl| {
                         if self.no_headers {
@@ -128,12 +149,27 @@ impl MessageReadCommand {
 
                         if self.html {
                             tpl = tpl.with_filter_parts(FilterParts::Only("text/html".into()));
+                        } else if html_body.is_some() {
+                            // HTML parts are rendered below
+                            let parts = vec!["text/html".into()];
+                            tpl = tpl.with_filter_parts(FilterParts::Exclude(parts));
                         }
 
                         tpl
                     })
                     .await?;
-                bodies.push_str(&tpl);
+
+                match html_body {
+                    None => bodies.push_str(&tpl),
+                    Some(html) => {
+                        let headers = tpl.trim_end();
+                        if !headers.is_empty() {
+                            bodies.push_str(headers);
+                            bodies.push_str("\n\n");
+                        }
+                        bodies.push_str(&html_renderer.render(&html).await?);
+                    }
+                }
             }
 
             glue = "\n\n";
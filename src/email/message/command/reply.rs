@@ -9,7 +9,7 @@ use crate::{
     account::arg::name::AccountNameFlag,
     backend::Backend,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
+    envelope::arg::ids::{EnvelopeIdArg, IdExpr},
     folder::arg::name::FolderNameOptionalFlag,
     message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs, reply::MessageReplyAllArg},
     printer::Printer,
@@ -53,26 +53,32 @@ impl MessageReplyCommand {
 
         let folder = &self.folder.name;
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), [&self.envelope.id])?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
         let add_message_kind = toml_account_config.add_message_kind();
         let send_message_kind = toml_account_config.send_message_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config.clone(),
-            add_message_kind.into_iter().chain(send_message_kind),
+            add_message_kind
+                .into_iter()
+                .chain(send_message_kind)
+                .chain(list_envelopes_kind),
             |builder| {
                 builder.set_add_message(BackendFeatureSource::Context);
                 builder.set_send_message(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
             },
         )
         .await?;
 
-        let id = self.envelope.id;
+        let id = backend.resolve_id(folder, &self.envelope.id).await?;
+
         let tpl = backend
             .get_messages(folder, &[id])
             .await?
@@ -1,7 +1,6 @@
 use clap::Parser;
 use color_eyre::Result;
 use email::backend::feature::BackendFeatureSource;
-use std::io::{self, BufRead, IsTerminal};
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
@@ -53,20 +52,9 @@ impl MessageSaveCommand {
         )
         .await?;
 
-        let is_tty = io::stdin().is_terminal();
-        let is_json = printer.is_json();
-        let msg = if is_tty || is_json {
-            self.message.raw()
-        } else {
-            io::stdin()
-                .lock()
-                .lines()
-                .map_while(Result::ok)
-                .collect::<Vec<String>>()
-                .join("\r\n")
-        };
+        let msg = self.message.read()?;
 
-        backend.add_message(folder, msg.as_bytes()).await?;
+        backend.add_message(folder, &msg).await?;
 
         printer.print(format!("Message successfully saved to {folder}!"))
     }
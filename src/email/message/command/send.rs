@@ -1,7 +1,6 @@
 use clap::Parser;
 use color_eyre::Result;
 use email::backend::feature::BackendFeatureSource;
-use std::io::{self, BufRead, IsTerminal};
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
@@ -55,18 +54,9 @@ impl MessageSendCommand {
         )
         .await?;
 
-        let msg = if io::stdin().is_terminal() {
-            self.message.raw()
-        } else {
-            io::stdin()
-                .lock()
-                .lines()
-                .map_while(Result::ok)
-                .collect::<Vec<_>>()
-                .join("\r\n")
-        };
+        let msg = self.message.read()?;
 
-        backend.send_message_then_save_copy(msg.as_bytes()).await?;
+        backend.send_message_then_save_copy(&msg).await?;
 
         printer.print("Message successfully sent!")
     }
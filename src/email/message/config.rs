@@ -6,6 +6,8 @@ use std::collections::HashSet;
 
 use crate::backend::BackendKind;
 
+use super::html::HtmlRenderer;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
 pub struct MessageConfig {
     pub write: Option<MessageAddConfig>,
@@ -112,6 +114,11 @@ impl MessagePeekConfig {
 pub struct MessageGetConfig {
     pub backend: Option<BackendKind>,
 
+    /// The renderer of HTML parts of messages without text/plain
+    /// alternative.
+    #[serde(rename = "html-renderer")]
+    pub html_renderer: Option<HtmlRenderer>,
+
     #[serde(flatten)]
     pub remote: email::message::get::config::MessageReadConfig,
 }
@@ -0,0 +1,128 @@
+//! Module related to messages export.
+//!
+//! Messages are exported unedited, either to a single mboxrd file or
+//! to a directory of .eml files named after their date and subject.
+
+use clap::ValueEnum;
+use color_eyre::{eyre::Context, Result};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::mbox;
+
+/// The maximum amount of characters of the subject part of .eml
+/// file names.
+const SUBJECT_LEN: usize = 60;
+
+/// The format of exported messages.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum ExportFmt {
+    /// A single mbox file, using the mboxrd escaping.
+    #[default]
+    Mboxrd,
+    /// A directory of .eml files, one per message.
+    Eml,
+}
+
+/// Represents the destination of exported messages.
+pub enum Exporter {
+    Mbox(BufWriter<File>),
+    Eml(PathBuf),
+}
+
+impl Exporter {
+    /// Open the destination of the given format.
+    ///
+    /// Messages are appended to existing mbox files, and the .eml
+    /// directory is created when missing.
+    pub fn new(fmt: ExportFmt, path: &Path) -> Result<Self> {
+        match fmt {
+            ExportFmt::Mboxrd => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("cannot open mbox file at {path:?}"))?;
+                Ok(Self::Mbox(BufWriter::new(file)))
+            }
+            ExportFmt::Eml => {
+                fs::create_dir_all(path)
+                    .with_context(|| format!("cannot create export directory at {path:?}"))?;
+                Ok(Self::Eml(path.to_owned()))
+            }
+        }
+    }
+
+    /// Export the given raw message.
+    pub fn export(&mut self, raw: &[u8], msg: &mail_parser::Message) -> Result<()> {
+        match self {
+            Self::Mbox(writer) => {
+                writer.write_all(mbox::from_line(msg).as_bytes())?;
+                writer.write_all(&mbox::escape(raw))?;
+                // messages are separated by an empty line
+                writer.write_all(b"\n")?;
+                Ok(())
+            }
+            Self::Eml(dir) => {
+                let name = file_name(msg);
+                let mut path = dir.join(format!("{name}.eml"));
+                let mut n = 1;
+
+                while path.exists() {
+                    path = dir.join(format!("{name}-{n}.eml"));
+                    n += 1;
+                }
+
+                fs::write(&path, raw).with_context(|| format!("cannot write message at {path:?}"))
+            }
+        }
+    }
+
+    /// Flush the messages written to the destination.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Mbox(mut writer) => Ok(writer.flush().context("cannot write mbox file")?),
+            Self::Eml(_) => Ok(()),
+        }
+    }
+}
+
+/// Build the file name of the given message, without extension.
+///
+/// The name is made of the date of the message as written in its
+/// header (`2024-01-31_142501`) and of its subject, reduced to
+/// lowercase alphanumeric words separated by dashes.
+pub fn file_name(msg: &mail_parser::Message) -> String {
+    let date = match msg.date() {
+        Some(date) => format!(
+            "{:04}-{:02}-{:02}_{:02}{:02}{:02}",
+            date.year, date.month, date.day, date.hour, date.minute, date.second
+        ),
+        None => String::from("undated"),
+    };
+
+    let mut subject = String::new();
+    for word in msg
+        .subject()
+        .unwrap_or_default()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !subject.is_empty() {
+            subject.push('-');
+        }
+        subject.push_str(&word.to_lowercase());
+    }
+
+    let subject: String = subject.chars().take(SUBJECT_LEN).collect();
+    let subject = subject.trim_end_matches('-');
+
+    if subject.is_empty() {
+        format!("{date}_no-subject")
+    } else {
+        format!("{date}_{subject}")
+    }
+}
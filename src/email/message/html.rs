@@ -0,0 +1,1146 @@
+//! Module related to HTML rendering.
+//!
+//! HTML parts of messages without text/plain alternative are
+//! rendered as wrapped text, either by the builtin renderer or by a
+//! shell command. The builtin renderer turns links into numbered
+//! footnotes and lays out data tables in columns.
+
+use color_eyre::{eyre::eyre, Result};
+use mail_parser::{Message, PartType};
+use process::SingleCommand;
+use serde::{Deserialize, Serialize};
+use std::{fmt, mem, str::FromStr};
+use unicode_width::UnicodeWidthStr;
+
+/// The maximum width of rendered text.
+const MAX_WIDTH: usize = 80;
+
+/// The minimum width of rendered text, whatever the indentation.
+const MIN_WIDTH: usize = 10;
+
+/// The maximum depth of the parsed tree. Deeper elements are
+/// flattened into their ancestor, so that rendering does not
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// The separator of data table columns.
+const COLUMN_SEP: &str = " | ";
+
+/// Elements without content nor closing tag.
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose content is not HTML, skipped by the parser.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Invisible characters, often used to pad message previews.
+const INVISIBLE_CHARS: [char; 6] = [
+    '\u{ad}', '\u{34f}', '\u{200b}', '\u{200c}', '\u{200d}', '\u{feff}',
+];
+
+/// Represents the HTML renderer of an account.
+///
+/// Renderers are written `builtin`, or `cmd:` followed by a shell
+/// command reading HTML from its standard input and writing text to
+/// its standard output, for example `cmd:w3m -dump -T text/html`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum HtmlRenderer {
+    #[default]
+    Builtin,
+    Cmd(String),
+}
+
+impl HtmlRenderer {
+    /// Render the given HTML as text.
+    pub async fn render(&self, html: &str) -> Result<String> {
+        match self {
+            Self::Builtin => Ok(render(html, width())),
+            Self::Cmd(cmd) => {
+                let output = SingleCommand::from(cmd.clone())
+                    .run_with(html)
+                    .await
+                    .map_err(|err| eyre!("cannot render HTML with command {cmd}: {err}"))?;
+                Ok(output.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl FromStr for HtmlRenderer {
+    type Err = String;
+
+    fn from_str(renderer: &str) -> Result<Self, Self::Err> {
+        let renderer = renderer.trim();
+
+        if renderer.eq_ignore_ascii_case("builtin") {
+            return Ok(Self::Builtin);
+        }
+
+        let cmd = renderer.strip_prefix("cmd:").map(|cmd| {
+            let cmd = cmd.trim();
+            match cmd.strip_prefix('"').and_then(|cmd| cmd.strip_suffix('"')) {
+                Some(cmd) => cmd.trim(),
+                None => cmd,
+            }
+        });
+
+        match cmd {
+            Some(cmd) if !cmd.is_empty() => Ok(Self::Cmd(cmd.to_owned())),
+            _ => Err(format!(
+                "invalid HTML renderer {renderer}, expected builtin or cmd:<command>"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for HtmlRenderer {
+    type Error = String;
+
+    fn try_from(renderer: String) -> Result<Self, Self::Error> {
+        renderer.parse()
+    }
+}
+
+impl From<HtmlRenderer> for String {
+    fn from(renderer: HtmlRenderer) -> Self {
+        renderer.to_string()
+    }
+}
+
+impl fmt::Display for HtmlRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Builtin => write!(f, "builtin"),
+            Self::Cmd(cmd) => write!(f, "cmd:{cmd}"),
+        }
+    }
+}
+
+/// Get the HTML body of the given message, unless it has a
+/// text/plain alternative.
+pub fn html_only_body(msg: &Message) -> Option<String> {
+    let part = |id: &usize| msg.part(*id).map(|part| &part.body);
+
+    if msg
+        .text_body
+        .iter()
+        .any(|id| matches!(part(id), Some(PartType::Text(_))))
+    {
+        return None;
+    }
+
+    let html: Vec<_> = msg
+        .html_body
+        .iter()
+        .filter_map(|id| match part(id)? {
+            PartType::Html(html) => Some(html.as_ref()),
+            _ => None,
+        })
+        .collect();
+
+    (!html.is_empty()).then(|| html.join("\n"))
+}
+
+/// Get the width of rendered text: the width of the terminal, up to
+/// [`MAX_WIDTH`].
+fn width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(terminal_size::Width(width), _)| width as usize)
+        .unwrap_or(MAX_WIDTH)
+        .min(MAX_WIDTH)
+}
+
+/// Render the given HTML as text wrapped at the given width.
+///
+/// Links are followed by their number, and listed at the end of the
+/// text.
+pub fn render(html: &str, width: usize) -> String {
+    let root = parse(html);
+
+    let mut renderer = Renderer::new(width, Vec::new());
+    renderer.render_children(&root);
+    renderer.flush();
+
+    let mut lines = renderer.lines;
+
+    if !renderer.links.is_empty() {
+        lines.push(String::new());
+        for (i, link) in renderer.links.iter().enumerate() {
+            lines.push(format!("[{}] {link}", i + 1));
+        }
+    }
+
+    lines.join("\n")
+}
+
+enum Node {
+    Text(String),
+    Element(Element),
+}
+
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(el) => Some(el),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.elements()
+            .any(|el| el.name == name || el.contains(name))
+    }
+
+    /// Return `true` if the element is not displayed, like previews
+    /// of newsletters.
+    fn is_hidden(&self) -> bool {
+        let style = self.attr("style").unwrap_or_default();
+        let style: String = style.split_whitespace().collect();
+
+        self.attr("hidden").is_some() || style.to_ascii_lowercase().contains("display:none")
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+
+        for child in &self.children {
+            match child {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(el) if el.name == "br" => text.push('\n'),
+                Node::Element(el) => text.push_str(&el.text()),
+            }
+        }
+
+        text
+    }
+}
+
+impl Drop for Element {
+    // the default drop is recursive, which would overflow the stack
+    // on deep trees
+    fn drop(&mut self) {
+        let mut nodes = mem::take(&mut self.children);
+
+        while let Some(node) = nodes.pop() {
+            if let Node::Element(mut el) = node {
+                nodes.append(&mut el.children);
+            }
+        }
+    }
+}
+
+/// Parse the given HTML into a tree, as leniently as browsers do:
+/// unknown end tags are ignored and unclosed elements are closed by
+/// their parent.
+///
+/// Elements nested deeper than [`MAX_DEPTH`] are flattened: their
+/// content belongs to the deepest element, and their end tags are
+/// ignored.
+fn parse(html: &str) -> Element {
+    let mut stack = vec![Element::new(String::new())];
+    // the names of the flattened elements, still open
+    let mut flattened: Vec<String> = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut stack, &rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>').unwrap_or(tag.len());
+            let name = tag[..end].trim().to_ascii_lowercase();
+            match flattened.iter().rposition(|el| *el == name) {
+                Some(pos) => flattened.truncate(pos),
+                None => close(&mut stack, &name),
+            }
+            rest = tag.get(end + 1..).unwrap_or_default();
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (mut el, tail) = parse_tag(&rest[1..]);
+            rest = tail;
+
+            if RAW_TEXT_ELEMENTS.contains(&el.name.as_str()) {
+                // ASCII lowercase preserves byte positions
+                let closing = format!("</{}", el.name);
+                rest = match rest.to_ascii_lowercase().find(&closing) {
+                    Some(end) => rest[end..].find('>').map_or("", |e| &rest[end + e + 1..]),
+                    None => "",
+                };
+            } else if stack.len() > MAX_DEPTH && !VOID_ELEMENTS.contains(&el.name.as_str()) {
+                flattened.push(mem::take(&mut el.name));
+            } else {
+                open(&mut stack, el);
+            }
+        } else {
+            push_text(&mut stack, "<");
+            rest = &rest[1..];
+        }
+    }
+
+    push_text(&mut stack, rest);
+    pop_to(&mut stack, 1);
+    stack.remove(0)
+}
+
+/// Parse the name and the attributes of the given start tag, the
+/// opening angle bracket excluded.
+fn parse_tag(tag: &str) -> (Element, &str) {
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(tag.len());
+    let mut el = Element::new(tag[..end].to_ascii_lowercase());
+    let mut rest = &tag[end..];
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+
+        if rest.is_empty() {
+            return (el, rest);
+        }
+
+        if let Some(tail) = rest.strip_prefix('>') {
+            return (el, tail);
+        }
+
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(rest.len());
+        let name = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+
+        let mut val = String::new();
+
+        if let Some(tail) = rest.strip_prefix('=') {
+            let tail = tail.trim_start();
+            let (raw, tail) = match tail.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let tail = &tail[1..];
+                    let end = tail.find(quote).unwrap_or(tail.len());
+                    (&tail[..end], tail.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = tail
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(tail.len());
+                    (&tail[..end], &tail[end..])
+                }
+            };
+            val = decode_entities(raw);
+            rest = tail;
+        }
+
+        if !name.is_empty() {
+            el.attrs.push((name, val));
+        }
+    }
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let Some(parent) = stack.last_mut() else {
+        return;
+    };
+
+    let text = decode_entities(text);
+
+    match parent.children.last_mut() {
+        Some(Node::Text(prev)) => prev.push_str(&text),
+        _ => parent.children.push(Node::Text(text)),
+    }
+}
+
+fn open(stack: &mut Vec<Element>, el: Element) {
+    // elements implicitly closed by the opened one, unless one of
+    // the boundaries is met first
+    let (closed, boundaries): (&[&str], &[&str]) = match el.name.as_str() {
+        "li" => (&["li"], &["ul", "ol"]),
+        "dt" | "dd" => (&["dt", "dd"], &["dl"]),
+        "tr" => (&["tr", "td", "th"], &["table", "thead", "tbody", "tfoot"]),
+        "td" | "th" => (&["td", "th"], &["tr", "table"]),
+        "thead" | "tbody" | "tfoot" => (&["thead", "tbody", "tfoot", "tr", "td", "th"], &["table"]),
+        "p" | "div" | "ul" | "ol" | "dl" | "table" | "blockquote" | "pre" | "hr" | "h1" | "h2"
+        | "h3" | "h4" | "h5" | "h6" => (&["p"], &["div", "li", "td", "th", "blockquote"]),
+        _ => (&[], &[]),
+    };
+
+    let mut pos = None;
+    for (i, open) in stack.iter().enumerate().skip(1).rev() {
+        if boundaries.contains(&open.name.as_str()) {
+            break;
+        }
+        if closed.contains(&open.name.as_str()) {
+            pos = Some(i);
+        }
+    }
+
+    if let Some(pos) = pos {
+        pop_to(stack, pos);
+    }
+
+    if VOID_ELEMENTS.contains(&el.name.as_str()) {
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(Node::Element(el));
+        }
+    } else {
+        stack.push(el);
+    }
+}
+
+fn close(stack: &mut Vec<Element>, name: &str) {
+    if let Some(pos) = stack.iter().skip(1).rposition(|el| el.name == name) {
+        pop_to(stack, pos + 1);
+    }
+}
+
+/// Close the open elements down to the given position of the stack.
+fn pop_to(stack: &mut Vec<Element>, pos: usize) {
+    while stack.len() > pos.max(1) {
+        let Some(el) = stack.pop() else {
+            break;
+        };
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(Node::Element(el));
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..=end])?, end + 2)));
+
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(code) = name.strip_prefix('#') {
+        let code = match code.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => code.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "zwnj" => '\u{200c}',
+        "zwj" => '\u{200d}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "bull" => '•',
+        "middot" => '·',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+/// Split the given text into lines of at most the given width.
+///
+/// Words longer than the width are not split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split(' ').filter(|word| !word.is_empty()) {
+        if !line.is_empty() && line.width() + 1 + word.width() > width {
+            lines.push(mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn is_blank(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || INVISIBLE_CHARS.contains(&c))
+}
+
+struct Renderer {
+    width: usize,
+    lines: Vec<String>,
+    /// The targets of the links, numbered from 1.
+    links: Vec<String>,
+    /// The text of the current paragraph, with collapsed whitespace
+    /// and line breaks.
+    text: String,
+    /// The prefixes of lines: quotes and list items indentation.
+    prefixes: Vec<String>,
+    /// The marker of the current list item, replacing the
+    /// indentation of its first line.
+    marker: Option<String>,
+    /// Whether a blank line should precede the next line.
+    blank: bool,
+    /// The depth of nested lists.
+    lists: usize,
+}
+
+impl Renderer {
+    fn new(width: usize, links: Vec<String>) -> Self {
+        Self {
+            width,
+            lines: Vec::new(),
+            links,
+            text: String::new(),
+            prefixes: Vec::new(),
+            marker: None,
+            blank: false,
+            lists: 0,
+        }
+    }
+
+    fn available(&self) -> usize {
+        let prefix: usize = self.prefixes.iter().map(String::len).sum();
+        self.width.saturating_sub(prefix).max(MIN_WIDTH)
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if INVISIBLE_CHARS.contains(&c) {
+                continue;
+            }
+
+            if !c.is_whitespace() {
+                self.text.push(c);
+            } else if !self.text.is_empty() && !self.text.ends_with([' ', '\n']) {
+                self.text.push(' ');
+            }
+        }
+    }
+
+    fn push_break(&mut self) {
+        let len = self.text.trim_end_matches(' ').len();
+        self.text.truncate(len);
+        self.text.push('\n');
+    }
+
+    fn push_line(&mut self, line: &str) {
+        let mut prefix = self.prefixes.concat();
+
+        if mem::take(&mut self.blank) {
+            // blank lines keep the prefix shared with the previous
+            // line, so that quotes do not start with an empty quote
+            let blank = prefix.trim_end();
+            match self.lines.last() {
+                Some(last) if last.starts_with(blank) => self.lines.push(blank.to_owned()),
+                Some(_) => self.lines.push(String::new()),
+                None => (),
+            }
+        }
+
+        if let Some(marker) = self.marker.take() {
+            // the last prefix is the indentation of the list item,
+            // as wide as its marker
+            prefix.truncate(prefix.len().saturating_sub(marker.len()));
+            prefix.push_str(&marker);
+        }
+
+        self.lines
+            .push(format!("{prefix}{line}").trim_end().to_owned());
+    }
+
+    /// Write the text of the current paragraph.
+    fn flush(&mut self) {
+        let text = mem::take(&mut self.text);
+        let width = self.available();
+        let mut segments = text.split('\n').peekable();
+        let mut pushed = false;
+
+        while let Some(segment) = segments.next() {
+            let lines = wrap(segment, width);
+
+            // consecutive line breaks make a blank line
+            if lines.is_empty() {
+                if pushed && segments.peek().is_some() {
+                    self.blank = true;
+                }
+                continue;
+            }
+
+            for line in lines {
+                self.push_line(&line);
+            }
+
+            pushed = true;
+        }
+    }
+
+    /// End the current paragraph, optionally separating it from the
+    /// next one with a blank line.
+    ///
+    /// The first paragraph of a list item follows its marker.
+    fn block(&mut self, blank: bool) {
+        self.flush();
+        if blank && self.marker.is_none() {
+            self.blank = true;
+        }
+    }
+
+    fn render_children(&mut self, el: &Element) {
+        for child in &el.children {
+            match child {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(el) => self.render_element(el),
+            }
+        }
+    }
+
+    fn render_element(&mut self, el: &Element) {
+        if el.is_hidden() {
+            return;
+        }
+
+        match el.name.as_str() {
+            "head" | "title" | "template" => (),
+            "br" => self.push_break(),
+            "hr" => {
+                self.block(true);
+                let rule = "-".repeat(self.available().min(MAX_WIDTH));
+                self.push_line(&rule);
+                self.blank = true;
+            }
+            "p" | "dl" => {
+                self.block(true);
+                self.render_children(el);
+                self.block(true);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.render_heading(el),
+            "blockquote" => {
+                self.block(true);
+                self.prefixes.push(String::from("> "));
+                self.render_children(el);
+                self.flush();
+                self.prefixes.pop();
+                self.blank = true;
+            }
+            "dd" => {
+                self.flush();
+                self.prefixes.push(String::from("    "));
+                self.render_children(el);
+                self.flush();
+                self.prefixes.pop();
+            }
+            "pre" => {
+                self.block(true);
+                for line in el.text().trim_matches('\n').lines() {
+                    self.push_line(line);
+                }
+                self.blank = true;
+            }
+            "ul" | "ol" => self.render_list(el),
+            "li" => self.render_item(el, String::from("- ")),
+            "table" => self.render_table(el),
+            "a" => self.render_link(el),
+            "img" => {
+                if let Some(alt) = el.attr("alt").filter(|alt| !is_blank(alt)) {
+                    self.push_text(&format!("[{}]", alt.trim()));
+                }
+            }
+            "div" | "section" | "article" | "header" | "footer" | "main" | "nav" | "aside"
+            | "center" | "form" | "figure" | "figcaption" | "address" | "caption" | "dt" | "tr"
+            | "td" | "th" => {
+                self.flush();
+                self.render_children(el);
+                self.flush();
+            }
+            _ => self.render_children(el),
+        }
+    }
+
+    /// Render the given heading as a paragraph, underlined for the
+    /// first two levels.
+    fn render_heading(&mut self, el: &Element) {
+        self.block(true);
+
+        let start = self.lines.len();
+        self.render_children(el);
+        self.flush();
+
+        let underline = match el.name.as_str() {
+            "h1" => Some("="),
+            "h2" => Some("-"),
+            _ => None,
+        };
+
+        if let Some(underline) = underline {
+            let prefix: usize = self.prefixes.iter().map(String::len).sum();
+            let width = self.lines[start..]
+                .iter()
+                .map(|line| line.width().saturating_sub(prefix))
+                .max()
+                .unwrap_or_default();
+
+            if width > 0 {
+                self.push_line(&underline.repeat(width));
+            }
+        }
+
+        self.blank = true;
+    }
+
+    fn render_list(&mut self, list: &Element) {
+        self.block(self.lists == 0);
+        self.lists += 1;
+
+        let ordered = list.name == "ol";
+        let mut n = list
+            .attr("start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(1usize);
+
+        for child in &list.children {
+            match child {
+                Node::Element(item) if item.name == "li" => {
+                    let marker = if ordered {
+                        format!("{n}. ")
+                    } else {
+                        String::from("- ")
+                    };
+                    n += 1;
+                    self.render_item(item, marker);
+                }
+                Node::Element(el) => self.render_element(el),
+                Node::Text(text) => self.push_text(text),
+            }
+        }
+
+        self.lists -= 1;
+        self.block(self.lists == 0);
+    }
+
+    fn render_item(&mut self, item: &Element, marker: String) {
+        self.flush();
+        self.prefixes.push(" ".repeat(marker.len()));
+        self.marker = Some(marker);
+        self.render_children(item);
+        self.flush();
+        self.prefixes.pop();
+        self.marker = None;
+    }
+
+    /// Render the children of the given link, followed by the number
+    /// of its target.
+    ///
+    /// Links whose text is their target are left as is, anchors and
+    /// scripts are ignored.
+    fn render_link(&mut self, link: &Element) {
+        let start = self.text.len();
+        self.render_children(link);
+
+        let Some(href) = link.attr("href").map(str::trim) else {
+            return;
+        };
+
+        if href.is_empty()
+            || href.starts_with('#')
+            || href.to_ascii_lowercase().starts_with("javascript:")
+        {
+            return;
+        }
+
+        let text = self.text.get(start..).unwrap_or_default().trim();
+        if text == href || Some(text) == href.strip_prefix("mailto:") {
+            return;
+        }
+
+        let n = match self.links.iter().position(|link| link == href) {
+            Some(pos) => pos + 1,
+            None => {
+                self.links.push(href.to_owned());
+                self.links.len()
+            }
+        };
+
+        self.push_text(&format!("[{n}]"));
+    }
+
+    /// Render the given table.
+    ///
+    /// Tables with several filled columns are laid out in columns,
+    /// shrunk to fit the width. Tables used for layout, with one
+    /// filled cell per row or nesting other tables, are rendered as a
+    /// sequence of blocks.
+    fn render_table(&mut self, table: &Element) {
+        let mut rows = Vec::new();
+        for el in table.elements() {
+            match el.name.as_str() {
+                "tr" => rows.push(el),
+                "thead" | "tbody" | "tfoot" => {
+                    rows.extend(el.elements().filter(|el| el.name == "tr"))
+                }
+                _ => (),
+            }
+        }
+
+        let rows: Vec<Vec<&Element>> = rows
+            .into_iter()
+            .map(|row| {
+                row.elements()
+                    .filter(|el| el.name == "td" || el.name == "th")
+                    .collect()
+            })
+            .collect();
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+
+        let is_layout = columns < 2
+            || rows.iter().flatten().any(|cell| cell.contains("table"))
+            || rows.iter().all(|row| {
+                let filled = row.iter().filter(|cell| !is_blank(&cell.text()));
+                filled.count() < 2
+            });
+
+        if is_layout {
+            self.flush();
+            for cell in rows.iter().flatten() {
+                self.render_children(cell);
+                self.flush();
+            }
+            return;
+        }
+
+        self.block(true);
+
+        if let Some(caption) = table.elements().find(|el| el.name == "caption") {
+            self.render_children(caption);
+            self.flush();
+        }
+
+        // shrink the widest columns until the table fits
+        let mut widths = vec![0; columns];
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                let lines = self.render_cell(cell, usize::MAX);
+                let width = lines.iter().map(|line| line.width()).max();
+                widths[i] = widths[i].max(width.unwrap_or_default());
+            }
+        }
+
+        let available = self
+            .available()
+            .saturating_sub(COLUMN_SEP.len() * (columns - 1));
+
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().max() else {
+                break;
+            };
+            if *widest <= MIN_WIDTH {
+                break;
+            }
+            *widest -= 1;
+        }
+
+        let cells: Vec<Vec<Vec<String>>> = rows
+            .iter()
+            .map(|row| {
+                let cells = row.iter().enumerate();
+                cells
+                    .map(|(i, cell)| self.render_cell(cell, widths[i]))
+                    .collect()
+            })
+            .collect();
+
+        // long words may exceed the width of their column
+        for row in &cells {
+            for (i, lines) in row.iter().enumerate() {
+                let width = lines.iter().map(|line| line.width()).max();
+                widths[i] = widths[i].max(width.unwrap_or_default());
+            }
+        }
+
+        for (n, row) in cells.iter().enumerate() {
+            let height = row.iter().map(Vec::len).max().unwrap_or_default();
+
+            for i in 0..height {
+                let line: Vec<_> = widths
+                    .iter()
+                    .enumerate()
+                    .map(|(col, width)| {
+                        let cell = row.get(col).and_then(|lines| lines.get(i));
+                        let cell = cell.map(String::as_str).unwrap_or_default();
+                        format!("{cell}{}", " ".repeat(width.saturating_sub(cell.width())))
+                    })
+                    .collect();
+                self.push_line(&line.join(COLUMN_SEP));
+            }
+
+            let is_header = !rows[n].is_empty() && rows[n].iter().all(|cell| cell.name == "th");
+            if n == 0 && is_header {
+                let rule: Vec<_> = widths.iter().map(|width| "-".repeat(*width)).collect();
+                self.push_line(&rule.join("-+-"));
+            }
+        }
+
+        self.blank = true;
+    }
+
+    /// Render the content of the given cell, wrapped at the given
+    /// width.
+    fn render_cell(&mut self, cell: &Element, width: usize) -> Vec<String> {
+        let mut renderer = Renderer::new(width, mem::take(&mut self.links));
+        renderer.render_children(cell);
+        renderer.flush();
+        self.links = renderer.links;
+        renderer.lines
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_entities, parse, render, wrap, Element, HtmlRenderer, Node, MAX_DEPTH};
+
+    fn names(el: &Element) -> Vec<&str> {
+        el.elements().map(|el| el.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_tree() {
+        let root = parse("<P Class='a b' hidden>Hi <b>there</b></p><br/>");
+        assert_eq!(names(&root), ["p", "br"]);
+
+        let p = root.elements().next().unwrap();
+        assert_eq!(p.attr("class"), Some("a b"));
+        assert_eq!(p.attr("hidden"), Some(""));
+        assert_eq!(names(p), ["b"]);
+        assert_eq!(p.text(), "Hi there");
+    }
+
+    #[test]
+    fn parse_implicit_end_tags() {
+        let root = parse("<ul><li>a<li>b<ul><li>c</ul></ul><p>d<p>e");
+        assert_eq!(names(&root), ["ul", "p", "p"]);
+
+        let ul = root.elements().next().unwrap();
+        assert_eq!(names(ul), ["li", "li"]);
+        assert_eq!(ul.text(), "abc");
+    }
+
+    #[test]
+    fn parse_unknown_end_tags() {
+        let root = parse("<div>a</span>b</div>c</div>");
+        assert_eq!(names(&root), ["div"]);
+        assert_eq!(root.text(), "abc");
+    }
+
+    #[test]
+    fn parse_skipped_content() {
+        let html = "<!DOCTYPE html><!-- comment --><script>a < b</script><STYLE>p {}</style>text";
+        let root = parse(html);
+        assert!(names(&root).is_empty());
+        assert_eq!(root.text(), "text");
+    }
+
+    #[test]
+    fn parse_lone_angle_bracket() {
+        assert_eq!(parse("a < b <3").text(), "a < b <3");
+    }
+
+    #[test]
+    fn parse_deep_tree() {
+        let n = 100_000;
+        let html = format!("{}x{}", "<div>".repeat(n), "</div>".repeat(n));
+        let root = parse(&html);
+
+        let mut depth = 0;
+        let mut el = &root;
+        while let Some(Node::Element(child)) = el.children.first() {
+            depth += 1;
+            el = child;
+        }
+
+        assert_eq!(depth, MAX_DEPTH);
+        assert_eq!(el.children.len(), 1);
+        assert_eq!(render(&html, 80), "x");
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_entities("&amp;&lt;&gt;&quot;&apos;"), "&<>\"'");
+        assert_eq!(decode_entities("&#65;&#x42;&#X43;&nbsp;"), "ABC\u{a0}");
+        assert_eq!(
+            decode_entities("&unknown; & &#xffffffff; &amp"),
+            "&unknown; & &#xffffffff; &amp"
+        );
+        assert_eq!(
+            decode_entities("a&verylongentityname;"),
+            "a&verylongentityname;"
+        );
+    }
+
+    #[test]
+    fn wrap_words() {
+        assert_eq!(wrap("a bb ccc dddd", 6), ["a bb", "ccc", "dddd"]);
+        assert_eq!(wrap("  a   b  ", 10), ["a b"]);
+        assert_eq!(wrap("abcdefghij k", 4), ["abcdefghij", "k"]);
+        assert!(wrap("", 10).is_empty());
+    }
+
+    #[test]
+    fn render_links() {
+        let html = concat!(
+            r##"<p>Hello <a href="https://example.com">world</a> and "##,
+            r##"<a href=" https://example.com ">again</a>, <a href="mailto:a@b.c">a@b.c</a>, "##,
+            r##"<a href="https://example.com/x">https://example.com/x</a>, "##,
+            r##"<a href="#top">top</a>, <a href="javascript:void(0)">js</a></p>"##,
+        );
+
+        assert_eq!(
+            render(html, 80),
+            "Hello world[1] and again[1], a@b.c, https://example.com/x, top, js\n\n[1] https://example.com"
+        );
+    }
+
+    #[test]
+    fn render_lists() {
+        let html = "<ul><li>one<li>two<ul><li>nested</ul></ul><ol start=3><li>three<li>four</ol>";
+        assert_eq!(
+            render(html, 80),
+            "- one\n- two\n  - nested\n\n3. three\n4. four"
+        );
+    }
+
+    #[test]
+    fn render_wrapped_list_item() {
+        let html = "<ul><li>one two three four</li></ul>";
+        assert_eq!(render(html, 12), "- one two\n  three four");
+    }
+
+    #[test]
+    fn render_data_table() {
+        let html = concat!(
+            "<table><caption>Files</caption>",
+            "<tr><th>Name<th>Size</tr>",
+            "<tr><td>a.txt<td>12</tr>",
+            "<tr><td>b.txt<td>3</tr></table>",
+        );
+        assert_eq!(
+            render(html, 80),
+            "Files\nName  | Size\n------+-----\na.txt | 12\nb.txt | 3"
+        );
+    }
+
+    #[test]
+    fn render_layout_table() {
+        let html = "<table><tr><td>first</td><td> </td></tr><tr><td>second</td></tr></table>";
+        assert_eq!(render(html, 80), "first\nsecond");
+    }
+
+    #[test]
+    fn render_hidden_elements() {
+        let html = concat!(
+            r#"<div style="DISPLAY: none">preview</div><span hidden>hidden</span>"#,
+            "<head><title>title</title></head><p>visible\u{200b}</p>",
+        );
+        assert_eq!(render(html, 80), "visible");
+    }
+
+    #[test]
+    fn render_blocks() {
+        let html = "<h1>Title</h1><p>a<br>b</p><blockquote>quoted<p>para</p></blockquote>";
+        assert_eq!(
+            render(html, 80),
+            "Title\n=====\n\na\nb\n\n> quoted\n>\n> para"
+        );
+    }
+
+    #[test]
+    fn renderer_from_str() {
+        assert_eq!("builtin".parse(), Ok(HtmlRenderer::Builtin));
+        assert_eq!(" BuiltIn ".parse(), Ok(HtmlRenderer::Builtin));
+        assert_eq!(
+            "cmd:w3m -dump -T text/html".parse(),
+            Ok(HtmlRenderer::Cmd(String::from("w3m -dump -T text/html")))
+        );
+        assert_eq!(
+            r#"cmd: "lynx -stdin -dump" "#.parse(),
+            Ok(HtmlRenderer::Cmd(String::from("lynx -stdin -dump")))
+        );
+        assert!("cmd:".parse::<HtmlRenderer>().is_err());
+        assert!(r#"cmd:"""#.parse::<HtmlRenderer>().is_err());
+        assert!("w3m".parse::<HtmlRenderer>().is_err());
+    }
+
+    #[test]
+    fn renderer_display() {
+        assert_eq!(HtmlRenderer::Builtin.to_string(), "builtin");
+        let cmd = HtmlRenderer::Cmd(String::from("w3m -dump"));
+        assert_eq!(cmd.to_string(), "cmd:w3m -dump");
+        assert_eq!(cmd.to_string().parse(), Ok(cmd));
+    }
+}
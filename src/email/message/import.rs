@@ -0,0 +1,440 @@
+//! Module related to messages import.
+//!
+//! Messages are read from mbox files, .eml files or Maildir
+//! directories, one file at a time, together with the flags the
+//! archive recorded for them: the info suffix of Maildir file names,
+//! the Status and X-Status headers of mbox and .eml messages.
+
+use color_eyre::{
+    eyre::{bail, Context, Report},
+    Result,
+};
+use email::flag::{Flag, Flags};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::printer::{Print, WriteColor};
+
+use super::{arg::normalize_line_endings, mbox};
+
+/// Represents a message read from an archive.
+pub struct ImportedMessage {
+    /// The location of the message, used to report failures: the
+    /// path of its file, followed by its position for mbox files.
+    pub source: String,
+    pub raw: Vec<u8>,
+    pub flags: Flags,
+}
+
+impl ImportedMessage {
+    fn new(source: String, raw: Vec<u8>, flags: Flags) -> Self {
+        Self { source, raw, flags }
+    }
+
+    /// Get the Message-ID of the message, without angle brackets.
+    pub fn message_id(&self) -> Option<String> {
+        let id = header(&self.raw, "Message-ID")?;
+        let id = id.trim().trim_matches(['<', '>']);
+        (!id.is_empty()).then(|| id.to_owned())
+    }
+}
+
+/// Represents a file of an archive, read on demand so that archives
+/// are never loaded at once.
+pub enum ArchiveFile {
+    /// A message of a Maildir, with the flags of its file name.
+    Maildir(PathBuf),
+    /// A .eml message of a directory.
+    Eml(PathBuf),
+    /// An mbox file, or a single .eml message when it does not start
+    /// with a `From ` line.
+    File(PathBuf),
+}
+
+impl ArchiveFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Maildir(path) | Self::Eml(path) | Self::File(path) => path,
+        }
+    }
+
+    /// Read the messages of the file.
+    ///
+    /// Messages of mbox files have their line endings normalized to
+    /// CRLF, since the mbox format uses bare line feeds.
+    pub fn read(&self) -> Result<Vec<ImportedMessage>> {
+        let path = self.path();
+        let content = fs::read(path).with_context(|| format!("cannot read file {path:?}"))?;
+        let source = path.display().to_string();
+
+        let msgs = match self {
+            Self::Maildir(_) => {
+                let flags = path
+                    .file_name()
+                    .map(|name| maildir_flags(&name.to_string_lossy()))
+                    .unwrap_or_default();
+                vec![ImportedMessage::new(source, content, flags)]
+            }
+            Self::File(_) if content.starts_with(b"From ") => mbox::split(&content)
+                .into_iter()
+                .enumerate()
+                .map(|(i, raw)| {
+                    let source = format!("{source}#{}", i + 1);
+                    let raw = normalize_line_endings(&raw);
+                    let flags = status_flags(&raw);
+                    ImportedMessage::new(source, raw, flags)
+                })
+                .collect(),
+            Self::Eml(_) | Self::File(_) => {
+                let flags = status_flags(&content);
+                vec![ImportedMessage::new(source, content, flags)]
+            }
+        };
+
+        Ok(msgs)
+    }
+}
+
+/// List the files of the archive at the given path.
+///
+/// Directories containing a `cur` directory are read as Maildir (new
+/// messages first), other directories as a collection of .eml files.
+/// Files starting with a `From ` line are read as mbox, other files
+/// as a single .eml message.
+pub fn files(path: &Path) -> Result<Vec<ArchiveFile>> {
+    if path.join("cur").is_dir() {
+        let mut files = Vec::new();
+
+        for dir in ["new", "cur"] {
+            let dir = path.join(dir);
+            if dir.is_dir() {
+                files.extend(sorted_files(&dir)?.into_iter().map(ArchiveFile::Maildir));
+            }
+        }
+
+        return Ok(files);
+    }
+
+    if path.is_dir() {
+        let files = sorted_files(path)?
+            .into_iter()
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
+            })
+            .map(ArchiveFile::Eml)
+            .collect();
+        return Ok(files);
+    }
+
+    if !path.exists() {
+        bail!("cannot find archive at {path:?}");
+    }
+
+    Ok(vec![ArchiveFile::File(path.to_owned())])
+}
+
+/// List the files of the given directory, sorted by name.
+fn sorted_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).with_context(|| format!("cannot read directory {dir:?}"))?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("cannot read directory {dir:?}"))?
+            .path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parse the flags of the info suffix of a Maildir file name
+/// (`1700000000.host:2,FS`).
+fn maildir_flags(name: &str) -> Flags {
+    let Some((_, info)) = name.rsplit_once(":2,") else {
+        return Flags::default();
+    };
+
+    Flags::from_iter(info.chars().filter_map(|c| match c {
+        'D' => Some(Flag::Draft),
+        'F' => Some(Flag::Flagged),
+        'R' => Some(Flag::Answered),
+        'S' => Some(Flag::Seen),
+        'T' => Some(Flag::Deleted),
+        _ => None,
+    }))
+}
+
+/// Parse the flags of the Status and X-Status headers of the given
+/// message, as written by mbox readers.
+fn status_flags(raw: &[u8]) -> Flags {
+    let status = header(raw, "Status").unwrap_or_default();
+    let x_status = header(raw, "X-Status").unwrap_or_default();
+
+    let chars = status.chars().chain(x_status.chars());
+
+    Flags::from_iter(chars.filter_map(|c| match c {
+        'R' => Some(Flag::Seen),
+        'A' => Some(Flag::Answered),
+        'F' => Some(Flag::Flagged),
+        'T' => Some(Flag::Draft),
+        'D' => Some(Flag::Deleted),
+        _ => None,
+    }))
+}
+
+/// Get the value of the first header of the given message matching
+/// the given name, unfolded.
+fn header(raw: &[u8], name: &str) -> Option<String> {
+    let mut value: Option<String> = None;
+
+    for line in raw.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        // headers end at the first empty line
+        if line.is_empty() {
+            break;
+        }
+
+        let line = String::from_utf8_lossy(line);
+
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = &mut value {
+                value.push_str(&line);
+            }
+            continue;
+        }
+
+        if value.is_some() {
+            break;
+        }
+
+        if let Some((key, val)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case(name) {
+                value = Some(val.trim().to_owned());
+            }
+        }
+    }
+
+    value
+}
+
+/// Represents the failure of the import of a message.
+///
+/// JSON schema: `{"source": string, "error": string}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportFailure {
+    pub source: String,
+    pub error: String,
+}
+
+impl ImportFailure {
+    pub fn new(source: impl ToString, err: &Report) -> Self {
+        Self {
+            source: source.to_string(),
+            error: format!("{err:#}"),
+        }
+    }
+}
+
+/// Represents the report of an import.
+///
+/// JSON schema: `{"imported": number, "skipped": number, "failed":
+/// [import failure]}`. Skipped messages are duplicates, found by
+/// Message-ID in the target folder or in the imported messages.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub failed: Vec<ImportFailure>,
+}
+
+impl Print for ImportReport {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        writeln!(
+            writer,
+            "{} message(s) imported, {} duplicate(s) skipped, {} failure(s).",
+            self.imported,
+            self.skipped,
+            self.failed.len()
+        )?;
+
+        for failure in &self.failed {
+            writeln!(writer, " - {}: {}", failure.source, failure.error)?;
+        }
+
+        Ok(writer.reset()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use email::flag::Flags;
+    use std::{env, fs, path::PathBuf};
+
+    use super::{files, header, maildir_flags, status_flags, ArchiveFile};
+
+    /// Get the names of the given flags, sorted.
+    fn names(flags: Flags) -> Vec<String> {
+        let mut names: Vec<_> = crate::flag::Flags::from(flags)
+            .iter()
+            .map(|flag| flag.as_str().to_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn archive(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "himalaya-test-import-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// Describe archive files as their kind and file name, like
+    /// `maildir 1:2,S`.
+    fn describe(files: &[ArchiveFile]) -> Vec<String> {
+        files
+            .iter()
+            .map(|file| {
+                let kind = match file {
+                    ArchiveFile::Maildir(_) => "maildir",
+                    ArchiveFile::Eml(_) => "eml",
+                    ArchiveFile::File(_) => "file",
+                };
+                let name = file.path().file_name().unwrap().to_string_lossy();
+                format!("{kind} {name}")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn maildir_flags_from_info() {
+        assert_eq!(
+            names(maildir_flags("1700000000.host:2,DFRST")),
+            vec!["answered", "deleted", "draft", "flagged", "seen"]
+        );
+        assert_eq!(names(maildir_flags("1700000000.host:2,Sa")), vec!["seen"]);
+        assert!(names(maildir_flags("1700000000.host:2,")).is_empty());
+        assert!(names(maildir_flags("1700000000.host")).is_empty());
+    }
+
+    #[test]
+    fn status_flags_from_headers() {
+        let raw = b"Subject: test\r\nStatus: RO\r\nX-Status: AF\r\n\r\nbody\r\n";
+        assert_eq!(
+            names(status_flags(raw)),
+            vec!["answered", "flagged", "seen"]
+        );
+
+        let raw = b"Subject: test\nX-Status: DT\n\nStatus: R\n";
+        assert_eq!(names(status_flags(raw)), vec!["deleted", "draft"]);
+
+        assert!(names(status_flags(b"Subject: test\n\nbody\n")).is_empty());
+    }
+
+    #[test]
+    fn folded_header() {
+        let raw =
+            b"Subject: first\r\n line\r\n\tand second\r\nMessage-ID:\r\n <id@localhost>\r\n\r\n";
+        assert_eq!(
+            header(raw, "subject").as_deref(),
+            Some("first line\tand second")
+        );
+        assert_eq!(
+            header(raw, "Message-ID").as_deref(),
+            Some(" <id@localhost>")
+        );
+        assert_eq!(header(raw, "To"), None);
+
+        // headers end at the first empty line
+        assert_eq!(header(b"Subject: test\n\nTo: body\n", "To"), None);
+    }
+
+    #[test]
+    fn mbox_or_single_eml() {
+        let path = archive("file");
+
+        let mbox = path.join("archive.mbox");
+        fs::write(
+            &mbox,
+            "From a@localhost Mon Jan  1 00:00:00 2024\nSubject: first\nStatus: RO\n\nbody\n\n\
+             From b@localhost Mon Jan  1 00:00:00 2024\nSubject: second\n\nbody\n",
+        )
+        .unwrap();
+
+        let eml = path.join("message.eml");
+        fs::write(&eml, "Subject: single\nX-Status: F\n\nFrom here\n").unwrap();
+
+        let mbox_files = files(&mbox).unwrap();
+        let mbox_msgs = mbox_files[0].read().unwrap();
+        let eml_files = files(&eml).unwrap();
+        let eml_msgs = eml_files[0].read().unwrap();
+        let missing = files(&path.join("missing"));
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(describe(&mbox_files), vec!["file archive.mbox"]);
+        assert_eq!(mbox_msgs.len(), 2);
+        assert!(mbox_msgs[0].source.ends_with("archive.mbox#1"));
+        assert!(mbox_msgs[1].source.ends_with("archive.mbox#2"));
+        assert_eq!(header(&mbox_msgs[1].raw, "Subject").unwrap(), "second");
+        assert!(mbox_msgs[0].raw.windows(2).any(|w| w == b"\r\n"));
+
+        let flags: Vec<_> = mbox_msgs.into_iter().map(|msg| names(msg.flags)).collect();
+        assert_eq!(flags, vec![vec!["seen".to_owned()], vec![]]);
+
+        assert_eq!(describe(&eml_files), vec!["file message.eml"]);
+        assert_eq!(eml_msgs.len(), 1);
+        assert_eq!(header(&eml_msgs[0].raw, "Subject").unwrap(), "single");
+        assert_eq!(names(eml_msgs[0].flags.clone()), vec!["flagged"]);
+
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn maildir_and_eml_dirs() {
+        let path = archive("dirs");
+
+        let maildir = path.join("maildir");
+        for (dir, file) in [
+            ("cur", "2:2,S"),
+            ("cur", "1:2,"),
+            ("new", "3"),
+            ("tmp", "4"),
+        ] {
+            fs::create_dir_all(maildir.join(dir)).unwrap();
+            fs::write(maildir.join(dir).join(file), "Subject: test\n\nbody\n").unwrap();
+        }
+
+        let emls = path.join("emls");
+        fs::create_dir_all(emls.join("sub")).unwrap();
+        for file in ["b.eml", "a.EML", "notes.txt", "sub/c.eml"] {
+            fs::write(emls.join(file), "Subject: test\n\nbody\n").unwrap();
+        }
+
+        let maildir_files = files(&maildir).unwrap();
+        let maildir_msgs = maildir_files[2].read().unwrap();
+        let eml_files = files(&emls).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        // new messages first, then by name
+        assert_eq!(
+            describe(&maildir_files),
+            vec!["maildir 3", "maildir 1:2,", "maildir 2:2,S"]
+        );
+        assert_eq!(names(maildir_msgs[0].flags.clone()), vec!["seen"]);
+
+        // other files and sub-directories are ignored
+        assert_eq!(describe(&eml_files), vec!["eml a.EML", "eml b.eml"]);
+    }
+}
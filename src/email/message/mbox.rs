@@ -0,0 +1,159 @@
+//! Module related to the mboxrd format.
+//!
+//! An mbox file is a concatenation of messages, each one preceded by
+//! a `From ` separator line. The mboxrd variant escapes lines of
+//! messages starting with `From `, optionally preceded by `>`, by
+//! adding one more `>`, which makes the escaping reversible.
+
+use chrono::{DateTime, Utc};
+
+use crate::envelope::Mailbox;
+
+/// The sender of the separator line of messages without sender.
+const UNKNOWN_SENDER: &str = "MAILER-DAEMON";
+
+/// Build the separator line of the given message, made of its sender
+/// and its date in the asctime format, in UTC.
+pub fn from_line(msg: &mail_parser::Message) -> String {
+    let sender = Mailbox::from_address(msg.from())
+        .into_iter()
+        .next()
+        .map(|mailbox| mailbox.addr)
+        .filter(|addr| !addr.is_empty() && !addr.contains(char::is_whitespace))
+        .unwrap_or_else(|| UNKNOWN_SENDER.to_owned());
+
+    let date = msg
+        .date()
+        .and_then(|date| DateTime::from_timestamp(date.to_timestamp(), 0))
+        .unwrap_or_else(Utc::now);
+
+    format!("From {sender} {}\n", date.format("%a %b %e %H:%M:%S %Y"))
+}
+
+/// Escape the given raw message.
+///
+/// Line endings are normalized to LF, lines matching `^>*From ` get
+/// one more `>`, and the message is terminated by a line ending.
+pub fn escape(raw: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(raw.len() + 1);
+
+    for line in raw.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        let quotes = line.iter().take_while(|b| **b == b'>').count();
+        if line[quotes..].starts_with(b"From ") {
+            escaped.push(b'>');
+        }
+
+        escaped.extend_from_slice(line);
+        escaped.push(b'\n');
+    }
+
+    // splitting a message terminated by a line ending yields an
+    // empty last line
+    if raw.ends_with(b"\n") {
+        escaped.pop();
+    }
+
+    escaped
+}
+
+/// Split the given mbox content into raw messages.
+///
+/// Lines starting with `From ` separate messages, the escaping of
+/// other lines is reversed and the empty line separating messages is
+/// removed. Content preceding the first separator is ignored.
+pub fn split(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut msgs = Vec::new();
+    let mut msg: Option<Vec<u8>> = None;
+
+    for line in content.split_inclusive(|b| *b == b'\n') {
+        if line.starts_with(b"From ") {
+            msgs.extend(msg.take().map(trim_separator));
+            msg = Some(Vec::new());
+            continue;
+        }
+
+        let Some(msg) = &mut msg else {
+            continue;
+        };
+
+        let quotes = line.iter().take_while(|b| **b == b'>').count();
+        if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            msg.extend_from_slice(&line[1..]);
+        } else {
+            msg.extend_from_slice(line);
+        }
+    }
+
+    msgs.extend(msg.map(trim_separator));
+    msgs
+}
+
+/// Remove the empty line ending the given message.
+fn trim_separator(mut msg: Vec<u8>) -> Vec<u8> {
+    if msg.ends_with(b"\r\n\r\n") {
+        msg.truncate(msg.len() - 2);
+    } else if msg.ends_with(b"\n\n") {
+        msg.truncate(msg.len() - 1);
+    }
+    msg
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape, split};
+
+    const SEPARATOR: &[u8] = b"From sender@localhost Mon Jan  1 00:00:00 2024\n";
+
+    fn mbox<'a>(msgs: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+        let mut mbox = Vec::new();
+        for msg in msgs {
+            mbox.extend_from_slice(SEPARATOR);
+            mbox.extend(escape(msg));
+            mbox.push(b'\n');
+        }
+        mbox
+    }
+
+    #[test]
+    fn escape_from_lines() {
+        let raw = b"Subject: test\n\nFrom here\n>From there\n>>From everywhere\nFrom\nfrom here\n";
+        let escaped =
+            b"Subject: test\n\n>From here\n>>From there\n>>>From everywhere\nFrom\nfrom here\n";
+        assert_eq!(escape(raw), escaped);
+    }
+
+    #[test]
+    fn escape_line_endings() {
+        assert_eq!(
+            escape(b"Subject: test\r\n\r\nbody\r\n"),
+            b"Subject: test\n\nbody\n"
+        );
+        assert_eq!(escape(b"Subject: test\n\nbody"), b"Subject: test\n\nbody\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        let msgs: [&[u8]; 3] = [
+            b"Subject: first\n\nFrom here\n>From there\n>>From everywhere\n",
+            b"Subject: second\n\n\nbody ending with empty lines\n\n",
+            b"Subject: third\n\n>>>From far away\nFrom the start\n",
+        ];
+
+        assert_eq!(split(&mbox(msgs)), msgs);
+    }
+
+    #[test]
+    fn split_ignores_preamble() {
+        let mut content = b"preamble\n>From not a separator\n".to_vec();
+        content.extend(mbox([&b"Subject: test\n\nbody\n"[..]]));
+
+        assert_eq!(split(&content), [b"Subject: test\n\nbody\n"]);
+    }
+
+    #[test]
+    fn split_empty() {
+        assert!(split(b"").is_empty());
+    }
+}
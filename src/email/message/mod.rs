@@ -2,4 +2,8 @@ pub mod arg;
 pub mod attachment;
 pub mod command;
 pub mod config;
+pub mod export;
+pub mod html;
+pub mod import;
+pub mod mbox;
 pub mod template;
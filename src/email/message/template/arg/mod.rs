@@ -1,6 +1,12 @@
 pub mod body;
 
 use clap::Parser;
+use color_eyre::{eyre::Context, Result};
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+};
 
 /// The raw template argument parser.
 #[derive(Debug, Parser)]
@@ -9,10 +15,38 @@ pub struct TemplateRawArg {
     #[arg(trailing_var_arg = true)]
     #[arg(name = "template_raw", value_name = "TEMPLATE")]
     pub raw: Vec<String>,
+
+    /// Read the raw template from the given file.
+    #[arg(long, value_name = "PATH", conflicts_with = "template_raw")]
+    pub file: Option<PathBuf>,
 }
 
 impl TemplateRawArg {
     pub fn raw(self) -> String {
         self.raw.join(" ").replace('\r', "")
     }
+
+    /// Read the raw template from the file, the arguments or the
+    /// standard input, in this order.
+    ///
+    /// Templates are text: unlike messages, they must be valid UTF-8
+    /// and their CRLF line endings are turned into line feeds, like
+    /// templates given as arguments.
+    pub fn read(self) -> Result<String> {
+        let raw = if let Some(path) = &self.file {
+            fs::read(path).with_context(|| format!("cannot read template at {path:?}"))?
+        } else if !self.raw.is_empty() || io::stdin().is_terminal() {
+            return Ok(self.raw());
+        } else {
+            let mut raw = Vec::new();
+            io::stdin()
+                .lock()
+                .read_to_end(&mut raw)
+                .context("cannot read template from standard input")?;
+            raw
+        };
+
+        let tpl = String::from_utf8(raw).context("cannot read template: invalid UTF-8")?;
+        Ok(tpl.replace("\r\n", "\n"))
+    }
 }
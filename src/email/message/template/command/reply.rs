@@ -9,7 +9,7 @@ use crate::{
     account::arg::name::AccountNameFlag,
     backend::Backend,
     config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdArg,
+    envelope::arg::ids::{EnvelopeIdArg, IdExpr},
     folder::arg::name::FolderNameOptionalFlag,
     message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs, reply::MessageReplyAllArg},
     printer::Printer,
@@ -51,24 +51,28 @@ impl TemplateReplyCommand {
         info!("executing reply template command");
 
         let folder = &self.folder.name;
-        let id = self.envelope.id;
-
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            IdExpr::account_name(self.account.name.as_deref(), [&self.envelope.id])?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
         let get_messages_kind = toml_account_config.get_messages_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config.clone(),
-            get_messages_kind,
-            |builder| builder.set_get_messages(BackendFeatureSource::Context),
+            get_messages_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_get_messages(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
         )
         .await?;
 
+        let id = backend.resolve_id(folder, &self.envelope.id).await?;
+
         let tpl = backend
             .get_messages(folder, &[id])
             .await?
@@ -2,7 +2,6 @@ use clap::Parser;
 use color_eyre::Result;
 use email::backend::feature::BackendFeatureSource;
 use mml::MmlCompilerBuilder;
-use std::io::{self, BufRead, IsTerminal};
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
@@ -57,18 +56,7 @@ impl TemplateSaveCommand {
         )
         .await?;
 
-        let is_tty = io::stdin().is_terminal();
-        let is_json = printer.is_json();
-        let tpl = if is_tty || is_json {
-            self.template.raw()
-        } else {
-            io::stdin()
-                .lock()
-                .lines()
-                .map_while(Result::ok)
-                .collect::<Vec<String>>()
-                .join("\n")
-        };
+        let tpl = self.template.read()?;
 
         #[allow(unused_mut)]
         let mut compiler = MmlCompilerBuilder::new();
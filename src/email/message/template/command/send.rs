@@ -2,7 +2,6 @@ use clap::Parser;
 use color_eyre::Result;
 use email::backend::feature::BackendFeatureSource;
 use mml::MmlCompilerBuilder;
-use std::io::{self, BufRead, IsTerminal};
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
@@ -58,16 +57,7 @@ impl TemplateSendCommand {
         )
         .await?;
 
-        let tpl = if io::stdin().is_terminal() {
-            self.template.raw()
-        } else {
-            io::stdin()
-                .lock()
-                .lines()
-                .map_while(Result::ok)
-                .collect::<Vec<_>>()
-                .join("\n")
-        };
+        let tpl = self.template.read()?;
 
         #[allow(unused_mut)]
         let mut compiler = MmlCompilerBuilder::new();
@@ -1,5 +1,9 @@
 use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
 use email::folder::INBOX;
+use glob::Pattern;
+
+use crate::folder::search::parse_folder_name;
 
 /// The optional folder name flag parser.
 #[derive(Debug, Parser)]
@@ -7,6 +11,7 @@ pub struct FolderNameOptionalFlag {
     /// The name of the folder.
     #[arg(long = "folder", short = 'f')]
     #[arg(name = "folder_name", value_name = "NAME", default_value = INBOX)]
+    #[arg(value_parser = parse_folder_name)]
     pub name: String,
 }
 
@@ -18,11 +23,114 @@ impl Default for FolderNameOptionalFlag {
     }
 }
 
+/// The optional folder or saved search name flag parser, for
+/// commands applying a query.
+#[derive(Debug, Parser)]
+pub struct FolderOrSearchNameOptionalFlag {
+    /// The name of the folder.
+    ///
+    /// The name can also be a saved search prefixed with @ (@unread),
+    /// bound to a single folder. Its query then applies, and can only
+    /// be completed by a sort query.
+    #[arg(long = "folder", short = 'f')]
+    #[arg(name = "folder_name", value_name = "NAME", default_value = INBOX)]
+    pub name: String,
+}
+
+/// The optional folder names flag parser.
+#[derive(Clone, Debug, Parser)]
+pub struct FolderNamesOptionalFlag {
+    /// The names of the folders.
+    ///
+    /// The flag can be repeated in order to target several folders.
+    /// Names can be glob patterns (Archives/*), matched against the
+    /// folders of the account, or a saved search prefixed with @
+    /// (@unread).
+    #[arg(long = "folder", short = 'f')]
+    #[arg(name = "folder_name", value_name = "NAME", default_value = INBOX)]
+    pub names: Vec<String>,
+
+    /// Target all the folders of the account.
+    #[arg(
+        long = "all-folders",
+        name = "all_folders",
+        conflicts_with = "folder_name"
+    )]
+    pub all: bool,
+}
+
+impl FolderNamesOptionalFlag {
+    /// Return `true` if the folders of the account need to be listed
+    /// in order to resolve the folder names.
+    pub fn requires_folders(&self) -> bool {
+        self.all || self.names.iter().any(|name| is_pattern(name))
+    }
+
+    /// Resolve the folder names against the given folders of the
+    /// account.
+    ///
+    /// Patterns are replaced by the folders they match, in the given
+    /// order. Other names are kept as they are, so that folder
+    /// aliases still apply. Duplicates are removed.
+    pub fn resolve<'a>(&self, folders: impl IntoIterator<Item = &'a str>) -> Result<Vec<String>> {
+        let folders: Vec<&str> = folders.into_iter().collect();
+        let mut names: Vec<String> = Vec::new();
+
+        let mut push = |name: &str| {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_owned())
+            }
+        };
+
+        if self.all {
+            folders.iter().for_each(|folder| push(folder));
+            return Ok(names);
+        }
+
+        for name in &self.names {
+            if !is_pattern(name) {
+                push(name);
+                continue;
+            }
+
+            let pattern = Pattern::new(name)
+                .map_err(|err| eyre!("cannot parse folder pattern {name}: {err}"))?;
+            let matches: Vec<&str> = folders
+                .iter()
+                .copied()
+                .filter(|folder| pattern.matches(folder))
+                .collect();
+
+            if matches.is_empty() {
+                return Err(eyre!("cannot find folders matching {name}"));
+            }
+
+            matches.into_iter().for_each(&mut push);
+        }
+
+        Ok(names)
+    }
+}
+
+impl Default for FolderNamesOptionalFlag {
+    fn default() -> Self {
+        Self {
+            names: vec![INBOX.to_owned()],
+            all: false,
+        }
+    }
+}
+
+fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
 /// The optional folder name argument parser.
 #[derive(Debug, Parser)]
 pub struct FolderNameOptionalArg {
     /// The name of the folder.
     #[arg(name = "folder_name", value_name = "FOLDER", default_value = INBOX)]
+    #[arg(value_parser = parse_folder_name)]
     pub name: String,
 }
 
@@ -39,6 +147,7 @@ impl Default for FolderNameOptionalArg {
 pub struct FolderNameArg {
     /// The name of the folder.
     #[arg(name = "folder_name", value_name = "FOLDER")]
+    #[arg(value_parser = parse_folder_name)]
     pub name: String,
 }
 
@@ -46,6 +155,10 @@ pub struct FolderNameArg {
 #[derive(Debug, Parser)]
 pub struct SourceFolderNameOptionalFlag {
     /// The name of the source folder.
+    ///
+    /// The name can also be a saved search prefixed with @ (@unread),
+    /// bound to a single folder. Its query then applies, and can only
+    /// be completed by a sort query.
     #[arg(long = "folder", short = 'f')]
     #[arg(name = "source_folder_name", value_name = "SOURCE", default_value = INBOX)]
     pub name: String,
@@ -56,5 +169,6 @@ pub struct SourceFolderNameOptionalFlag {
 pub struct TargetFolderNameArg {
     /// The name of the target folder.
     #[arg(name = "target_folder_name", value_name = "TARGET")]
+    #[arg(value_parser = parse_folder_name)]
     pub name: String,
 }
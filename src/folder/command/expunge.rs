@@ -1,6 +1,6 @@
 use clap::Parser;
 use color_eyre::Result;
-use email::{backend::feature::BackendFeatureSource, folder::expunge::ExpungeFolder};
+use email::backend::feature::BackendFeatureSource;
 use tracing::info;
 
 #[cfg(feature = "account-sync")]
@@ -1,18 +1,28 @@
 use clap::Parser;
 use color_eyre::Result;
 use email::{backend::feature::BackendFeatureSource, folder::list::ListFolders};
-use tracing::info;
+use futures::future::join_all;
+use tracing::{debug, info, warn};
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig, folder::Folders,
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::{search, Folders},
     printer::Printer,
 };
 
 /// List all folders.
 ///
-/// This command allows you to list all exsting folders.
+/// This command allows you to list all exsting folders. Saved
+/// searches are listed after them, as virtual folders.
+///
+/// With --stats, the amount of messages, unseen messages, recent
+/// messages and the size of each folder are computed as well. The
+/// recent count is only available for IMAP and Maildir folders, the
+/// size for Maildir folders.
 #[derive(Debug, Parser)]
 pub struct FolderListCommand {
     #[cfg(feature = "account-sync")]
@@ -22,6 +32,15 @@ pub struct FolderListCommand {
     #[command(flatten)]
     pub account: AccountNameFlag,
 
+    /// Compute statistics of each folder.
+    ///
+    /// Statistics cost one request per folder (IMAP STATUS or Notmuch
+    /// count), or a read of its directory for Maildir. Other backends
+    /// list all envelopes of each folder. Folders whose statistics
+    /// cannot be computed have no statistics.
+    #[arg(long, short = 's')]
+    pub stats: bool,
+
     /// The maximum width the table should not exceed.
     ///
     /// This argument will force the table not to exceed the given
@@ -42,16 +61,49 @@ impl FolderListCommand {
         )?;
 
         let list_folders_kind = toml_account_config.list_folders_kind();
+        let list_envelopes_kind = toml_account_config
+            .list_envelopes_kind()
+            .filter(|_| self.stats);
 
         let backend = Backend::new(
             toml_account_config.clone(),
             account_config.clone(),
-            list_folders_kind,
-            |builder| builder.set_list_folders(BackendFeatureSource::Context),
+            list_folders_kind.into_iter().chain(list_envelopes_kind),
+            |builder| {
+                builder.set_list_folders(BackendFeatureSource::Context);
+                if self.stats {
+                    builder.set_list_envelopes(BackendFeatureSource::Context);
+                }
+            },
         )
         .await?;
 
-        let folders: Folders = backend.list_folders().await?.into();
+        let mut folders: Folders = backend.list_folders().await?.into();
+
+        if self.stats {
+            let stats = join_all(
+                folders
+                    .iter()
+                    .map(|folder| backend.folder_stats(&folder.name)),
+            )
+            .await;
+            // a folder that cannot be selected, like Gmail's
+            // [Gmail], must not prevent others from being listed
+            for (folder, stats) in folders.iter_mut().zip(stats) {
+                match stats {
+                    Ok(stats) => folder.stats = Some(stats),
+                    Err(err) => {
+                        warn!("cannot get statistics of folder {}: {err}", folder.name);
+                        debug!("{err:?}");
+                    }
+                }
+            }
+        }
+
+        if let Some(searches) = &toml_account_config.search {
+            folders.extend(search::folders(searches));
+        }
+        folders.set_theme(config.theme(Some(toml_account_config.as_ref())));
 
         printer.print_table(folders, self.table_max_width)?;
         Ok(())
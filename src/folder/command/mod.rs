@@ -3,6 +3,7 @@ mod delete;
 mod expunge;
 mod list;
 mod purge;
+mod status;
 
 use color_eyre::Result;
 use clap::Subcommand;
@@ -11,7 +12,7 @@ use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
     add::AddFolderCommand, delete::FolderDeleteCommand, expunge::FolderExpungeCommand,
-    list::FolderListCommand, purge::FolderPurgeCommand,
+    list::FolderListCommand, purge::FolderPurgeCommand, status::FolderStatusCommand,
 };
 
 /// Manage folders.
@@ -26,6 +27,9 @@ pub enum FolderSubcommand {
     #[command(alias = "lst")]
     List(FolderListCommand),
 
+    #[command(alias = "stat")]
+    Status(FolderStatusCommand),
+
     #[command()]
     Expunge(FolderExpungeCommand),
 
@@ -42,6 +46,7 @@ impl FolderSubcommand {
         match self {
             Self::Add(cmd) => cmd.execute(printer, config).await,
             Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Status(cmd) => cmd.execute(printer, config).await,
             Self::Expunge(cmd) => cmd.execute(printer, config).await,
             Self::Purge(cmd) => cmd.execute(printer, config).await,
             Self::Delete(cmd) => cmd.execute(printer, config).await,
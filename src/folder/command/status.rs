@@ -0,0 +1,64 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::backend::feature::BackendFeatureSource;
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::{arg::name::FolderNameArg, Folder},
+    printer::Printer,
+};
+
+/// Show the status of a folder.
+///
+/// This command computes the amount of messages, unseen messages,
+/// recent messages and the size of the given folder. The recent count
+/// is only available for IMAP and Maildir folders, the size for
+/// Maildir folders.
+#[derive(Debug, Parser)]
+pub struct FolderStatusCommand {
+    #[command(flatten)]
+    pub folder: FolderNameArg,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl FolderStatusCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing folder status command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            list_envelopes_kind,
+            |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let stats = backend.folder_stats(folder).await?;
+
+        printer.print(Folder {
+            name: folder.clone(),
+            desc: String::new(),
+            stats: Some(stats),
+        })
+    }
+}
@@ -1,18 +1,57 @@
 pub mod arg;
 pub mod command;
 pub mod config;
+pub mod search;
+pub mod stats;
 
 use color_eyre::Result;
-use comfy_table::{presets, Attribute, Cell, ContentArrangement, Row, Table};
+use comfy_table::{Cell, ContentArrangement, Row, Table};
 use serde::Serialize;
 use std::ops;
 
-use crate::printer::{PrintTable, WriteColor};
+use crate::{
+    envelope::column::format_size,
+    printer::{Print, PrintTable, WriteColor},
+    theme::config::ThemeConfig,
+};
 
+use self::stats::FolderStats;
+
+/// Represents a folder.
+///
+/// JSON schema: `{"name": string, "desc": string}`. Saved searches
+/// are listed as virtual folders, named after the search prefixed
+/// with @. When computed, the folder statistics are added to the
+/// object.
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Folder {
     pub name: String,
     pub desc: String,
+    #[serde(flatten)]
+    pub stats: Option<FolderStats>,
+}
+
+impl Folder {
+    fn row(&self, theme: &ThemeConfig, with_stats: bool) -> Row {
+        let mut row = theme.folder_row(&self.name, &self.desc);
+
+        if with_stats {
+            let stats = self.stats.as_ref();
+            let count = |count: Option<usize>| count.map(|c| c.to_string()).unwrap_or_default();
+
+            row.add_cell(Cell::new(count(stats.map(|s| s.total))));
+            row.add_cell(Cell::new(count(stats.map(|s| s.unseen))));
+            row.add_cell(Cell::new(count(stats.and_then(|s| s.recent))));
+            row.add_cell(Cell::new(
+                stats
+                    .and_then(|s| s.size)
+                    .map(format_size)
+                    .unwrap_or_default(),
+            ));
+        }
+
+        row
+    }
 }
 
 impl From<&email::folder::Folder> for Folder {
@@ -20,57 +59,62 @@ impl From<&email::folder::Folder> for Folder {
         Folder {
             name: folder.name.clone(),
             desc: folder.desc.clone(),
+            stats: None,
         }
     }
 }
 impl From<&Folder> for Row {
     fn from(folder: &Folder) -> Self {
-        let mut row = Row::new();
-        row.add_cell(Cell::new(&folder.name).fg(comfy_table::Color::Blue));
-        row.add_cell(Cell::new(&folder.desc).fg(comfy_table::Color::Green));
-
-        row
+        folder.row(&ThemeConfig::default(), folder.stats.is_some())
     }
 }
 
 impl From<Folder> for Row {
     fn from(folder: Folder) -> Self {
-        let mut row = Row::new();
-        row.add_cell(Cell::new(folder.name).fg(comfy_table::Color::Blue));
-        row.add_cell(Cell::new(folder.desc).fg(comfy_table::Color::Green));
-
-        row
+        Row::from(&folder)
     }
 }
 
+/// Represents the list of folders.
+///
+/// JSON schema: an array of folders.
 #[derive(Clone, Debug, Default, Serialize)]
-pub struct Folders(Vec<Folder>);
+#[serde(transparent)]
+pub struct Folders {
+    folders: Vec<Folder>,
+    /// The theme used to display folders as a table.
+    #[serde(skip)]
+    theme: ThemeConfig,
+}
+
+impl Folders {
+    pub fn set_theme(&mut self, theme: ThemeConfig) {
+        self.theme = theme;
+    }
+}
 
 impl From<Folders> for Table {
     fn from(folders: Folders) -> Self {
-        let mut table = Table::new();
-        table
-            .load_preset(presets::NOTHING)
-            .set_header(Row::from([
-                Cell::new("NAME").add_attribute(Attribute::Reverse),
-                Cell::new("DESC").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(folders.0.into_iter().map(Row::from));
-        table
+        Table::from(&folders)
     }
 }
 
 impl From<&Folders> for Table {
     fn from(folders: &Folders) -> Self {
+        let theme = &folders.theme;
+        let with_stats = folders.iter().any(|folder| folder.stats.is_some());
+
+        let mut header = vec!["NAME", "DESC"];
+        if with_stats {
+            header.extend(["TOTAL", "UNSEEN", "RECENT", "SIZE"]);
+        }
+
         let mut table = Table::new();
         table
-            .load_preset(presets::NOTHING)
+            .load_preset(theme.preset())
             .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(Row::from([
-                Cell::new("NAME").add_attribute(Attribute::Reverse),
-                Cell::new("DESC").add_attribute(Attribute::Reverse),
-            ]))
-            .add_rows(folders.0.iter().map(Row::from));
+            .set_header(theme.header(header))
+            .add_rows(folders.iter().map(|folder| folder.row(theme, with_stats)));
         table
     }
 }
@@ -79,13 +123,22 @@ impl ops::Deref for Folders {
     type Target = Vec<Folder>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.folders
+    }
+}
+
+impl ops::DerefMut for Folders {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.folders
     }
 }
 
 impl From<email::folder::Folders> for Folders {
     fn from(folders: email::folder::Folders) -> Self {
-        Folders(folders.iter().map(Folder::from).collect())
+        Folders {
+            folders: folders.iter().map(Folder::from).collect(),
+            theme: Default::default(),
+        }
     }
 }
 
@@ -101,3 +154,24 @@ impl PrintTable for Folders {
         Ok(())
     }
 }
+
+impl Print for Folder {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        writeln!(writer, "Name:   {}", self.name)?;
+        if !self.desc.is_empty() {
+            writeln!(writer, "Desc:   {}", self.desc)?;
+        }
+        if let Some(stats) = &self.stats {
+            writeln!(writer, "Total:  {}", stats.total)?;
+            writeln!(writer, "Unseen: {}", stats.unseen)?;
+            if let Some(recent) = stats.recent {
+                writeln!(writer, "Recent: {recent}")?;
+            }
+            if let Some(size) = stats.size {
+                writeln!(writer, "Size:   {}", format_size(size))?;
+            }
+        }
+
+        Ok(writer.reset()?)
+    }
+}
@@ -0,0 +1,164 @@
+//! Module related to saved searches.
+//!
+//! A saved search is a named query, optionally bound to folders. It
+//! acts as a virtual folder, referenced by its name prefixed with @
+//! (`-f @unread`).
+
+use color_eyre::{eyre::eyre, Result};
+use email::folder::INBOX;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{account::config::TomlAccountConfig, envelope::query::split_sort, folder::Folder};
+
+/// The prefix of saved search names used as folders.
+pub const PREFIX: char = '@';
+
+/// Represents a saved search configuration.
+///
+/// A saved search is either a query, or a table made of a query and
+/// the folders it applies to.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SavedSearchConfig {
+    Query(String),
+    Table {
+        query: String,
+        /// The folders the query applies to. Folders can be glob
+        /// patterns. Defaults to the inbox.
+        folders: Option<Vec<String>>,
+    },
+}
+
+impl SavedSearchConfig {
+    pub fn query(&self) -> &str {
+        match self {
+            Self::Query(query) => query,
+            Self::Table { query, .. } => query,
+        }
+    }
+
+    pub fn folders(&self) -> Vec<String> {
+        match self {
+            Self::Table {
+                folders: Some(folders),
+                ..
+            } if !folders.is_empty() => folders.clone(),
+            _ => vec![INBOX.to_owned()],
+        }
+    }
+
+    /// Combine the query of the saved search with the given query.
+    ///
+    /// The given query can only override the sort part of the saved
+    /// search, since filter queries cannot be grouped.
+    pub fn combine_query(&self, name: &str, query: Option<&str>) -> Result<String> {
+        let (filter, sort) = split_sort(self.query());
+
+        let Some(query) = query else {
+            return Ok(self.query().to_owned());
+        };
+
+        let (query_filter, query_sort) = split_sort(query);
+
+        if !query_filter.is_empty() {
+            return Err(eyre!(
+                "cannot combine saved search {PREFIX}{name} with filter query {query_filter}"
+            ));
+        }
+
+        let sort = if query_sort.is_empty() {
+            sort
+        } else {
+            query_sort
+        };
+
+        Ok(format!("{filter} {sort}").trim().to_owned())
+    }
+}
+
+/// Find the saved search matching the given folder name, if it
+/// starts with @.
+pub fn find<'a>(
+    config: &'a TomlAccountConfig,
+    folder: &str,
+) -> Result<Option<(&'a str, &'a SavedSearchConfig)>> {
+    let Some(name) = folder.strip_prefix(PREFIX) else {
+        return Ok(None);
+    };
+
+    config
+        .search
+        .as_ref()
+        .and_then(|searches| searches.get_key_value(name))
+        .map(|(name, search)| Some((name.as_str(), search)))
+        .ok_or_else(|| eyre!("cannot find saved search {PREFIX}{name}"))
+}
+
+/// Resolve the saved search targeted by the given folder name, for
+/// commands applying a query to a single folder.
+///
+/// A saved search is replaced by its folder, which must be unique
+/// and cannot be a pattern, and its query is combined with the given
+/// one. Saved searches cannot be combined with envelope ids, since
+/// ids do not come from the search. Other folders are kept as they
+/// are.
+pub fn resolve_folder(
+    config: &TomlAccountConfig,
+    folder: &str,
+    query: Option<&str>,
+    has_ids: bool,
+) -> Result<(String, Option<String>)> {
+    let Some((name, search)) = find(config, folder)? else {
+        return Ok((folder.to_owned(), query.map(ToOwned::to_owned)));
+    };
+
+    if has_ids {
+        return Err(eyre!(
+            "cannot use envelope ids with saved search {PREFIX}{name}, use a sort query instead"
+        ));
+    }
+
+    let [folder]: [String; 1] = search.folders().try_into().map_err(|_| {
+        eyre!("cannot use saved search {PREFIX}{name}: it must target a single folder")
+    })?;
+
+    if folder.contains(['*', '?', '[']) {
+        return Err(eyre!(
+            "cannot use saved search {PREFIX}{name}: it must target a single folder"
+        ));
+    }
+
+    let query = search.combine_query(name, query)?;
+    Ok((folder, Some(query)))
+}
+
+/// Parse the given folder name, rejecting saved searches.
+///
+/// Saved searches only make sense for commands applying a query, so
+/// other commands reject them instead of acting on the whole folder.
+pub fn parse_folder_name(name: &str) -> Result<String, String> {
+    if name.starts_with(PREFIX) {
+        return Err(format!(
+            "saved search {name} can only be used by commands applying a query"
+        ));
+    }
+
+    Ok(name.to_owned())
+}
+
+/// Build the virtual folders of the given saved searches, sorted by
+/// name.
+pub fn folders(searches: &HashMap<String, SavedSearchConfig>) -> Vec<Folder> {
+    let mut folders: Vec<Folder> = searches
+        .iter()
+        .map(|(name, search)| Folder {
+            name: format!("{PREFIX}{name}"),
+            desc: format!("{} (in {})", search.query(), search.folders().join(", ")),
+            stats: None,
+        })
+        .collect();
+
+    folders.sort_by(|a, b| a.name.cmp(&b.name));
+    folders
+}
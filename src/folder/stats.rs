@@ -0,0 +1,172 @@
+//! Module related to folder statistics.
+//!
+//! Statistics are requested with the IMAP STATUS command, which also
+//! gives the recent count, or counted by the Notmuch database. Maildir
+//! folders are read directly from the file system, which gives both
+//! their recent count and their size. Other backends compute
+//! statistics from the envelopes of the folder.
+
+use color_eyre::Result;
+use email::{envelope::Envelope, flag::Flag};
+use serde::Serialize;
+use std::{fs, path::Path};
+
+/// Represents the statistics of a folder.
+///
+/// JSON schema: `{"total": number, "unseen": number, "recent":
+/// number | null, "size": number | null}`. The recent count and the
+/// size (in bytes) are null when the backend does not provide them.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FolderStats {
+    pub total: usize,
+    pub unseen: usize,
+    pub recent: Option<usize>,
+    pub size: Option<usize>,
+}
+
+impl FolderStats {
+    /// Compute statistics from the given envelopes.
+    pub fn from_envelopes<'a>(envelopes: impl IntoIterator<Item = &'a Envelope>) -> Self {
+        let mut stats = Self::default();
+
+        for envelope in envelopes {
+            stats.total += 1;
+            if !envelope.flags.contains(&Flag::Seen) {
+                stats.unseen += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// Compute statistics from the given Maildir directory.
+    ///
+    /// Messages of the `new` directory are recent (and unseen),
+    /// messages of the `cur` directory are unseen unless their info
+    /// contains the S flag.
+    pub fn from_maildir(path: &Path) -> Result<Self> {
+        let mut stats = Self::default();
+        let mut recent_count = 0;
+        let mut size = 0;
+
+        for (dir, recent) in [("new", true), ("cur", false)] {
+            for entry in fs::read_dir(path.join(dir))? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if !metadata.is_file() {
+                    continue;
+                }
+
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let seen = !recent
+                    && name
+                        .rsplit_once(":2,")
+                        .is_some_and(|(_, flags)| flags.contains('S'));
+
+                stats.total += 1;
+                if !seen {
+                    stats.unseen += 1;
+                }
+                if recent {
+                    recent_count += 1;
+                }
+                size += metadata.len() as usize;
+            }
+        }
+
+        stats.recent = Some(recent_count);
+        stats.size = Some(size);
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use super::FolderStats;
+
+    fn maildir(name: &str, new: &[&str], cur: &[&str]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("himalaya-test-stats-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+
+        for (dir, files) in [("new", new), ("cur", cur), ("tmp", &[][..])] {
+            fs::create_dir_all(path.join(dir)).unwrap();
+            for file in files {
+                fs::write(path.join(dir).join(file), "Subject: test\n\nbody\n").unwrap();
+            }
+        }
+
+        path
+    }
+
+    #[test]
+    fn from_maildir() {
+        let path = maildir(
+            "flags",
+            &["1.recent"],
+            &[
+                "2.seen:2,S",
+                "3.unseen:2,",
+                "4.flagged:2,FS",
+                "5.replied:2,R",
+            ],
+        );
+        let stats = FolderStats::from_maildir(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.unseen, 3);
+        assert_eq!(stats.recent, Some(1));
+        assert_eq!(stats.size, Some(5 * 20));
+    }
+
+    #[test]
+    fn from_maildir_without_info() {
+        let path = maildir("info", &[], &["1.no-info", "2.other-info:1,S"]);
+        let stats = FolderStats::from_maildir(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(stats.total, 2);
+        assert_eq!(stats.unseen, 2);
+        assert_eq!(stats.recent, Some(0));
+    }
+
+    #[test]
+    fn from_maildir_skips_dirs() {
+        let path = maildir("dirs", &["1"], &[]);
+        fs::create_dir(path.join("cur").join("subdir")).unwrap();
+        let stats = FolderStats::from_maildir(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(stats.total, 1);
+        assert_eq!(stats.unseen, 1);
+        assert_eq!(stats.recent, Some(1));
+    }
+
+    #[test]
+    fn from_maildir_empty() {
+        let path = maildir("empty", &[], &[]);
+        let stats = FolderStats::from_maildir(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(
+            stats,
+            FolderStats {
+                total: 0,
+                unseen: 0,
+                recent: Some(0),
+                size: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn from_maildir_missing() {
+        let path = std::env::temp_dir().join("himalaya-test-stats-missing");
+        assert!(FolderStats::from_maildir(&path).is_err());
+    }
+}
@@ -15,12 +15,16 @@ pub mod manual;
 pub mod notmuch;
 pub mod output;
 pub mod printer;
+pub mod rule;
 #[cfg(feature = "sendmail")]
 pub mod sendmail;
 #[cfg(feature = "smtp")]
 pub mod smtp;
+pub mod status;
+pub mod theme;
 pub mod tracing;
 pub mod ui;
+pub mod undo;
 
 #[doc(inline)]
 pub use crate::email::{envelope, flag, message};
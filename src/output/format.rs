@@ -0,0 +1,230 @@
+//! Module related to serialized output formats.
+//!
+//! This module turns serializable data into records, then writes
+//! them as NDJSON, CSV or TSV.
+
+use color_eyre::{eyre::Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::io::Write;
+
+/// Serialize the given data into a list of records.
+///
+/// Arrays are split into one record per item, any other value is
+/// considered as a single record.
+pub fn records(data: &impl Serialize) -> Result<Vec<Value>> {
+    let value = serde_json::to_value(data).context("cannot serialize output")?;
+
+    Ok(match value {
+        Value::Array(items) => items,
+        value => vec![value],
+    })
+}
+
+/// Flatten the given record into a list of key/value pairs.
+///
+/// Keys of nested objects are joined with dots (from.addr), arrays
+/// of scalars are joined with commas and null values become empty
+/// strings. Scalar records are keyed by "value".
+pub fn flatten(record: &Value) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+
+    match record {
+        Value::Object(map) => flatten_object(&mut fields, "", map),
+        value => fields.push((String::from("value"), to_field(value))),
+    }
+
+    fields
+}
+
+fn flatten_object(fields: &mut Vec<(String, String)>, prefix: &str, map: &Map<String, Value>) {
+    for (key, value) in map {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value {
+            Value::Object(map) => flatten_object(fields, &key, map),
+            value => fields.push((key, to_field(value))),
+        }
+    }
+}
+
+/// Render the given value as a single field.
+pub fn to_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.iter().all(|item| !item.is_object()) => {
+            items.iter().map(to_field).collect::<Vec<_>>().join(",")
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Write the given data as newline-delimited JSON, one record per
+/// line.
+pub fn write_ndjson<W: Write + ?Sized>(writer: &mut W, data: &impl Serialize) -> Result<()> {
+    for record in records(data)? {
+        serde_json::to_writer(&mut *writer, &record).context("cannot write ndjson to writer")?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write the given data as delimiter-separated values, with a header
+/// line made of the flattened keys of all records.
+pub fn write_dsv<W: Write + ?Sized>(
+    writer: &mut W,
+    data: &impl Serialize,
+    delimiter: char,
+) -> Result<()> {
+    let records: Vec<_> = records(data)?.iter().map(flatten).collect();
+
+    let mut header: Vec<&str> = Vec::new();
+    for record in &records {
+        for (key, _) in record {
+            if !header.contains(&key.as_str()) {
+                header.push(key);
+            }
+        }
+    }
+
+    let line = |fields: Vec<&str>| {
+        fields
+            .into_iter()
+            .map(|field| escape_dsv_field(field, delimiter))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string())
+    };
+
+    writeln!(writer, "{}", line(header.clone()))?;
+
+    for record in &records {
+        let fields = header
+            .iter()
+            .map(|key| {
+                record
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.as_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        writeln!(writer, "{}", line(fields))?;
+    }
+
+    Ok(())
+}
+
+/// Escape a CSV field: fields containing the delimiter, quotes or
+/// line breaks are quoted, and their quotes doubled.
+///
+/// TSV fields cannot be quoted, so tabs and line breaks are replaced
+/// by spaces instead.
+fn escape_dsv_field(field: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return field.replace(['\t', '\r', '\n'], " ");
+    }
+
+    if field.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{escape_dsv_field, flatten, records, to_field, write_dsv, write_ndjson};
+
+    #[test]
+    fn records_of_array_and_value() {
+        assert_eq!(records(&json!([1, 2])).unwrap(), [json!(1), json!(2)]);
+        assert_eq!(records(&json!({"a": 1})).unwrap(), [json!({"a": 1})]);
+    }
+
+    #[test]
+    fn flatten_record() {
+        let record = json!({
+            "flags": ["seen", "flagged"],
+            "from": {"addr": "a@localhost", "name": null},
+            "id": "1",
+        });
+
+        assert_eq!(
+            flatten(&record),
+            [
+                (String::from("flags"), String::from("seen,flagged")),
+                (String::from("from.addr"), String::from("a@localhost")),
+                (String::from("from.name"), String::new()),
+                (String::from("id"), String::from("1")),
+            ]
+        );
+
+        assert_eq!(
+            flatten(&json!("inbox")),
+            [(String::from("value"), String::from("inbox"))]
+        );
+    }
+
+    #[test]
+    fn field() {
+        assert_eq!(to_field(&json!(null)), "");
+        assert_eq!(to_field(&json!("a")), "a");
+        assert_eq!(to_field(&json!(42)), "42");
+        assert_eq!(to_field(&json!(true)), "true");
+        assert_eq!(to_field(&json!(["a", 1])), "a,1");
+        assert_eq!(to_field(&json!([{"a": 1}])), r#"[{"a":1}]"#);
+    }
+
+    #[test]
+    fn escape_csv() {
+        assert_eq!(escape_dsv_field("a", ','), "a");
+        assert_eq!(escape_dsv_field("a,b", ','), r#""a,b""#);
+        assert_eq!(escape_dsv_field(r#"a "b""#, ','), r#""a ""b""""#);
+        assert_eq!(escape_dsv_field("a\nb", ','), "\"a\nb\"");
+    }
+
+    #[test]
+    fn escape_tsv() {
+        assert_eq!(escape_dsv_field("a\tb\r\nc", '\t'), "a b  c");
+        assert_eq!(escape_dsv_field(r#"a "b""#, '\t'), r#"a "b""#);
+    }
+
+    #[test]
+    fn dsv() {
+        let data = json!([
+            {"id": "1", "subject": "Hello, world"},
+            {"id": "2", "preview": "Hi"},
+        ]);
+
+        let mut csv = Vec::new();
+        write_dsv(&mut csv, &data, ',').unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,subject,preview\n1,\"Hello, world\",\n2,,Hi\n"
+        );
+
+        let mut tsv = Vec::new();
+        write_dsv(&mut tsv, &data, '\t').unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "id\tsubject\tpreview\n1\tHello, world\t\n2\t\tHi\n"
+        );
+    }
+
+    #[test]
+    fn ndjson() {
+        let mut ndjson = Vec::new();
+        write_ndjson(&mut ndjson, &json!([{"id": "1"}, {"id": "2"}])).unwrap();
+        assert_eq!(
+            String::from_utf8(ndjson).unwrap(),
+            "{\"id\":\"1\"}\n{\"id\":\"2\"}\n"
+        );
+    }
+}
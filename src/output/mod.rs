@@ -1,5 +1,7 @@
 pub mod args;
+pub mod format;
 #[allow(clippy::module_inception)]
 pub mod output;
+pub mod template;
 
 pub use output::*;
@@ -14,6 +14,17 @@ pub enum OutputFmt {
     #[default]
     Plain,
     Json,
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+impl OutputFmt {
+    /// Return `true` if the output is meant to be parsed by another
+    /// program.
+    pub fn is_machine_readable(&self) -> bool {
+        *self != Self::Plain
+    }
 }
 
 impl FromStr for OutputFmt {
@@ -22,6 +33,9 @@ impl FromStr for OutputFmt {
     fn from_str(fmt: &str) -> Result<Self, Self::Err> {
         match fmt {
             fmt if fmt.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            fmt if fmt.eq_ignore_ascii_case("ndjson") => Ok(Self::Ndjson),
+            fmt if fmt.eq_ignore_ascii_case("csv") => Ok(Self::Csv),
+            fmt if fmt.eq_ignore_ascii_case("tsv") => Ok(Self::Tsv),
             fmt if fmt.eq_ignore_ascii_case("plain") => Ok(Self::Plain),
             unknown => Err(eyre!("cannot parse output format {}", unknown)),
         }
@@ -32,6 +46,9 @@ impl fmt::Display for OutputFmt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let fmt = match *self {
             OutputFmt::Json => "JSON",
+            OutputFmt::Ndjson => "NDJSON",
+            OutputFmt::Csv => "CSV",
+            OutputFmt::Tsv => "TSV",
             OutputFmt::Plain => "Plain",
         };
         write!(f, "{}", fmt)
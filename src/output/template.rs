@@ -0,0 +1,187 @@
+//! Module related to output templates.
+//!
+//! This module renders serialized records using a user template,
+//! like `{id}\t{from.addr}\t{subject}`.
+
+use color_eyre::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::{io::Write, str::FromStr};
+
+use super::format::{records, to_field};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Text(String),
+    /// A dotted path to a serialized field.
+    Field(Vec<String>),
+}
+
+/// Represents an output template.
+///
+/// Placeholders are field names enclosed in braces, nested fields
+/// are reached with dots (`{from.addr}`). Missing and null fields
+/// render as empty strings. Literal braces are written `{{` and
+/// `}}`, and the escapes `\t`, `\n` and `\\` are interpreted. The
+/// template is rendered once per record, followed by a line break.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OutputTemplate(Vec<Token>);
+
+impl OutputTemplate {
+    pub fn render(&self, record: &Value) -> String {
+        let mut output = String::new();
+
+        for token in &self.0 {
+            match token {
+                Token::Text(text) => output.push_str(text),
+                Token::Field(path) => {
+                    let value = path
+                        .iter()
+                        .try_fold(record, |value, key| match value {
+                            Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+                            value => value.get(key),
+                        })
+                        .unwrap_or(&Value::Null);
+                    output.push_str(&to_field(value));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Write the given data, one rendered record per line.
+    pub fn write<W: Write + ?Sized>(&self, writer: &mut W, data: &impl Serialize) -> Result<()> {
+        for record in records(data)? {
+            writeln!(writer, "{}", self.render(&record))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(tpl: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut chars = tpl.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => {
+                                return Err(format!(
+                                    "cannot parse output template {tpl}: unclosed {{"
+                                ))
+                            }
+                        }
+                    }
+
+                    let field = field.trim();
+                    if field.is_empty() {
+                        return Err(format!(
+                            "cannot parse output template {tpl}: empty placeholder"
+                        ));
+                    }
+
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+
+                    tokens.push(Token::Field(field.split('.').map(String::from).collect()));
+                }
+                '}' => return Err(format!("cannot parse output template {tpl}: unopened }}")),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+
+        Ok(Self(tokens))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::OutputTemplate;
+
+    fn render(tpl: &str, record: serde_json::Value) -> String {
+        tpl.parse::<OutputTemplate>().unwrap().render(&record)
+    }
+
+    #[test]
+    fn fields() {
+        let record = json!({
+            "id": "1",
+            "from": {"addr": "a@localhost", "name": null},
+            "flags": ["seen", "flagged"],
+        });
+
+        assert_eq!(render("{id}", record.clone()), "1");
+        assert_eq!(
+            render("{id}: { from.addr }", record.clone()),
+            "1: a@localhost"
+        );
+        assert_eq!(render("{from.name}", record.clone()), "");
+        assert_eq!(render("{missing}|{id.missing}", record.clone()), "|");
+        assert_eq!(render("{flags}", record.clone()), "seen,flagged");
+        assert_eq!(render("{flags.1}", record), "flagged");
+    }
+
+    #[test]
+    fn escapes() {
+        let record = json!({"id": "1"});
+
+        assert_eq!(render(r"{id}\t{id}\n", record.clone()), "1\t1\n");
+        assert_eq!(render(r"\\{id}", record.clone()), r"\1");
+        assert_eq!(render(r"\x", record.clone()), r"\x");
+        assert_eq!(render("{{{id}}}", record), "{1}");
+    }
+
+    #[test]
+    fn errors() {
+        assert!("{id".parse::<OutputTemplate>().is_err());
+        assert!("id}".parse::<OutputTemplate>().is_err());
+        assert!("{ }".parse::<OutputTemplate>().is_err());
+    }
+
+    #[test]
+    fn write() {
+        let tpl: OutputTemplate = "{id} {subject}".parse().unwrap();
+        let mut output = Vec::new();
+        tpl.write(
+            &mut output,
+            &json!([{"id": "1", "subject": "a"}, {"id": "2"}]),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "1 a\n2 \n");
+    }
+}
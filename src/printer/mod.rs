@@ -1,9 +1,11 @@
+pub mod page;
 pub mod print;
 #[allow(clippy::module_inception)]
 pub mod printer;
 
 use std::io;
 
+pub use page::*;
 pub use print::*;
 pub use printer::*;
 use termcolor::StandardStream;
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+/// Represents a page of printable items, with its pagination
+/// metadata.
+///
+/// JSON schema: `{"page": number, "page_size": number, "count":
+/// number, "total": number | null, "has_more": bool, "items":
+/// [item]}`. The total is only known once the last page has been
+/// reached.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    /// The number of the first listed page, starting from 1.
+    pub page: usize,
+    /// The amount of items per page.
+    pub page_size: usize,
+    /// The amount of listed items.
+    pub count: usize,
+    /// The total amount of items, when known.
+    pub total: Option<usize>,
+    /// Whether items remain after the listed ones.
+    pub has_more: bool,
+    pub items: T,
+}
+
+impl<T> Page<T> {
+    /// Build the plain text summary printed after the items.
+    pub fn summary(&self) -> String {
+        match self.total {
+            Some(total) => format!("{} listed, {total} in total.", self.count),
+            None if self.has_more => format!("{} listed, more remain.", self.count),
+            None => format!("{} listed.", self.count),
+        }
+    }
+}
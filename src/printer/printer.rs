@@ -1,11 +1,12 @@
 use clap::ArgMatches;
 use color_eyre::{eyre::Context, Report, Result};
+use serde::Serialize;
 use std::fmt::Debug;
 use termcolor::StandardStream;
 
 use crate::{
-    output::{args, ColorFmt, OutputFmt},
-    printer::{Print, WriteColor},
+    output::{args, format, template::OutputTemplate, ColorFmt, OutputFmt},
+    printer::{Page, Print, WriteColor},
 };
 pub trait PrintTable {
     fn print_table(&self, writer: &mut dyn WriteColor, table_max_width: Option<u16>) -> Result<()>;
@@ -17,61 +18,145 @@ pub trait Printer {
     // TODO: rename log
     fn print_log<T: Debug + Print>(&mut self, data: T) -> Result<()>;
     // TODO: rename table
-    fn print_table<T: Debug + PrintTable>(
+    /// Print the given data as a table, or as JSON when the JSON
+    /// output format is used.
+    fn print_table<T: Debug + PrintTable + serde::Serialize>(
         &mut self,
         data: T,
         table_max_width: Option<u16>,
     ) -> Result<()>;
 
+    /// Print the given page of items as a table followed by a
+    /// summary, or as JSON with its pagination metadata. Other
+    /// machine readable formats only print the items.
+    fn print_page<T: Debug + PrintTable + serde::Serialize>(
+        &mut self,
+        page: Page<T>,
+        table_max_width: Option<u16>,
+    ) -> Result<()>;
+
+    /// Return `true` if items can be printed as soon as they arrive,
+    /// which is the case of line-oriented formats (NDJSON and
+    /// templates).
+    fn can_stream(&self) -> bool;
+
+    /// Return `true` if the output is meant to be parsed by another
+    /// program (any format other than plain, or a template).
     fn is_json(&self) -> bool;
 }
 
 pub struct StdoutPrinter {
     pub writer: Box<dyn WriteColor>,
     pub fmt: OutputFmt,
+    /// The template used to render data, which takes precedence
+    /// over the output format.
+    pub template: Option<OutputTemplate>,
 }
 
 impl Default for StdoutPrinter {
     fn default() -> Self {
         let fmt = OutputFmt::default();
         let writer = Box::new(StandardStream::stdout(ColorFmt::default().into()));
-        Self { fmt, writer }
+        Self {
+            fmt,
+            writer,
+            template: None,
+        }
     }
 }
 
 impl StdoutPrinter {
     pub fn new(fmt: OutputFmt, color: ColorFmt) -> Self {
         let writer = Box::new(StandardStream::stdout(color.into()));
-        Self { fmt, writer }
+        Self {
+            fmt,
+            writer,
+            template: None,
+        }
+    }
+
+    pub fn with_template(mut self, template: Option<OutputTemplate>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Write the given data using the template or the machine
+    /// readable output format.
+    ///
+    /// Returns `None` when data should be printed as plain text.
+    fn write_serialized(&mut self, data: &impl Serialize) -> Option<Result<()>> {
+        let writer = self.writer.as_mut();
+
+        if let Some(template) = &self.template {
+            return Some(template.write(writer, data));
+        }
+
+        match self.fmt {
+            OutputFmt::Plain => None,
+            OutputFmt::Json => {
+                Some(serde_json::to_writer(writer, data).context("cannot write json to writer"))
+            }
+            OutputFmt::Ndjson => Some(format::write_ndjson(writer, data)),
+            OutputFmt::Csv => Some(format::write_dsv(writer, data, ',')),
+            OutputFmt::Tsv => Some(format::write_dsv(writer, data, '\t')),
+        }
     }
 }
 
 impl Printer for StdoutPrinter {
     fn print_log<T: Debug + Print>(&mut self, data: T) -> Result<()> {
-        match self.fmt {
-            OutputFmt::Plain => data.print(self.writer.as_mut()),
-            OutputFmt::Json => Ok(()),
+        if self.is_json() {
+            Ok(())
+        } else {
+            data.print(self.writer.as_mut())
         }
     }
 
     fn print<T: Debug + Print + serde::Serialize>(&mut self, data: T) -> Result<()> {
-        match self.fmt {
-            OutputFmt::Plain => data.print(self.writer.as_mut()),
-            OutputFmt::Json => serde_json::to_writer(self.writer.as_mut(), &data)
-                .context("cannot write json to writer"),
+        match self.write_serialized(&data) {
+            Some(res) => res,
+            None => data.print(self.writer.as_mut()),
         }
     }
 
     fn is_json(&self) -> bool {
-        self.fmt == OutputFmt::Json
+        self.fmt.is_machine_readable() || self.template.is_some()
     }
 
-    fn print_table<T: Debug + PrintTable>(
+    fn print_table<T: Debug + PrintTable + serde::Serialize>(
         &mut self,
         data: T,
         table_max_width: Option<u16>,
     ) -> Result<()> {
-        data.print_table(self.writer.as_mut(), table_max_width)
+        match self.write_serialized(&data) {
+            Some(res) => res,
+            None => data.print_table(self.writer.as_mut(), table_max_width),
+        }
+    }
+
+    fn print_page<T: Debug + PrintTable + serde::Serialize>(
+        &mut self,
+        page: Page<T>,
+        table_max_width: Option<u16>,
+    ) -> Result<()> {
+        if self.template.is_none() && self.fmt == OutputFmt::Json {
+            return serde_json::to_writer(self.writer.as_mut(), &page)
+                .context("cannot write json to writer");
+        }
+
+        match self.write_serialized(&page.items) {
+            Some(res) => res,
+            None => {
+                page.items
+                    .print_table(self.writer.as_mut(), table_max_width)?;
+                writeln!(self.writer, "{}", page.summary())?;
+                Ok(())
+            }
+        }
+    }
+
+    fn can_stream(&self) -> bool {
+        self.template.is_some() || self.fmt == OutputFmt::Ndjson
     }
 }
 
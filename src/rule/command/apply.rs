@@ -0,0 +1,81 @@
+use clap::Parser;
+use color_eyre::{eyre::bail, Result};
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    printer::Printer,
+    rule::{self, RuleHistory},
+};
+
+/// Apply filtering rules.
+///
+/// This command applies the rules of the account on the envelopes
+/// matching their query. Each rule is applied to its own folders
+/// (defaults to the inbox), unless folders are given. Rules are
+/// applied in the alphabetical order of their names, and only once
+/// per envelope (by Message-ID), unless forced.
+///
+/// When the account is synchronized, actions are executed on the
+/// local cache, and reach the server at the next synchronization.
+#[derive(Debug, Parser)]
+pub struct RulesApplyCommand {
+    /// Apply all the rules to the given folders.
+    ///
+    /// The flag can be repeated in order to target several folders.
+    #[arg(long = "folder", short = 'f', value_name = "NAME")]
+    pub folders: Vec<String>,
+
+    /// Show the envelopes matched by each rule, without applying
+    /// any action.
+    #[arg(long, short)]
+    pub dry_run: bool,
+
+    /// Apply the rules to envelopes they already handled.
+    #[arg(long)]
+    pub force: bool,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl RulesApplyCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing apply rules command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        if rule::rules(&toml_account_config).is_empty() {
+            bail!("cannot apply rules: no rule defined for this account");
+        }
+
+        let backend =
+            rule::build_backend(toml_account_config.clone(), account_config.clone()).await?;
+
+        let history = RuleHistory::new(&account_config)?;
+        let folders = (!self.folders.is_empty()).then_some(self.folders.as_slice());
+
+        let matches = rule::apply(
+            &backend,
+            &history,
+            &toml_account_config,
+            folders,
+            self.dry_run,
+            self.force,
+        )
+        .await?;
+
+        printer.print(matches)
+    }
+}
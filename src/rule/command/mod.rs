@@ -0,0 +1,30 @@
+mod apply;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::apply::RulesApplyCommand;
+
+/// Manage filtering rules.
+///
+/// Rules are defined per account, in the rules section of the
+/// configuration. Each rule pairs a filter query with actions (flags
+/// changes, copy, shell command, forward and move). Rules are also
+/// applied on new envelopes seen by envelope watch, and after account
+/// synchronizations.
+#[derive(Debug, Subcommand)]
+pub enum RuleSubcommand {
+    #[command()]
+    Apply(RulesApplyCommand),
+}
+
+impl RuleSubcommand {
+    #[allow(unused)]
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Apply(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
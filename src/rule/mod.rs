@@ -0,0 +1,587 @@
+//! Module related to filtering rules.
+//!
+//! A rule pairs a filter query with actions applied on the envelopes
+//! matching the query: flags changes, copy, shell command, forward
+//! and move. Rules are applied by the rules apply command, on new
+//! envelopes seen by watchers and after account synchronizations.
+//! Envelopes handled by a rule are recorded in the rules history, so
+//! that actions are executed once per envelope. Watchers and
+//! synchronizations only handle envelopes added after the first
+//! application of a rule to a folder.
+
+pub mod command;
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use dirs::data_dir;
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    flag::{Flag, Flags},
+    folder::INBOX,
+};
+use mml::MmlCompilerBuilder;
+use process::SingleCommand;
+use serde::{Deserialize, Serialize};
+use sled::{Db, Tree};
+use std::{collections::HashSet, mem, ops, slice, sync::Arc};
+use tracing::{debug, info};
+
+use crate::{
+    account::config::TomlAccountConfig,
+    backend::Backend,
+    envelope::query,
+    printer::{Print, WriteColor},
+};
+
+/// Represents a filtering rule configuration.
+///
+/// Actions are applied in the following order, whatever their order
+/// in the configuration: flags changes, copy, shell command, forward
+/// and move.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RuleConfig {
+    /// The filter query selecting envelopes, see the envelope list
+    /// command for its syntax.
+    pub query: String,
+
+    /// The folders the rule applies to. Defaults to the inbox.
+    pub folders: Option<Vec<String>>,
+
+    /// The flags to add to matching envelopes.
+    pub add_flags: Option<Vec<String>>,
+
+    /// The flags to remove from matching envelopes.
+    pub remove_flags: Option<Vec<String>>,
+
+    /// The folder matching messages are copied to.
+    pub copy: Option<String>,
+
+    /// The shell command executed for each matching envelope.
+    ///
+    /// The placeholders {id}, {folder} and {account} are replaced by
+    /// the envelope id, its folder and its account.
+    pub cmd: Option<String>,
+
+    /// The address matching messages are forwarded to.
+    pub forward: Option<String>,
+
+    /// The folder matching messages are moved to.
+    #[serde(rename = "move")]
+    pub move_to: Option<String>,
+}
+
+impl RuleConfig {
+    pub fn folders(&self) -> Vec<String> {
+        match &self.folders {
+            Some(folders) if !folders.is_empty() => folders.clone(),
+            _ => vec![INBOX.to_owned()],
+        }
+    }
+
+    fn flags(flags: &Option<Vec<String>>) -> Option<Flags> {
+        let flags = flags.as_ref().filter(|flags| !flags.is_empty())?;
+        Some(Flags::from_iter(
+            flags.iter().map(|flag| Flag::from(flag.as_str())),
+        ))
+    }
+
+    /// Describe the actions of the rule.
+    pub fn actions(&self) -> Vec<String> {
+        let mut actions = Vec::new();
+
+        if let Some(flags) = &self.add_flags {
+            actions.push(format!("add flags {}", flags.join(", ")));
+        }
+        if let Some(flags) = &self.remove_flags {
+            actions.push(format!("remove flags {}", flags.join(", ")));
+        }
+        if let Some(folder) = &self.copy {
+            actions.push(format!("copy to {folder}"));
+        }
+        if let Some(cmd) = &self.cmd {
+            actions.push(format!("run {cmd}"));
+        }
+        if let Some(addr) = &self.forward {
+            actions.push(format!("forward to {addr}"));
+        }
+        if let Some(folder) = &self.move_to {
+            actions.push(format!("move to {folder}"));
+        }
+
+        actions
+    }
+}
+
+/// Get the rules of the given account, sorted by name.
+pub fn rules(config: &TomlAccountConfig) -> Vec<(&str, &RuleConfig)> {
+    let mut rules: Vec<_> = config
+        .rules
+        .iter()
+        .flatten()
+        .map(|(name, rule)| (name.as_str(), rule))
+        .collect();
+
+    rules.sort_by_key(|(name, _)| *name);
+    rules
+}
+
+/// Build a backend supporting the actions of the rules of the given
+/// account.
+///
+/// Actions of rules are not recorded in the journal: they would fill
+/// it with automatic operations, hiding the ones of the user.
+pub async fn build_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    let rules = rules(&toml_account_config);
+    let any = |f: fn(&RuleConfig) -> bool| rules.iter().any(|(_, rule)| f(rule));
+
+    let flags = any(|rule| rule.add_flags.is_some() || rule.remove_flags.is_some());
+    let copy = any(|rule| rule.copy.is_some());
+    let move_to = any(|rule| rule.move_to.is_some());
+    let forward = any(|rule| rule.forward.is_some());
+
+    let kinds = [
+        toml_account_config.list_envelopes_kind(),
+        toml_account_config
+            .get_envelope_kind()
+            .filter(|_| copy || move_to),
+        toml_account_config.add_flags_kind().filter(|_| flags),
+        toml_account_config.remove_flags_kind().filter(|_| flags),
+        toml_account_config.copy_messages_kind().filter(|_| copy),
+        toml_account_config.move_messages_kind().filter(|_| move_to),
+        toml_account_config.peek_messages_kind().filter(|_| forward),
+        toml_account_config.send_message_kind().filter(|_| forward),
+        toml_account_config
+            .add_message_kind()
+            .filter(|_| forward && account_config.should_save_copy_sent_message()),
+    ];
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        kinds.into_iter().flatten(),
+        |builder| {
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+            // envelopes are retrieved to follow copied and moved
+            // messages
+            if copy || move_to {
+                builder.set_get_envelope(BackendFeatureSource::Context);
+            }
+            if flags {
+                builder.set_add_flags(BackendFeatureSource::Context);
+                builder.set_remove_flags(BackendFeatureSource::Context);
+            }
+            if copy {
+                builder.set_copy_messages(BackendFeatureSource::Context);
+            }
+            if move_to {
+                builder.set_move_messages(BackendFeatureSource::Context);
+            }
+            if forward {
+                builder.set_peek_messages(BackendFeatureSource::Context);
+                builder.set_send_message(BackendFeatureSource::Context);
+                builder.set_add_message(BackendFeatureSource::Context);
+            }
+        },
+    )
+    .await
+    .map(Backend::without_journal)
+}
+
+/// Represents the envelopes already handled by the rules of an
+/// account, as pairs of rule name and Message-ID.
+///
+/// Rules match envelopes whatever their age, the history prevents
+/// their actions from being executed again on the same messages at
+/// each synchronization or application of the rules. Envelopes
+/// without Message-ID cannot be recorded, so they are always handled.
+///
+/// The history is opened once per account, then shared by the
+/// watchers of its folders.
+#[derive(Clone)]
+pub struct RuleHistory {
+    db: Db,
+    /// The folders each rule was already applied to, as pairs of rule
+    /// name and folder.
+    folders: Tree,
+}
+
+impl RuleHistory {
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let digest = md5::compute(&account_config.name);
+        let db_path = data_dir()
+            .ok_or(eyre!("cannot get XDG data directory"))?
+            .join("himalaya")
+            .join(".rules")
+            .join(format!("{digest:x}"));
+
+        let db = sled::open(&db_path)
+            .with_context(|| format!("cannot open rules history at {db_path:?}"))?;
+        let folders = db
+            .open_tree("folders")
+            .with_context(|| format!("cannot open rules history at {db_path:?}"))?;
+
+        Ok(Self { db, folders })
+    }
+
+    fn key(rule: &str, value: &str) -> Vec<u8> {
+        [rule.as_bytes(), b"\n".as_slice(), value.as_bytes()].concat()
+    }
+
+    /// Keep the envelopes not handled yet by the given rule, or all
+    /// of them when forced.
+    fn unhandled(
+        &self,
+        rule: &str,
+        envelopes: Vec<(usize, Option<String>)>,
+        force: bool,
+    ) -> Result<Vec<(usize, Option<String>)>> {
+        if force {
+            return Ok(envelopes);
+        }
+
+        let mut unhandled = Vec::with_capacity(envelopes.len());
+
+        for envelope in envelopes {
+            if let Some(message_id) = &envelope.1 {
+                let key = Self::key(rule, message_id);
+                if self
+                    .db
+                    .contains_key(key)
+                    .context("cannot read rules history")?
+                {
+                    continue;
+                }
+            }
+            unhandled.push(envelope);
+        }
+
+        Ok(unhandled)
+    }
+
+    /// Record the given envelopes as handled by the given rule.
+    fn record(&self, rule: &str, envelopes: &[(usize, Option<String>)]) -> Result<()> {
+        for (_, message_id) in envelopes {
+            if let Some(message_id) = message_id {
+                let key = Self::key(rule, message_id);
+                self.db
+                    .insert(key, &[])
+                    .context("cannot write rules history")?;
+            }
+        }
+
+        self.db.flush().context("cannot write rules history")?;
+        Ok(())
+    }
+
+    /// Return true if the given rule was already applied to the given
+    /// folder.
+    fn is_known(&self, rule: &str, folder: &str) -> Result<bool> {
+        self.folders
+            .contains_key(Self::key(rule, folder))
+            .context("cannot read rules history")
+    }
+
+    /// Record the given folder as known by the given rule, with its
+    /// existing envelopes as handled.
+    fn seed(&self, rule: &str, folder: &str, envelopes: &[(usize, Option<String>)]) -> Result<()> {
+        self.folders
+            .insert(Self::key(rule, folder), &[])
+            .context("cannot write rules history")?;
+        self.record(rule, envelopes)
+    }
+}
+
+/// Represents the envelopes a rule is applied to.
+#[derive(Clone, Copy, Debug)]
+enum Scope<'a> {
+    /// The matching envelopes not handled yet, or all of them when
+    /// forced.
+    All { force: bool },
+    /// The matching envelopes not handled yet. The first time a rule
+    /// is applied to a folder, they are recorded as handled instead.
+    New,
+    /// The given matching envelopes not handled yet. The first time a
+    /// rule is applied to a folder, the other ones are recorded as
+    /// handled.
+    Only(&'a HashSet<usize>),
+}
+
+/// Represents the envelopes of a folder matched by a rule.
+///
+/// JSON schema: `{"rule": string, "folder": string, "ids": [number],
+/// "actions": [string]}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleMatch {
+    pub rule: String,
+    pub folder: String,
+    pub ids: Vec<usize>,
+    pub actions: Vec<String>,
+}
+
+/// Represents the list of envelopes matched by rules.
+///
+/// JSON schema: an array of rule matches.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct RuleMatches(Vec<RuleMatch>);
+
+impl ops::Deref for RuleMatches {
+    type Target = Vec<RuleMatch>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for RuleMatches {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Print for RuleMatches {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        if self.is_empty() {
+            writeln!(writer, "No envelope matches the rules.")?;
+        }
+
+        for m in self.iter() {
+            let ids: Vec<_> = m.ids.iter().map(ToString::to_string).collect();
+            writeln!(
+                writer,
+                "Rule {} matches {} envelope(s) of folder {}: {}",
+                m.rule,
+                m.ids.len(),
+                m.folder,
+                ids.join(", ")
+            )?;
+            for action in &m.actions {
+                writeln!(writer, " - {action}")?;
+            }
+        }
+
+        Ok(writer.reset()?)
+    }
+}
+
+/// Apply the rules of the given account.
+///
+/// Each rule is applied to its own folders, unless folders are
+/// given, and only to envelopes it did not handle yet, unless
+/// forced. With dry run, matching envelopes are collected but actions
+/// are not executed.
+pub async fn apply(
+    backend: &Backend,
+    history: &RuleHistory,
+    toml_account_config: &TomlAccountConfig,
+    folders: Option<&[String]>,
+    dry_run: bool,
+    force: bool,
+) -> Result<RuleMatches> {
+    let scope = Scope::All { force };
+    let mut matches = RuleMatches::default();
+
+    for (name, rule) in rules(toml_account_config) {
+        let rule_folders = match folders {
+            Some(folders) => folders.to_vec(),
+            None => rule.folders(),
+        };
+
+        for folder in rule_folders {
+            let m = apply_rule(backend, history, name, rule, folder, scope, dry_run);
+            matches.extend(m.await?);
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Apply the rules of the given account to the envelopes they did
+/// not handle yet.
+///
+/// The first time a rule is applied to a folder, its matching
+/// envelopes are recorded as handled without executing its actions,
+/// so that only envelopes added afterwards are handled.
+pub async fn apply_to_new_envelopes(
+    backend: &Backend,
+    history: &RuleHistory,
+    toml_account_config: &TomlAccountConfig,
+) -> Result<RuleMatches> {
+    let mut matches = RuleMatches::default();
+
+    for (name, rule) in rules(toml_account_config) {
+        for folder in rule.folders() {
+            let m = apply_rule(backend, history, name, rule, folder, Scope::New, false);
+            matches.extend(m.await?);
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Apply the rules bound to the given folder on the given envelopes
+/// only.
+///
+/// The first time a rule is applied to the folder, its other matching
+/// envelopes are recorded as handled without executing its actions.
+pub async fn apply_to_envelopes(
+    backend: &Backend,
+    history: &RuleHistory,
+    toml_account_config: &TomlAccountConfig,
+    folder: &str,
+    ids: &HashSet<usize>,
+) -> Result<RuleMatches> {
+    let mut matches = RuleMatches::default();
+
+    let is_same_folder = |name: &String| {
+        name == folder || (name.eq_ignore_ascii_case(INBOX) && folder.eq_ignore_ascii_case(INBOX))
+    };
+
+    for (name, rule) in rules(toml_account_config) {
+        if rule.folders().iter().any(is_same_folder) {
+            let folder = folder.to_owned();
+            let scope = Scope::Only(ids);
+            let m = apply_rule(backend, history, name, rule, folder, scope, false);
+            matches.extend(m.await?);
+        }
+    }
+
+    Ok(matches)
+}
+
+async fn apply_rule(
+    backend: &Backend,
+    history: &RuleHistory,
+    name: &str,
+    rule: &RuleConfig,
+    folder: String,
+    scope: Scope<'_>,
+    dry_run: bool,
+) -> Result<Option<RuleMatch>> {
+    let query = query::parse(&rule.query)
+        .map_err(|err| err.wrap_err(format!("invalid query of rule {name}")))?;
+
+    let mut envelopes = backend.search_envelopes(&folder, query).await?;
+    let is_known = history.is_known(name, &folder)?;
+
+    // envelopes existing before the rule is first applied to the
+    // folder are not the ones the caller is after
+    let mut existing = Vec::new();
+    match scope {
+        Scope::All { .. } => (),
+        Scope::New if is_known => (),
+        Scope::New => existing = mem::take(&mut envelopes),
+        Scope::Only(ids) => {
+            let (only, others) = envelopes.into_iter().partition(|(id, _)| ids.contains(id));
+            envelopes = only;
+            if !is_known {
+                existing = others;
+            }
+        }
+    }
+
+    if !dry_run && !is_known {
+        debug!(
+            "recording {} existing envelope(s) of folder {folder} as handled by rule {name}",
+            existing.len()
+        );
+        history.seed(name, &folder, &existing)?;
+    }
+
+    let force = matches!(scope, Scope::All { force: true });
+    let envelopes = history.unhandled(name, envelopes, force)?;
+    if envelopes.is_empty() {
+        debug!("rule {name} matches no new envelope of folder {folder}");
+        return Ok(None);
+    }
+
+    let ids: Vec<usize> = envelopes.iter().map(|(id, _)| *id).collect();
+
+    if !dry_run {
+        info!("applying rule {name} to {} envelope(s)", ids.len());
+
+        // each envelope is recorded as soon as its actions succeed,
+        // so that a failure does not execute them again on the
+        // envelopes already handled
+        let config = &backend.account_config;
+        for envelope in &envelopes {
+            execute(backend, config, rule, &folder, &[envelope.0]).await?;
+            history.record(name, slice::from_ref(envelope))?;
+        }
+    }
+
+    Ok(Some(RuleMatch {
+        rule: name.to_owned(),
+        folder,
+        ids,
+        actions: rule.actions(),
+    }))
+}
+
+/// Execute the actions of the given rule on the given envelopes.
+async fn execute(
+    backend: &Backend,
+    account_config: &Arc<AccountConfig>,
+    rule: &RuleConfig,
+    folder: &str,
+    ids: &[usize],
+) -> Result<()> {
+    if let Some(flags) = RuleConfig::flags(&rule.add_flags) {
+        backend.add_flags(folder, ids, &flags).await?;
+    }
+
+    if let Some(flags) = RuleConfig::flags(&rule.remove_flags) {
+        backend.remove_flags(folder, ids, &flags).await?;
+    }
+
+    if let Some(target) = &rule.copy {
+        backend.copy_messages(folder, target, ids).await?;
+    }
+
+    if let Some(cmd) = &rule.cmd {
+        for id in ids {
+            let cmd = cmd
+                .replace("{id}", &id.to_string())
+                .replace("{folder}", folder)
+                .replace("{account}", &account_config.name);
+            SingleCommand::from(cmd.clone())
+                .run()
+                .await
+                .map_err(|err| eyre!("cannot run rule command {cmd}: {err}"))?;
+        }
+    }
+
+    if let Some(addr) = &rule.forward {
+        let msgs = backend.peek_messages(folder, ids).await?;
+
+        for msg in msgs.to_vec() {
+            let tpl = msg
+                .to_forward_tpl_builder(account_config.clone())
+                .with_headers([("To".to_owned(), addr.clone())])
+                .build()
+                .await?;
+
+            #[allow(unused_mut)]
+            let mut compiler = MmlCompilerBuilder::new();
+
+            #[cfg(feature = "pgp")]
+            compiler.set_some_pgp(account_config.pgp.clone());
+
+            let msg = compiler.build(tpl.as_str())?.compile().await?.into_vec()?;
+            backend.send_message_then_save_copy(&msg).await?;
+        }
+    }
+
+    if let Some(target) = &rule.move_to {
+        backend.move_messages(folder, target, ids).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::{backend::feature::BackendFeatureSource, folder::list::ListFolders};
+use futures::future::try_join_all;
+use std::time::Duration;
+use tracing::{debug, info};
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::{
+        arg::name::{AccountNameFlag, AccountNamesFlag},
+        config::TomlAccountConfig,
+    },
+    backend::Backend,
+    config::TomlConfig,
+    envelope::query,
+    folder::{arg::name::FolderNamesOptionalFlag, search, stats::FolderStats},
+    output::template::OutputTemplate,
+    printer::Printer,
+};
+
+use super::{AccountStatus, FolderStatus, StatusCache, Statuses};
+
+/// Show unseen and total amounts of messages.
+///
+/// This command prints a compact status of the given folders
+/// (defaults to the inbox), for one or several accounts. It is
+/// designed to be polled by status bars and prompts: the status can
+/// be rendered on a single line using a template, as a waybar custom
+/// module output, and cached for a few seconds in order to spare
+/// the servers.
+///
+/// Saved searches (@unread) count the envelopes matching their
+/// query, they must target a single folder.
+#[derive(Debug, Parser)]
+pub struct StatusCommand {
+    #[command(flatten)]
+    pub folder: FolderNamesOptionalFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+
+    #[command(flatten)]
+    pub accounts: AccountNamesFlag,
+
+    /// Render the status of each account using a template.
+    ///
+    /// Placeholders are {account}, {unseen} and {total}, the status
+    /// of folders is reached using their index ({folders.0.unseen}).
+    /// Unlike the global --format argument, accounts are rendered on
+    /// a single line, joined by the separator.
+    ///
+    /// Example: '{account}:{unseen}'
+    #[arg(long, short = 't', value_name = "TEMPLATE")]
+    pub template: Option<OutputTemplate>,
+
+    /// The separator between accounts rendered with the template.
+    #[arg(long, value_name = "SEPARATOR", default_value = " ")]
+    #[arg(requires = "template")]
+    pub separator: String,
+
+    /// Output the status as a waybar custom module.
+    ///
+    /// The output is a JSON object made of a text (the rendered
+    /// template, defaults to the total amount of unseen messages), a
+    /// tooltip detailing each account and folder, and an alt and a
+    /// class set to "unseen" or "seen".
+    #[arg(long)]
+    pub waybar: bool,
+
+    /// Reuse the status computed less than the given amount of
+    /// seconds ago.
+    ///
+    /// The status is cached per combination of accounts and folders,
+    /// which prevents frequent polling from hitting the servers.
+    #[arg(long, value_name = "SECONDS")]
+    pub cache_ttl: Option<u64>,
+}
+
+impl StatusCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing status command");
+
+        #[cfg(feature = "account-sync")]
+        let disable_cache = self.cache.disable;
+
+        let accounts = if self.accounts.is_multiple() {
+            self.accounts.resolve(config)?
+        } else {
+            let (_, account_config) = config.clone().into_account_configs(
+                self.account.name.as_deref(),
+                #[cfg(feature = "account-sync")]
+                disable_cache,
+            )?;
+            vec![account_config.name.clone()]
+        };
+
+        let cache = match self.cache_ttl {
+            Some(ttl) => {
+                let key = format!("{accounts:?}{:?}{}", self.folder.names, self.folder.all);
+                Some(StatusCache::new(&key, Duration::from_secs(ttl))?)
+            }
+            None => None,
+        };
+
+        let statuses = match cache.as_ref().and_then(StatusCache::get) {
+            Some(statuses) => {
+                debug!("using cached status");
+                statuses
+            }
+            None => {
+                let statuses = try_join_all(accounts.iter().map(|account| {
+                    account_status(
+                        config,
+                        account,
+                        &self.folder,
+                        #[cfg(feature = "account-sync")]
+                        disable_cache,
+                    )
+                }))
+                .await?;
+                let statuses = Statuses::from(statuses);
+
+                if let Some(cache) = &cache {
+                    cache.set(&statuses)?;
+                }
+
+                statuses
+            }
+        };
+
+        let text = match &self.template {
+            Some(template) => Some(statuses.render(template, &self.separator)?),
+            None => None,
+        };
+
+        if self.waybar {
+            printer.print(statuses.to_waybar(text))
+        } else if let Some(text) = text {
+            printer.print(text)
+        } else {
+            printer.print(statuses)
+        }
+    }
+}
+
+/// Compute the status of the given folders of the given account.
+async fn account_status(
+    config: &TomlConfig,
+    account: &str,
+    folder: &FolderNamesOptionalFlag,
+    #[cfg(feature = "account-sync")] disable_cache: bool,
+) -> Result<AccountStatus> {
+    let (toml_account_config, account_config) = config.clone().into_account_configs(
+        Some(account),
+        #[cfg(feature = "account-sync")]
+        disable_cache,
+    )?;
+
+    let requires_folders = folder.requires_folders();
+    let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+    let list_folders_kind = requires_folders
+        .then(|| toml_account_config.list_folders_kind())
+        .flatten();
+
+    let backend = Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        list_envelopes_kind.into_iter().chain(list_folders_kind),
+        |builder| {
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+            if requires_folders {
+                builder.set_list_folders(BackendFeatureSource::Context);
+            }
+        },
+    )
+    .await?;
+
+    let folders = if requires_folders {
+        let folders = backend.list_folders().await?;
+        folder.resolve(folders.iter().map(|folder| folder.name.as_str()))?
+    } else {
+        folder.names.clone()
+    };
+
+    let stats = try_join_all(
+        folders
+            .iter()
+            .map(|folder| folder_stats(&backend, &toml_account_config, folder)),
+    )
+    .await?;
+
+    let folders = folders
+        .into_iter()
+        .zip(stats)
+        .map(|(folder, stats)| FolderStatus {
+            folder,
+            unseen: stats.unseen,
+            total: stats.total,
+        })
+        .collect();
+
+    Ok(AccountStatus::new(account, folders))
+}
+
+/// Compute the statistics of the given folder, or of the envelopes
+/// matching the given saved search.
+async fn folder_stats(
+    backend: &Backend,
+    config: &TomlAccountConfig,
+    folder: &str,
+) -> Result<FolderStats> {
+    let (folder, search_query) = search::resolve_folder(config, folder, None, false)?;
+
+    match search_query {
+        Some(search_query) => {
+            let search_query = query::parse(&search_query)?;
+            backend.search_stats(&folder, search_query).await
+        }
+        None => backend.folder_stats(&folder).await,
+    }
+}
@@ -0,0 +1,215 @@
+//! Module related to the status command.
+//!
+//! The status is a compact summary of unseen and total amounts of
+//! messages, designed to be polled by status bars and prompts.
+
+pub mod command;
+
+use color_eyre::{eyre::eyre, eyre::Context, Result};
+use dirs::cache_dir;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, ops,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tracing::debug;
+
+use crate::{
+    output::template::OutputTemplate,
+    printer::{Print, WriteColor},
+};
+
+/// Represents the status of a folder.
+///
+/// JSON schema: `{"folder": string, "unseen": number, "total":
+/// number}`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FolderStatus {
+    pub folder: String,
+    pub unseen: usize,
+    pub total: usize,
+}
+
+/// Represents the status of an account, made of the sum of the
+/// status of its folders.
+///
+/// JSON schema: `{"account": string, "unseen": number, "total":
+/// number, "folders": [folder status]}`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountStatus {
+    pub account: String,
+    pub unseen: usize,
+    pub total: usize,
+    pub folders: Vec<FolderStatus>,
+}
+
+impl AccountStatus {
+    pub fn new(account: impl ToString, folders: Vec<FolderStatus>) -> Self {
+        Self {
+            account: account.to_string(),
+            unseen: folders.iter().map(|f| f.unseen).sum(),
+            total: folders.iter().map(|f| f.total).sum(),
+            folders,
+        }
+    }
+}
+
+/// Represents the status of several accounts.
+///
+/// JSON schema: an array of account status.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Statuses(Vec<AccountStatus>);
+
+impl Statuses {
+    pub fn unseen(&self) -> usize {
+        self.iter().map(|status| status.unseen).sum()
+    }
+
+    /// Describe the status of each account, followed by the status
+    /// of each of its folders when it has several of them.
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for status in self.iter() {
+            let line = format!("{}: {}/{}", status.account, status.unseen, status.total);
+            lines.push(line);
+
+            if status.folders.len() > 1 {
+                for folder in &status.folders {
+                    let line = format!("  {}: {}/{}", folder.folder, folder.unseen, folder.total);
+                    lines.push(line);
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Render the status of each account using the given template,
+    /// on a single line.
+    pub fn render(&self, template: &OutputTemplate, separator: &str) -> Result<String> {
+        let records = self
+            .iter()
+            .map(|status| {
+                let record = serde_json::to_value(status).context("cannot serialize status")?;
+                Ok(template.render(&record))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(records.join(separator))
+    }
+
+    /// Build the waybar representation of the status.
+    ///
+    /// The text defaults to the total amount of unseen messages, the
+    /// tooltip details the status of each account and folder.
+    pub fn to_waybar(&self, text: Option<String>) -> WaybarStatus {
+        let unseen = self.unseen();
+
+        let class = if unseen > 0 { "unseen" } else { "seen" };
+
+        WaybarStatus {
+            text: text.unwrap_or_else(|| unseen.to_string()),
+            tooltip: self.lines().join("\n"),
+            alt: class.to_owned(),
+            class: class.to_owned(),
+        }
+    }
+}
+
+impl From<Vec<AccountStatus>> for Statuses {
+    fn from(statuses: Vec<AccountStatus>) -> Self {
+        Self(statuses)
+    }
+}
+
+impl ops::Deref for Statuses {
+    type Target = Vec<AccountStatus>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Print for Statuses {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        for line in self.lines() {
+            writeln!(writer, "{line}")?;
+        }
+        Ok(writer.reset()?)
+    }
+}
+
+/// Represents the status as a waybar custom module output.
+///
+/// JSON schema: `{"text": string, "tooltip": string, "alt": string,
+/// "class": string}`. The alt and class are "unseen" when at least
+/// one message is unseen, otherwise "seen".
+#[derive(Clone, Debug, Serialize)]
+pub struct WaybarStatus {
+    pub text: String,
+    pub tooltip: String,
+    pub alt: String,
+    pub class: String,
+}
+
+impl Print for WaybarStatus {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        let json = serde_json::to_string(self).context("cannot serialize waybar status")?;
+        writeln!(writer, "{json}")?;
+        Ok(writer.reset()?)
+    }
+}
+
+/// Represents the cache of status, one file per combination of
+/// accounts and folders.
+pub struct StatusCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl StatusCache {
+    pub fn new(key: &str, ttl: Duration) -> Result<Self> {
+        let digest = md5::compute(key);
+        let path = cache_dir()
+            .ok_or(eyre!("cannot get XDG cache directory"))?
+            .join("himalaya")
+            .join("status")
+            .join(format!("{digest:x}.json"));
+
+        Ok(Self { path, ttl })
+    }
+
+    /// Read the cached status, unless it expired.
+    ///
+    /// Unreadable caches are considered expired.
+    pub fn get(&self) -> Option<Statuses> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now().duration_since(modified).ok()?;
+        if age > self.ttl {
+            return None;
+        }
+
+        let content = fs::read(&self.path).ok()?;
+        match serde_json::from_slice(&content) {
+            Ok(statuses) => Some(statuses),
+            Err(err) => {
+                debug!("cannot parse status cache at {:?}: {err}", self.path);
+                None
+            }
+        }
+    }
+
+    pub fn set(&self, statuses: &Statuses) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create status cache directory {dir:?}"))?;
+        }
+
+        let content = serde_json::to_vec(statuses).context("cannot serialize status cache")?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("cannot write status cache at {:?}", self.path))
+    }
+}
@@ -0,0 +1,269 @@
+use comfy_table::{Cell, Color, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    envelope::column::EnvelopeColumnKind,
+    flag::{Flag, Flags},
+};
+
+use super::{cell, ThemeAttribute, ThemeColor, ThemePreset};
+
+/// Represents the theme configuration of tables.
+///
+/// The theme can be defined globally and per account. Account
+/// options take precedence over global ones.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// The table preset, which defines borders and separators.
+    pub preset: Option<ThemePreset>,
+    /// The attributes of table headers.
+    pub header: Option<Vec<ThemeAttribute>>,
+    /// The attributes of seen envelopes.
+    pub seen: Option<Vec<ThemeAttribute>>,
+    /// The attributes of unseen envelopes.
+    pub unseen: Option<Vec<ThemeAttribute>>,
+    /// The colors of envelope columns.
+    pub envelope: Option<HashMap<EnvelopeColumnKind, ThemeColor>>,
+    /// The colors of folder columns.
+    pub folder: Option<FolderThemeConfig>,
+    /// The colors of account columns.
+    pub account: Option<AccountThemeConfig>,
+    /// The glyphs of envelope flags.
+    pub flags: Option<FlagGlyphsConfig>,
+}
+
+impl ThemeConfig {
+    /// Merge the given global and account themes, account options
+    /// taking precedence over global ones.
+    pub fn merge(global: Option<&Self>, account: Option<&Self>) -> Self {
+        let (global, account) = match (global, account) {
+            (None, None) => return Self::default(),
+            (Some(theme), None) | (None, Some(theme)) => return theme.clone(),
+            (Some(global), Some(account)) => (global, account),
+        };
+
+        Self {
+            preset: account.preset.or(global.preset),
+            header: account.header.clone().or_else(|| global.header.clone()),
+            seen: account.seen.clone().or_else(|| global.seen.clone()),
+            unseen: account.unseen.clone().or_else(|| global.unseen.clone()),
+            envelope: merge_maps(&global.envelope, &account.envelope),
+            folder: match (&global.folder, &account.folder) {
+                (Some(global), Some(account)) => Some(FolderThemeConfig {
+                    name: account.name.or(global.name),
+                    desc: account.desc.or(global.desc),
+                }),
+                (global, account) => account.clone().or_else(|| global.clone()),
+            },
+            account: match (&global.account, &account.account) {
+                (Some(global), Some(account)) => Some(AccountThemeConfig {
+                    name: account.name.or(global.name),
+                    backend: account.backend.or(global.backend),
+                    default: account.default.or(global.default),
+                }),
+                (global, account) => account.clone().or_else(|| global.clone()),
+            },
+            flags: match (&global.flags, &account.flags) {
+                (Some(global), Some(account)) => Some(FlagGlyphsConfig {
+                    seen: account.seen.clone().or_else(|| global.seen.clone()),
+                    unseen: account.unseen.clone().or_else(|| global.unseen.clone()),
+                    answered: account.answered.clone().or_else(|| global.answered.clone()),
+                    flagged: account.flagged.clone().or_else(|| global.flagged.clone()),
+                    draft: account.draft.clone().or_else(|| global.draft.clone()),
+                    deleted: account.deleted.clone().or_else(|| global.deleted.clone()),
+                    custom: account.custom.clone().or_else(|| global.custom.clone()),
+                    keywords: merge_maps(&global.keywords, &account.keywords),
+                }),
+                (global, account) => account.clone().or_else(|| global.clone()),
+            },
+        }
+    }
+
+    pub fn preset(&self) -> &'static str {
+        self.preset.unwrap_or_default().as_preset()
+    }
+
+    /// Build the header row of a table from the given titles.
+    pub fn header<'a>(&self, titles: impl IntoIterator<Item = &'a str>) -> Row {
+        let attrs = self.header.as_deref().unwrap_or(&[ThemeAttribute::Reverse]);
+        Row::from(titles.into_iter().map(|title| cell(title, None, attrs)))
+    }
+
+    /// Return the attributes of envelopes having the given flags.
+    pub fn envelope_attributes(&self, flags: &Flags) -> &[ThemeAttribute] {
+        if flags.contains(&Flag::Seen) {
+            self.seen.as_deref().unwrap_or(&[])
+        } else {
+            self.unseen.as_deref().unwrap_or(&[ThemeAttribute::Bold])
+        }
+    }
+
+    pub fn envelope_color(&self, kind: &EnvelopeColumnKind) -> Color {
+        let color = self.envelope.as_ref().and_then(|colors| colors.get(kind));
+
+        match color {
+            Some(color) => color.0,
+            None => match kind {
+                EnvelopeColumnKind::Id => Color::Red,
+                EnvelopeColumnKind::Flags | EnvelopeColumnKind::Attachment => Color::White,
+                EnvelopeColumnKind::Subject => Color::Green,
+                EnvelopeColumnKind::From | EnvelopeColumnKind::To | EnvelopeColumnKind::Cc => {
+                    Color::Blue
+                }
+                EnvelopeColumnKind::Date => Color::Yellow,
+                EnvelopeColumnKind::Size | EnvelopeColumnKind::Folder => Color::Magenta,
+                EnvelopeColumnKind::Account => Color::Cyan,
+                EnvelopeColumnKind::Preview => Color::DarkGrey,
+            },
+        }
+    }
+
+    /// Render the given flags as glyphs.
+    ///
+    /// The seen, answered and flagged glyphs always take the same
+    /// place, so that flags stay aligned. Other glyphs are appended
+    /// only when their flag is present.
+    pub fn flags_glyphs(&self, flags: &Flags) -> String {
+        let default = FlagGlyphsConfig::default();
+        let glyphs = self.flags.as_ref().unwrap_or(&default);
+
+        let mut output = String::new();
+
+        let mut push_aligned = |glyph: &str, present: bool| {
+            if present {
+                output.push_str(glyph)
+            } else {
+                output.extend(glyph.chars().map(|_| ' '))
+            }
+        };
+
+        if flags.contains(&Flag::Seen) {
+            push_aligned(glyphs.seen.as_deref().unwrap_or(" "), true);
+        } else {
+            push_aligned(glyphs.unseen.as_deref().unwrap_or("✷"), true);
+        }
+
+        push_aligned(
+            glyphs.answered.as_deref().unwrap_or("↵"),
+            flags.contains(&Flag::Answered),
+        );
+
+        push_aligned(
+            glyphs.flagged.as_deref().unwrap_or("⚑"),
+            flags.contains(&Flag::Flagged),
+        );
+
+        if flags.contains(&Flag::Draft) {
+            output.push_str(glyphs.draft.as_deref().unwrap_or("✎"));
+        }
+
+        if flags.contains(&Flag::Deleted) {
+            output.push_str(glyphs.deleted.as_deref().unwrap_or("✗"));
+        }
+
+        let mut keywords: Vec<_> = flags
+            .iter()
+            .filter_map(|flag| match flag {
+                Flag::Custom(keyword) => Some(keyword.as_str()),
+                _ => None,
+            })
+            .collect();
+        keywords.sort();
+
+        for keyword in keywords {
+            let glyph = glyphs
+                .keywords
+                .as_ref()
+                .and_then(|keywords| keywords.get(keyword))
+                .or(glyphs.custom.as_ref());
+
+            if let Some(glyph) = glyph {
+                output.push_str(glyph);
+            }
+        }
+
+        output
+    }
+
+    pub fn folder_row(&self, name: &str, desc: &str) -> Row {
+        let default = FolderThemeConfig::default();
+        let colors = self.folder.as_ref().unwrap_or(&default);
+
+        Row::from([
+            Cell::new(name).fg(colors.name.map(|c| c.0).unwrap_or(Color::Blue)),
+            Cell::new(desc).fg(colors.desc.map(|c| c.0).unwrap_or(Color::Green)),
+        ])
+    }
+
+    pub fn account_row(&self, name: &str, backend: &str, default: bool) -> Row {
+        let default_colors = AccountThemeConfig::default();
+        let colors = self.account.as_ref().unwrap_or(&default_colors);
+
+        Row::from([
+            Cell::new(name).fg(colors.name.map(|c| c.0).unwrap_or(Color::Green)),
+            Cell::new(backend).fg(colors.backend.map(|c| c.0).unwrap_or(Color::Blue)),
+            Cell::new(if default { "yes" } else { "" })
+                .fg(colors.default.map(|c| c.0).unwrap_or(Color::White)),
+        ])
+    }
+}
+
+/// Represents the colors of folder columns.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FolderThemeConfig {
+    pub name: Option<ThemeColor>,
+    pub desc: Option<ThemeColor>,
+}
+
+/// Represents the colors of account columns.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AccountThemeConfig {
+    pub name: Option<ThemeColor>,
+    pub backend: Option<ThemeColor>,
+    pub default: Option<ThemeColor>,
+}
+
+/// Represents the glyphs used to display envelope flags.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FlagGlyphsConfig {
+    /// The glyph of seen envelopes. Defaults to a blank.
+    pub seen: Option<String>,
+    /// The glyph of unseen envelopes. Defaults to ✷.
+    pub unseen: Option<String>,
+    /// The glyph of the answered flag. Defaults to ↵.
+    pub answered: Option<String>,
+    /// The glyph of the flagged flag. Defaults to ⚑.
+    pub flagged: Option<String>,
+    /// The glyph of the draft flag. Defaults to ✎.
+    pub draft: Option<String>,
+    /// The glyph of the deleted flag. Defaults to ✗.
+    pub deleted: Option<String>,
+    /// The glyph of custom keywords without a dedicated glyph.
+    /// Defaults to none, which hides those keywords.
+    pub custom: Option<String>,
+    /// The glyphs of custom keywords, by keyword.
+    pub keywords: Option<HashMap<String, String>>,
+}
+
+fn merge_maps<K, V>(
+    global: &Option<HashMap<K, V>>,
+    account: &Option<HashMap<K, V>>,
+) -> Option<HashMap<K, V>>
+where
+    K: Clone + Eq + std::hash::Hash,
+    V: Clone,
+{
+    match (global, account) {
+        (Some(global), Some(account)) => {
+            let mut merged = global.clone();
+            merged.extend(account.clone());
+            Some(merged)
+        }
+        (global, account) => account.clone().or_else(|| global.clone()),
+    }
+}
@@ -0,0 +1,182 @@
+pub mod config;
+
+use comfy_table::{presets, Attribute, Cell, Color};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// A table cell color.
+///
+/// Colors are written as a name (red, dark-red, grey…), as a
+/// hexadecimal RGB value (#ff8800) or as an ANSI value (208).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ThemeColor(pub Color);
+
+const COLORS: [(&str, Color); 17] = [
+    ("reset", Color::Reset),
+    ("black", Color::Black),
+    ("dark-grey", Color::DarkGrey),
+    ("red", Color::Red),
+    ("dark-red", Color::DarkRed),
+    ("green", Color::Green),
+    ("dark-green", Color::DarkGreen),
+    ("yellow", Color::Yellow),
+    ("dark-yellow", Color::DarkYellow),
+    ("blue", Color::Blue),
+    ("dark-blue", Color::DarkBlue),
+    ("magenta", Color::Magenta),
+    ("dark-magenta", Color::DarkMagenta),
+    ("cyan", Color::Cyan),
+    ("dark-cyan", Color::DarkCyan),
+    ("white", Color::White),
+    ("grey", Color::Grey),
+];
+
+impl FromStr for ThemeColor {
+    type Err = String;
+
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        let color = color.trim();
+
+        if let Some(hex) = color.strip_prefix('#') {
+            let invalid = || format!("invalid hexadecimal color {color}, expected #rrggbb");
+            if hex.len() != 6 {
+                return Err(invalid());
+            }
+            let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            return Ok(Self(Color::Rgb {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            }));
+        }
+
+        if let Ok(ansi) = color.parse::<u8>() {
+            return Ok(Self(Color::AnsiValue(ansi)));
+        }
+
+        COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(color))
+            .map(|(_, color)| Self(*color))
+            .ok_or_else(|| {
+                let names: Vec<_> = COLORS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "invalid color {color}, expected #rrggbb, an ANSI value or one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(color: String) -> Result<Self, Self::Error> {
+        color.parse()
+    }
+}
+
+impl From<ThemeColor> for String {
+    fn from(color: ThemeColor) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for ThemeColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Color::Rgb { r, g, b } => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+            Color::AnsiValue(ansi) => write!(f, "{ansi}"),
+            color => {
+                let name = COLORS
+                    .iter()
+                    .find(|(_, c)| *c == color)
+                    .map(|(name, _)| *name)
+                    .unwrap_or("reset");
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+/// A table cell text attribute.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeAttribute {
+    Bold,
+    Dim,
+    Italic,
+    Underlined,
+    SlowBlink,
+    Reverse,
+    Hidden,
+    CrossedOut,
+}
+
+impl From<ThemeAttribute> for Attribute {
+    fn from(attr: ThemeAttribute) -> Self {
+        match attr {
+            ThemeAttribute::Bold => Attribute::Bold,
+            ThemeAttribute::Dim => Attribute::Dim,
+            ThemeAttribute::Italic => Attribute::Italic,
+            ThemeAttribute::Underlined => Attribute::Underlined,
+            ThemeAttribute::SlowBlink => Attribute::SlowBlink,
+            ThemeAttribute::Reverse => Attribute::Reverse,
+            ThemeAttribute::Hidden => Attribute::Hidden,
+            ThemeAttribute::CrossedOut => Attribute::CrossedOut,
+        }
+    }
+}
+
+/// The table preset, which defines borders and separators.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    /// No border nor separator.
+    #[default]
+    Nothing,
+    AsciiFull,
+    AsciiFullCondensed,
+    AsciiNoBorders,
+    AsciiBordersOnly,
+    AsciiBordersOnlyCondensed,
+    AsciiHorizontalOnly,
+    AsciiMarkdown,
+    Utf8Full,
+    Utf8FullCondensed,
+    Utf8NoBorders,
+    Utf8BordersOnly,
+    Utf8HorizontalOnly,
+}
+
+impl ThemePreset {
+    pub fn as_preset(&self) -> &'static str {
+        match self {
+            Self::Nothing => presets::NOTHING,
+            Self::AsciiFull => presets::ASCII_FULL,
+            Self::AsciiFullCondensed => presets::ASCII_FULL_CONDENSED,
+            Self::AsciiNoBorders => presets::ASCII_NO_BORDERS,
+            Self::AsciiBordersOnly => presets::ASCII_BORDERS_ONLY,
+            Self::AsciiBordersOnlyCondensed => presets::ASCII_BORDERS_ONLY_CONDENSED,
+            Self::AsciiHorizontalOnly => presets::ASCII_HORIZONTAL_ONLY,
+            Self::AsciiMarkdown => presets::ASCII_MARKDOWN,
+            Self::Utf8Full => presets::UTF8_FULL,
+            Self::Utf8FullCondensed => presets::UTF8_FULL_CONDENSED,
+            Self::Utf8NoBorders => presets::UTF8_NO_BORDERS,
+            Self::Utf8BordersOnly => presets::UTF8_BORDERS_ONLY,
+            Self::Utf8HorizontalOnly => presets::UTF8_HORIZONTAL_ONLY,
+        }
+    }
+}
+
+/// Build a table cell with the given color and attributes.
+pub fn cell(content: impl ToString, color: Option<Color>, attrs: &[ThemeAttribute]) -> Cell {
+    let mut cell = Cell::new(content);
+
+    if let Some(color) = color {
+        cell = cell.fg(color);
+    }
+
+    cell.add_attributes(attrs.iter().copied().map(Attribute::from).collect())
+}
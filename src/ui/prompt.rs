@@ -1,4 +1,4 @@
-use std::io;
+use std::io::{self, IsTerminal};
 
 pub(crate) fn passwd(prompt: &str) -> io::Result<String> {
     inquire::Password::new(prompt)
@@ -26,3 +26,22 @@ pub(crate) fn secret(prompt: &str) -> io::Result<String> {
             )
         })
 }
+
+pub(crate) fn confirm(prompt: &str) -> io::Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "cannot ask for confirmation from a non-interactive shell, use --yes instead",
+        ));
+    }
+
+    inquire::Confirm::new(prompt)
+        .with_default(false)
+        .prompt()
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("failed to get confirmation: {e}"),
+            )
+        })
+}
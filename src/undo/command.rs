@@ -0,0 +1,134 @@
+use clap::Parser;
+use color_eyre::Result;
+use email::backend::feature::BackendFeatureSource;
+use tracing::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig, printer::Printer,
+};
+
+use super::{Journal, JournalEntries};
+
+/// Undo the last operations.
+///
+/// When the journal option of the account is enabled, mutating
+/// operations (flags changes, copy, move and delete of messages,
+/// folder expunge) are recorded in a journal, per account. This
+/// command replays the inverse of the last operations, most recent
+/// first, where the backend allows it: flags are restored, moved and
+/// deleted messages are moved back to their folder, copies are
+/// flagged as deleted then expunged from their folder. Expunged
+/// messages cannot be restored.
+#[derive(Debug, Parser)]
+pub struct UndoCommand {
+    /// The amount of operations to undo.
+    #[arg(value_name = "N", default_value_t = 1)]
+    pub count: usize,
+
+    /// List the recorded operations, most recent first, instead of
+    /// undoing them.
+    #[arg(long, short, conflicts_with = "count")]
+    pub list: bool,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl UndoCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing undo command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let journal = Journal::new(&account_config.name)?;
+        // held until the entries are written back, so that operations
+        // recorded meanwhile by other processes are not lost
+        let _lock = journal.lock()?;
+        let mut entries = journal.entries()?;
+
+        if self.list {
+            entries.reverse();
+            return printer.print(JournalEntries::from(entries));
+        }
+
+        if entries.is_empty() {
+            return printer.print("No operation to undo.");
+        }
+
+        let add_flags_kind = toml_account_config.add_flags_kind();
+        let set_flags_kind = toml_account_config.set_flags_kind();
+        let remove_flags_kind = toml_account_config.remove_flags_kind();
+        let move_messages_kind = toml_account_config.move_messages_kind();
+        let expunge_folder_kind = toml_account_config.expunge_folder_kind();
+        let get_envelope_kind = toml_account_config.get_envelope_kind();
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            add_flags_kind
+                .into_iter()
+                .chain(set_flags_kind)
+                .chain(remove_flags_kind)
+                .chain(move_messages_kind)
+                .chain(expunge_folder_kind)
+                .chain(get_envelope_kind)
+                .chain(list_envelopes_kind),
+            |builder| {
+                builder.set_add_flags(BackendFeatureSource::Context);
+                builder.set_set_flags(BackendFeatureSource::Context);
+                builder.set_remove_flags(BackendFeatureSource::Context);
+                builder.set_move_messages(BackendFeatureSource::Context);
+                builder.set_expunge_folder(BackendFeatureSource::Context);
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+            },
+        )
+        .await?
+        .without_journal();
+
+        let mut undone = 0;
+
+        for _ in 0..self.count {
+            let Some(entry) = entries.pop() else {
+                break;
+            };
+
+            let desc = entry.describe();
+
+            if !entry.is_reversible() {
+                printer.print_log(format!("Cannot undo {desc}, skipping it"))?;
+                journal.write(&entries)?;
+                continue;
+            }
+
+            match entry.undo(&backend).await {
+                Ok(0) => printer.print_log(format!("Undone: {desc}"))?,
+                Ok(n) => printer.print_log(format!(
+                    "Undone: {desc} ({n} envelope(s) could not be restored)"
+                ))?,
+                Err(err) => {
+                    // keep the failed operation, so it can be retried
+                    entries.push(entry);
+                    journal.write(&entries)?;
+                    return Err(err.wrap_err(format!("cannot undo {desc}")));
+                }
+            }
+
+            undone += 1;
+            journal.write(&entries)?;
+        }
+
+        printer.print(format!("{undone} operation(s) successfully undone!"))
+    }
+}
@@ -0,0 +1,401 @@
+//! Module related to the undo journal.
+//!
+//! When enabled by the account configuration, mutating operations
+//! routed through the backend (flags changes, copy, move, delete and
+//! expunge) are recorded in a journal, one per account. The undo command replays the inverse of recorded
+//! operations, where the backend allows it.
+
+pub mod command;
+
+use chrono::{Local, SecondsFormat};
+use color_eyre::{
+    eyre::{bail, eyre, Context},
+    Result,
+};
+use dirs::data_dir;
+use email::flag::{Flag, Flags};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    ops,
+    path::PathBuf,
+};
+use tracing::debug;
+
+use crate::{
+    backend::Backend,
+    printer::{Print, WriteColor},
+};
+
+/// The maximum amount of entries kept in a journal. Older entries
+/// are dropped.
+const MAX_ENTRIES: usize = 100;
+
+/// Represents a recorded operation.
+///
+/// JSON schema: `{"date": string, "account": string, "folder":
+/// string, "ids": [string], "op": string, ...}`, where the remaining
+/// fields depend on the operation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// The identifier of the backend which executed the operation.
+    ///
+    /// Consecutive operations of the same backend, like batches of
+    /// a single command, are merged into one entry.
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub session: String,
+    pub date: String,
+    pub account: String,
+    pub folder: String,
+    /// The aliases of the envelopes, at the time of the operation.
+    pub ids: Vec<String>,
+    #[serde(flatten)]
+    pub op: JournalOp,
+}
+
+/// Represents the kind of a recorded operation, with the state
+/// required to reverse it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum JournalOp {
+    /// Flags of envelopes changed. Previous flags are indexed by
+    /// alias, envelopes whose flags could not be read are missing.
+    Flags { prev: BTreeMap<String, Vec<String>> },
+    /// Messages copied to the target folder. The new aliases follow
+    /// the order of ids, unknown ones are unset.
+    Copy {
+        target: String,
+        new_ids: Vec<Option<String>>,
+    },
+    /// Messages moved to the target folder.
+    Move {
+        target: String,
+        new_ids: Vec<Option<String>>,
+    },
+    /// Messages deleted. When the trash is unset, the deleted flag
+    /// was added to envelopes, otherwise messages were moved to the
+    /// trash.
+    Delete {
+        trash: Option<String>,
+        new_ids: Vec<Option<String>>,
+    },
+    /// Folder expunged. Expunged messages cannot be restored.
+    Expunge,
+}
+
+impl JournalEntry {
+    pub fn new(
+        session: impl ToString,
+        account: impl ToString,
+        folder: impl ToString,
+        ids: impl IntoIterator<Item = impl ToString>,
+        op: JournalOp,
+    ) -> Self {
+        Self {
+            session: session.to_string(),
+            date: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+            account: account.to_string(),
+            folder: folder.to_string(),
+            ids: ids.into_iter().map(|id| id.to_string()).collect(),
+            op,
+        }
+    }
+
+    /// Merge the given entry into this one, if both come from the
+    /// same backend and record the same operation.
+    ///
+    /// Returns the given entry back when they cannot be merged.
+    fn merge(&mut self, entry: JournalEntry) -> Option<JournalEntry> {
+        if self.session.is_empty() || self.session != entry.session || self.folder != entry.folder {
+            return Some(entry);
+        }
+
+        match (&mut self.op, entry.op) {
+            (JournalOp::Flags { prev }, JournalOp::Flags { prev: next }) => {
+                // keep the oldest known flags
+                for (id, flags) in next {
+                    prev.entry(id).or_insert(flags);
+                }
+            }
+            (
+                JournalOp::Copy { target, new_ids },
+                JournalOp::Copy {
+                    target: next_target,
+                    new_ids: next_ids,
+                },
+            )
+            | (
+                JournalOp::Move { target, new_ids },
+                JournalOp::Move {
+                    target: next_target,
+                    new_ids: next_ids,
+                },
+            ) if *target == next_target => new_ids.extend(next_ids),
+            (
+                JournalOp::Delete { trash, new_ids },
+                JournalOp::Delete {
+                    trash: next_trash,
+                    new_ids: next_ids,
+                },
+            ) if *trash == next_trash => new_ids.extend(next_ids),
+            (_, op) => return Some(JournalEntry { op, ..entry }),
+        }
+
+        self.ids.extend(entry.ids);
+        self.date = entry.date;
+        None
+    }
+
+    /// Describe the operation.
+    pub fn describe(&self) -> String {
+        let folder = &self.folder;
+        let n = self.ids.len();
+
+        match &self.op {
+            JournalOp::Flags { .. } => format!("change flags of {n} envelope(s) from {folder}"),
+            JournalOp::Copy { target, .. } => {
+                format!("copy {n} message(s) from {folder} to {target}")
+            }
+            JournalOp::Move { target, .. } => {
+                format!("move {n} message(s) from {folder} to {target}")
+            }
+            JournalOp::Delete { .. } => format!("delete {n} message(s) from {folder}"),
+            JournalOp::Expunge => format!("expunge folder {folder}"),
+        }
+    }
+
+    /// Return `true` if the operation can be reversed.
+    pub fn is_reversible(&self) -> bool {
+        !matches!(self.op, JournalOp::Expunge)
+    }
+
+    /// Replay the inverse of the operation.
+    ///
+    /// Returns the amount of envelopes that could not be restored,
+    /// because they could not be followed or their previous state is
+    /// unknown.
+    pub async fn undo(&self, backend: &Backend) -> Result<usize> {
+        let folder = &self.folder;
+
+        match &self.op {
+            JournalOp::Flags { prev } => {
+                for (id, flags) in prev {
+                    let flags = Flags::from_iter(flags.iter().map(|f| Flag::from(f.as_str())));
+                    backend.set_flags(folder, &[parse_id(id)?], &flags).await?;
+                }
+                Ok(self.ids.len().saturating_sub(prev.len()))
+            }
+            JournalOp::Copy { target, new_ids } => {
+                // copies are removed for good, whatever the delete
+                // configuration of the account
+                let ids = parse_ids(new_ids)?;
+                if !ids.is_empty() {
+                    backend.add_flag(target, &ids, Flag::Deleted).await?;
+                    backend.expunge_folder(target).await?;
+                }
+                Ok(self.ids.len().saturating_sub(ids.len()))
+            }
+            JournalOp::Move { target, new_ids }
+            | JournalOp::Delete {
+                trash: Some(target),
+                new_ids,
+            } => {
+                let ids = parse_ids(new_ids)?;
+                if !ids.is_empty() {
+                    backend.move_messages(target, folder, &ids).await?;
+                }
+                Ok(self.ids.len().saturating_sub(ids.len()))
+            }
+            JournalOp::Delete { trash: None, .. } => {
+                let ids: Result<Vec<_>> = self.ids.iter().map(|id| parse_id(id)).collect();
+                backend.remove_flag(folder, &ids?, Flag::Deleted).await?;
+                Ok(0)
+            }
+            JournalOp::Expunge => {
+                bail!("cannot undo expunge of folder {folder}: messages are permanently removed")
+            }
+        }
+    }
+}
+
+/// Parse the known aliases among the given ones.
+fn parse_ids<'a>(ids: impl IntoIterator<Item = &'a Option<String>>) -> Result<Vec<usize>> {
+    ids.into_iter().flatten().map(|id| parse_id(id)).collect()
+}
+
+fn parse_id(id: &str) -> Result<usize> {
+    id.parse()
+        .map_err(|err| eyre!("cannot parse journal id {id}: {err}"))
+}
+
+/// Get the flags of an envelope, as they are recorded in the journal.
+pub fn to_journal_flags(flags: email::flag::Flags) -> Vec<String> {
+    let mut flags: Vec<_> = crate::flag::Flags::from(flags)
+        .iter()
+        .map(|flag| flag.as_str().to_owned())
+        .collect();
+    flags.sort();
+    flags
+}
+
+/// Represents the entries of a journal, most recent first.
+///
+/// JSON schema: an array of journal entries.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct JournalEntries(Vec<JournalEntry>);
+
+impl From<Vec<JournalEntry>> for JournalEntries {
+    fn from(entries: Vec<JournalEntry>) -> Self {
+        Self(entries)
+    }
+}
+
+impl ops::Deref for JournalEntries {
+    type Target = Vec<JournalEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Print for JournalEntries {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        if self.is_empty() {
+            writeln!(writer, "No operation to undo.")?;
+        }
+
+        for (i, entry) in self.iter().enumerate() {
+            writeln!(writer, "{}. {} {}", i + 1, entry.date, entry.describe())?;
+        }
+
+        Ok(writer.reset()?)
+    }
+}
+
+/// Represents the journal of an account, stored as one JSON entry
+/// per line.
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(account: &str) -> Result<Self> {
+        let digest = md5::compute(account);
+        let path = data_dir()
+            .ok_or(eyre!("cannot get XDG data directory"))?
+            .join("himalaya")
+            .join("journal")
+            .join(format!("{digest:x}.jsonl"));
+
+        Ok(Self { path })
+    }
+
+    /// Read the entries of the journal, oldest first.
+    ///
+    /// Unparsable entries are ignored.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("cannot read journal at {:?}", self.path))?;
+
+        let entries = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    debug!("cannot parse journal entry {line}: {err}");
+                    None
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Lock the journal against other processes, until the returned
+    /// lock file is dropped.
+    ///
+    /// Entries read then written back must be read under the lock.
+    pub fn lock(&self) -> Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create journal directory {dir:?}"))?;
+        }
+
+        let path = self.path.with_extension("lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("cannot open journal lock at {path:?}"))?;
+        file.lock_exclusive()
+            .with_context(|| format!("cannot lock journal at {:?}", self.path))?;
+
+        Ok(file)
+    }
+
+    /// Record the given entry, merging it into the last one when
+    /// possible.
+    ///
+    /// The journal is locked while it is read then written, so that
+    /// concurrent processes do not lose entries.
+    pub fn append(&self, entry: JournalEntry) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+
+        let entry = match entries.last_mut() {
+            Some(last) => last.merge(entry),
+            None => Some(entry),
+        };
+
+        match entry {
+            Some(entry) => entries.push(entry),
+            // the last entry changed
+            None => return self.write(&entries),
+        }
+
+        if entries.len() > MAX_ENTRIES {
+            let excess = entries.len() - MAX_ENTRIES;
+            entries.drain(..excess);
+            return self.write(&entries);
+        }
+
+        let mut line = serde_json::to_string(&entries[entries.len() - 1])
+            .context("cannot serialize journal entry")?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("cannot write journal at {:?}", self.path))
+    }
+
+    /// Replace the entries of the journal.
+    pub fn write(&self, entries: &[JournalEntry]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create journal directory {dir:?}"))?;
+        }
+
+        let mut content = String::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry).context("cannot serialize journal entry")?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        fs::write(&self.path, content)
+            .with_context(|| format!("cannot write journal at {:?}", self.path))
+    }
+}